@@ -1,22 +1,81 @@
-use clap::{ArgAction, CommandFactory, Parser, Subcommand};
+use clap::{ArgAction, Args, CommandFactory, Parser, Subcommand};
 use clap_complete::{Shell, generate};
+use log::{LevelFilter, error, warn};
+use std::env;
 use std::io;
-use stool_core::config::Config;
-use stool_core::error::Result;
-use stool_modules::{aws, docker, filesystem, ssh, transfer, update};
+use std::process::ExitCode;
+use stool_core::config::execution::ExecutionConfig;
+use stool_core::config::{Config, Context, ServerFilter};
+use stool_core::error::{Result, StoolErrorType};
+use stool_modules::export::{self, ExportFormat};
+use stool_modules::ssh::Destination;
+use stool_modules::{
+    aws, config, context, docker, entries, filesystem, history, ssh, transfer, update, vault,
+};
+use stool_utils::runner::{self, DryRunRunner};
+use stool_utils::{askpass, logger};
+
+/// Environment variable that enables the log file like `--log-file`.
+const LOG_FILE_ENV_VAR: &str = "STOOL_LOG_FILE";
+
+/// Entries shown by `stool history` without a subcommand.
+const DEFAULT_HISTORY_LIMIT: usize = 20;
 
 #[derive(Parser)]
 #[command(name = "stool")]
 #[command(version)]
 #[command(about = "seokjin's CLI tool for Mac/Linux terminal tasks", long_about = None)]
 #[command(arg_required_else_help = true)]
+#[command(disable_version_flag = true, disable_help_flag = true)]
 struct Cli {
-    #[arg(short = 'v', short_alias = 'V', long, action = ArgAction::Version)]
+    #[arg(short = 'V', long, action = ArgAction::Version, help = "Print version")]
     version: Option<bool>,
 
-    #[arg(short = 'h', short_alias = 'H', long, action = ArgAction::Help)]
+    #[arg(
+        short = 'h',
+        short_alias = 'H',
+        long,
+        global = true,
+        action = ArgAction::Help,
+        help = "Print help"
+    )]
     help: Option<bool>,
 
+    #[arg(
+        short,
+        long,
+        global = true,
+        help = "Config file layered over $STOOL_CONFIG, user and embedded config"
+    )]
+    config: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Print external commands (docker, aws, ssh, scp, ...) instead of running them"
+    )]
+    dry_run: bool,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = ArgAction::Count,
+        conflicts_with = "quiet",
+        help = "Show debug detail, including every external command (-vv for trace)"
+    )]
+    verbose: u8,
+
+    #[arg(short, long, global = true, help = "Only show warnings and errors")]
+    quiet: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Also append debug logs to $XDG_STATE_HOME/stool/stool.log (or set STOOL_LOG_FILE=1)"
+    )]
+    log_file: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -26,15 +85,16 @@ enum Commands {
     #[command(
         short_flag = 's',
         about = "SSH connection",
-        long_about = "Connect to remote servers via SSH with multiple authentication methods\n\nFeatures:\n  - Server selection from config or manual input\n  - PEM key authentication\n  - Password authentication with expect\n  - Password prompt with masked input\n  - Default SSH authentication (ssh-agent, ~/.ssh/config)"
+        long_about = "Connect to remote servers via SSH with multiple authentication methods\n\nFeatures:\n  - Server selection from config or manual input\n  - PEM key authentication\n  - Password authentication (stool serves the password to ssh)\n  - Password prompt with masked input\n  - Default SSH authentication (ssh-agent, ~/.ssh/config)\n  - Filter servers by --group, --tag and --env\n  - Connect directly by name: exact, prefix or fuzzy match (menu if ambiguous)\n  - --ip/--user override the server's host and user, or give an ad-hoc host\n\nCommands:\n  exec - Run one command (or a script from stdin) and exit with its exit code",
+        args_conflicts_with_subcommands = true
     )]
     Ssh {
-        #[arg(
-            short,
-            long,
-            help = "External config file (default: embedded config.yaml)"
-        )]
-        config: Option<String>,
+        #[command(subcommand)]
+        command: Option<Box<SshCommands>>,
+        #[command(flatten)]
+        destination: DestinationArgs,
+        #[command(flatten)]
+        filter: FilterArgs,
     },
     #[command(
         short_flag = 'u',
@@ -59,15 +119,11 @@ enum Commands {
     #[command(
         short_flag = 't',
         about = "File transfer (scp)",
-        long_about = "Transfer files between local and remote systems via SCP\n\nFeatures:\n  - Upload/Download support\n  - Server selection from config or manual input\n  - Tab completion for local file paths\n  - Default paths: Upload(~/), Download(~/Downloads/)\n  - Same authentication methods as SSH"
+        long_about = "Transfer files between local and remote systems via SCP\n\nFeatures:\n  - Upload/Download support\n  - Server selection from config or manual input\n  - Tab completion for local file paths\n  - Default paths: Upload(~/), Download(~/Downloads/)\n  - Same authentication methods as SSH\n  - Filter servers by --group, --tag and --env"
     )]
     Transfer {
-        #[arg(
-            short,
-            long,
-            help = "External config file (default: embedded config.yaml)"
-        )]
-        config: Option<String>,
+        #[command(flatten)]
+        filter: FilterArgs,
     },
     #[command(
         short_flag = 'd',
@@ -87,11 +143,266 @@ enum Commands {
         #[command(subcommand)]
         command: AwsCommands,
     },
+    #[command(
+        about = "Inspect and edit configuration",
+        long_about = "Inspect and edit the resolved configuration\n\nCommands:\n  show       - Print merged config (passwords masked)\n  path       - List config layers and which were used\n  validate   - Validate the resolved config\n  edit       - Edit the config file in $EDITOR (validated before saving)\n  import-ssh - Import hosts from ~/.ssh/config as servers\n  server     - Add, edit or remove servers\n  registry   - Add or remove ECR registries\n  sso        - Add or remove SSO configs\n  migrate    - Upgrade config files to the current schema version\n  export     - Export servers as ssh config, Ansible inventory or CSV"
+    )]
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    #[command(
+        about = "Switch between named contexts",
+        long_about = "Named sets of defaults (server group, ECR registry, SSO profile) defined under contexts: in config\n\nCommands:\n  use   - Select a context\n  list  - List contexts (* marks the active one)\n  show  - Show the active context\n  clear - Clear the selection\n\nSet STOOL_CONTEXT to override the selection for one invocation"
+    )]
+    Context {
+        #[command(subcommand)]
+        command: ContextCommands,
+    },
+    #[command(
+        about = "Encrypted secrets vault",
+        long_about = "Passphrase-encrypted secrets referenced from config as vault:NAME\n\nCommands:\n  init - Create a new vault\n  set  - Store a secret\n  get  - Print a secret\n  list - List secret names\n  rm   - Remove a secret\n\nSet STOOL_VAULT_PASSPHRASE to skip the passphrase prompt"
+    )]
+    Vault {
+        #[command(subcommand)]
+        command: VaultCommands,
+    },
+    #[command(
+        about = "Operation history and re-run",
+        long_about = "Completed ssh, transfer, docker, ECR login and update operations, recorded in $XDG_STATE_HOME/stool/history.jsonl\n\nCommands:\n  list  - List recent entries, optionally matching a search (default)\n  rerun - Replay an entry without prompting"
+    )]
+    History {
+        #[command(subcommand)]
+        command: Option<HistoryCommands>,
+    },
     #[command(about = "Generate shell completion script")]
     Completion {
         #[arg(value_enum, help = "Shell type (bash, zsh, fish, powershell)")]
         shell: Shell,
     },
+    #[command(
+        hide = true,
+        about = "Answer ssh password prompts (run by ssh as SSH_ASKPASS)"
+    )]
+    Askpass {
+        #[arg(allow_hyphen_values = true, help = "Prompt shown by ssh")]
+        prompt: Option<String>,
+    },
+}
+
+/// Server named on the command line instead of chosen from the menu.
+#[derive(Args)]
+struct DestinationArgs {
+    #[arg(help = "Server name (exact, prefix or fuzzy match)")]
+    name: Option<String>,
+    #[arg(
+        long,
+        help = "Connect to this host instead (ad-hoc host without a name)"
+    )]
+    ip: Option<String>,
+    #[arg(long, help = "Log in as this user instead")]
+    user: Option<String>,
+}
+
+impl From<DestinationArgs> for Destination {
+    fn from(args: DestinationArgs) -> Self {
+        Self {
+            name: args.name,
+            ip: args.ip,
+            user: args.user,
+        }
+    }
+}
+
+/// Server filter options shared by server commands.
+#[derive(Args)]
+struct FilterArgs {
+    #[arg(short, long, help = "Only servers in this group")]
+    group: Option<String>,
+    #[arg(
+        long = "tag",
+        value_name = "TAG",
+        help = "Only servers with this tag (repeatable)"
+    )]
+    tags: Vec<String>,
+    #[arg(
+        short,
+        long = "env",
+        value_name = "ENV",
+        help = "Only servers in this environment"
+    )]
+    environment: Option<String>,
+}
+
+impl From<FilterArgs> for ServerFilter {
+    fn from(args: FilterArgs) -> Self {
+        Self {
+            group: args.group,
+            tags: args.tags,
+            environment: args.environment,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    #[command(about = "Print merged config with passwords masked")]
+    Show,
+    #[command(about = "List config layers and which were used")]
+    Path,
+    #[command(about = "Validate the resolved config")]
+    Validate,
+    #[command(about = "Edit the highest-priority config file in $EDITOR")]
+    Edit,
+    #[command(about = "Import hosts from ~/.ssh/config as servers")]
+    ImportSsh {
+        #[arg(short, long, help = "ssh config file (default: ~/.ssh/config)")]
+        file: Option<String>,
+        #[arg(short, long, help = "Append to the config file instead of printing")]
+        write: bool,
+    },
+    #[command(about = "Add, edit or remove servers")]
+    Server {
+        #[command(subcommand)]
+        command: ServerCommands,
+    },
+    #[command(about = "Add or remove ECR registries")]
+    Registry {
+        #[command(subcommand)]
+        command: EntryCommands,
+    },
+    #[command(about = "Add or remove SSO configs")]
+    Sso {
+        #[command(subcommand)]
+        command: EntryCommands,
+    },
+    #[command(about = "Upgrade config files to the current schema version")]
+    Migrate {
+        #[arg(short, long, help = "Rewrite without asking for confirmation")]
+        yes: bool,
+    },
+    #[command(about = "Export servers as ssh config, Ansible inventory or CSV")]
+    Export {
+        #[arg(short, long, value_parser = ExportFormat::NAMES, help = "Output format")]
+        format: String,
+        #[arg(long, help = "Resolve and include passwords (not for ssh-config)")]
+        include_passwords: bool,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum ServerCommands {
+    #[command(about = "Add a server")]
+    Add,
+    #[command(about = "Edit a server")]
+    Edit {
+        #[arg(help = "Server name (selected from a menu if omitted)")]
+        name: Option<String>,
+    },
+    #[command(alias = "remove", about = "Remove a server")]
+    Rm {
+        #[arg(help = "Server name (selected from a menu if omitted)")]
+        name: Option<String>,
+        #[arg(short, long, help = "Remove without asking for confirmation")]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum EntryCommands {
+    #[command(about = "Add an entry")]
+    Add,
+    #[command(alias = "remove", about = "Remove an entry")]
+    Rm {
+        #[arg(help = "Entry name (selected from a menu if omitted)")]
+        name: Option<String>,
+        #[arg(short, long, help = "Remove without asking for confirmation")]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ContextCommands {
+    #[command(about = "Select a context")]
+    Use {
+        #[arg(help = "Context name")]
+        name: String,
+    },
+    #[command(alias = "ls", about = "List contexts")]
+    List,
+    #[command(about = "Show the active context")]
+    Show,
+    #[command(about = "Clear the selected context")]
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum VaultCommands {
+    #[command(about = "Create a new vault")]
+    Init,
+    #[command(about = "Store a secret")]
+    Set {
+        #[arg(help = "Secret name")]
+        name: String,
+        #[arg(long, help = "Read the value from stdin instead of prompting")]
+        stdin: bool,
+    },
+    #[command(about = "Print a secret")]
+    Get {
+        #[arg(help = "Secret name")]
+        name: String,
+    },
+    #[command(alias = "ls", about = "List secret names")]
+    List,
+    #[command(alias = "remove", about = "Remove a secret")]
+    Rm {
+        #[arg(help = "Secret name")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SshCommands {
+    #[command(
+        about = "Run a command on a server",
+        long_about = "Run one command on a server with its configured authentication\n\nThe command goes after `--`; without one, a script piped on stdin is run with `sh -s`. stool exits with the remote command's exit code.\n\nExamples:\n  stool ssh exec web-1 -- uptime\n  stool ssh exec web-1 -t -- sudo systemctl restart nginx\n  stool ssh exec web-1 < deploy.sh"
+    )]
+    Exec {
+        #[command(flatten)]
+        destination: DestinationArgs,
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[arg(
+            short,
+            long,
+            help = "Allocate a pseudo-terminal (for sudo prompts or full-screen programs)"
+        )]
+        tty: bool,
+        #[arg(
+            last = true,
+            value_name = "COMMAND",
+            help = "Remote command (after --)"
+        )]
+        command: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum HistoryCommands {
+    #[command(alias = "ls", about = "List recent entries")]
+    List {
+        #[arg(help = "Only entries containing this text (case-insensitive)")]
+        query: Option<String>,
+        #[arg(short = 'n', long, default_value_t = DEFAULT_HISTORY_LIMIT, help = "Number of entries shown")]
+        limit: usize,
+    },
+    #[command(about = "Replay an entry without prompting")]
+    Rerun {
+        #[arg(help = "Entry id shown by `stool history`")]
+        id: usize,
+    },
 }
 
 #[derive(Subcommand)]
@@ -116,26 +427,12 @@ enum DockerCommands {
         about = "Build Docker image",
         long_about = "Build Docker image with platform options (linux/arm64, --provenance=false, --sbom=false)\nImage name can be selected from config or manually entered"
     )]
-    Build {
-        #[arg(
-            short,
-            long,
-            help = "External config file (default: embedded config.yaml)"
-        )]
-        config: Option<String>,
-    },
+    Build,
     #[command(
         about = "Build and push Docker image to ECR",
         long_about = "Build, tag, and push Docker image to AWS ECR with automatic version management\n\nWorkflow:\n1. Select ECR registry\n2. Select or input image name\n3. Build with standard options\n4. Select version type (major/middle/minor)\n5. Tag and push both 'latest' and version tags"
     )]
-    Push {
-        #[arg(
-            short,
-            long,
-            help = "External config file (default: embedded config.yaml)"
-        )]
-        config: Option<String>,
-    },
+    Push,
 }
 
 #[derive(Subcommand)]
@@ -147,51 +444,81 @@ enum AwsCommands {
     )]
     Configure,
     #[command(about = "Configure AWS SSO (aws configure sso)")]
-    Sso {
-        #[arg(
-            short,
-            long,
-            help = "External config file (default: embedded config.yaml)"
-        )]
-        config: Option<String>,
-    },
+    Sso,
     #[command(about = "SSO login/token refresh (aws sso login)")]
-    Login {
-        #[arg(
-            short,
-            long,
-            help = "External config file (default: embedded config.yaml)"
-        )]
-        config: Option<String>,
-    },
+    Login,
     #[command(about = "Login to AWS ECR registry")]
-    Ecr {
-        #[arg(
-            short,
-            long,
-            help = "External config file (default: embedded config.yaml)"
-        )]
-        config: Option<String>,
-    },
+    Ecr,
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // Cancelling is a deliberate choice, not a failure worth reporting
+            if e.error_type != StoolErrorType::Cancelled {
+                error!("{}", e.report());
+            }
+            ExitCode::from(e.exit_code())
+        }
+    }
 }
 
-fn main() -> Result<()> {
+fn run() -> Result<()> {
     let cli = Cli::parse();
+    let level = match (cli.quiet, cli.verbose) {
+        (true, _) => LevelFilter::Warn,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+    let log_file =
+        cli.log_file || env::var(LOG_FILE_ENV_VAR).is_ok_and(|v| !v.is_empty() && v != "0");
+    if let Err(e) = logger::init(level, log_file) {
+        warn!("{}", e.report());
+    }
+    log::debug!(
+        "stool {} {:?}",
+        env!("CARGO_PKG_VERSION"),
+        env::args().skip(1).collect::<Vec<_>>()
+    );
+    if cli.dry_run {
+        runner::set_default(Box::new(DryRunRunner));
+    }
 
     match cli.command {
-        Some(Commands::Ssh { config }) => {
-            let cfg = if let Some(path) = config {
-                Config::load(&path)?
-            } else {
-                Config::load_embedded()?
-            };
-            ssh::connect(&cfg.servers)?;
+        Some(Commands::Ssh {
+            command,
+            destination,
+            filter,
+        }) => {
+            let cfg = load_config(cli.config.as_deref())?;
+            match command.map(|c| *c) {
+                Some(SshCommands::Exec {
+                    destination,
+                    filter,
+                    tty,
+                    command,
+                }) => ssh::exec(
+                    &cfg.servers,
+                    &destination.into(),
+                    &filter.into(),
+                    active_context(&cfg),
+                    &command,
+                    tty,
+                )?,
+                None => ssh::connect(
+                    &cfg.servers,
+                    &destination.into(),
+                    &filter.into(),
+                    active_context(&cfg),
+                )?,
+            }
+        }
+        Some(Commands::Update { brew, rustup }) => {
+            let execution = execution_config(cli.config.as_deref());
+            update::run(brew, rustup, &execution)?;
         }
-        Some(Commands::Update { brew, rustup }) => match (brew, rustup) {
-            (true, false) => update::update_brew()?,
-            (false, true) => update::update_rustup()?,
-            _ => update::update_all()?,
-        },
         Some(Commands::Filesystem { command }) => match command {
             FilesystemCommands::Find { pattern, path } => {
                 filesystem::find(&pattern, path.as_deref())?;
@@ -200,67 +527,152 @@ fn main() -> Result<()> {
                 filesystem::count(path.as_deref())?;
             }
         },
-        Some(Commands::Transfer { config }) => {
-            let cfg = if let Some(path) = config {
-                Config::load(&path)?
-            } else {
-                Config::load_embedded()?
-            };
-            transfer::transfer(&cfg.servers)?;
+        Some(Commands::Transfer { filter }) => {
+            let cfg = load_config(cli.config.as_deref())?;
+            transfer::transfer(&cfg.servers, &filter.into(), active_context(&cfg))?;
         }
         Some(Commands::Docker { command }) => match command {
-            DockerCommands::Build { config } => {
-                let cfg = if let Some(path) = config {
-                    Config::load(&path)?
-                } else {
-                    Config::load_embedded()?
-                };
-                docker::build_only(&cfg.ecr_registries)?;
+            DockerCommands::Build => {
+                let cfg = load_config(cli.config.as_deref())?;
+                docker::build_only(&cfg.ecr_registries, active_context(&cfg), &cfg.execution)?;
             }
-            DockerCommands::Push { config } => {
-                let cfg = if let Some(path) = config {
-                    Config::load(&path)?
-                } else {
-                    Config::load_embedded()?
-                };
-                docker::push_to_ecr(&cfg.ecr_registries)?;
+            DockerCommands::Push => {
+                let cfg = load_config(cli.config.as_deref())?;
+                docker::push_to_ecr(&cfg.ecr_registries, active_context(&cfg), &cfg.execution)?;
             }
         },
         Some(Commands::Aws { command }) => match command {
             AwsCommands::Configure => {
                 aws::configure()?;
             }
-            AwsCommands::Sso { config } => {
-                let cfg = if let Some(path) = config {
-                    Config::load(&path)?
-                } else {
-                    Config::load_embedded()?
-                };
-                aws::sso_configure(&cfg.sso_configs)?;
+            AwsCommands::Sso => {
+                let cfg = load_config(cli.config.as_deref())?;
+                aws::sso_configure(&cfg.sso_configs, active_context(&cfg))?;
+            }
+            AwsCommands::Login => {
+                let cfg = load_config(cli.config.as_deref())?;
+                aws::sso_login(&cfg.sso_configs, active_context(&cfg))?;
+            }
+            AwsCommands::Ecr => {
+                let cfg = load_config(cli.config.as_deref())?;
+                aws::ecr_login(&cfg.ecr_registries, active_context(&cfg), &cfg.execution)?;
             }
-            AwsCommands::Login { config } => {
-                let cfg = if let Some(path) = config {
-                    Config::load(&path)?
-                } else {
-                    Config::load_embedded()?
-                };
-                aws::sso_login(&cfg.sso_configs)?;
+        },
+        Some(Commands::Config { command }) => match command {
+            ConfigCommands::Show => {
+                let cfg = Config::resolve(cli.config.as_deref())?;
+                config::show(&cfg)?;
+            }
+            ConfigCommands::Path => {
+                config::path(cli.config.as_deref())?;
+            }
+            ConfigCommands::Validate => {
+                config::validate(cli.config.as_deref())?;
+            }
+            ConfigCommands::Edit => {
+                config::edit(cli.config.as_deref())?;
+            }
+            ConfigCommands::ImportSsh { file, write } => {
+                config::import_ssh(cli.config.as_deref(), file.as_deref(), write)?;
             }
-            AwsCommands::Ecr { config } => {
-                let cfg = if let Some(path) = config {
-                    Config::load(&path)?
-                } else {
-                    Config::load_embedded()?
-                };
-                aws::ecr_login(&cfg.ecr_registries)?;
+            ConfigCommands::Server { command } => match command {
+                ServerCommands::Add => entries::server_add(cli.config.as_deref())?,
+                ServerCommands::Edit { name } => {
+                    entries::server_edit(cli.config.as_deref(), name.as_deref())?;
+                }
+                ServerCommands::Rm { name, yes } => {
+                    entries::server_remove(cli.config.as_deref(), name.as_deref(), yes)?;
+                }
+            },
+            ConfigCommands::Registry { command } => match command {
+                EntryCommands::Add => entries::registry_add(cli.config.as_deref())?,
+                EntryCommands::Rm { name, yes } => {
+                    entries::registry_remove(cli.config.as_deref(), name.as_deref(), yes)?;
+                }
+            },
+            ConfigCommands::Sso { command } => match command {
+                EntryCommands::Add => entries::sso_add(cli.config.as_deref())?,
+                EntryCommands::Rm { name, yes } => {
+                    entries::sso_remove(cli.config.as_deref(), name.as_deref(), yes)?;
+                }
+            },
+            ConfigCommands::Migrate { yes } => {
+                config::migrate(cli.config.as_deref(), yes)?;
+            }
+            ConfigCommands::Export {
+                format,
+                include_passwords,
+                filter,
+            } => {
+                let cfg = load_config(cli.config.as_deref())?;
+                export::export(&cfg, format.parse()?, include_passwords, &filter.into())?;
+            }
+        },
+        Some(Commands::Context { command }) => {
+            let cfg = load_config(cli.config.as_deref())?;
+            match command {
+                ContextCommands::Use { name } => context::use_context(&cfg, &name)?,
+                ContextCommands::List => context::list(&cfg)?,
+                ContextCommands::Show => context::show(&cfg)?,
+                ContextCommands::Clear => context::clear()?,
+            }
+        }
+        Some(Commands::Vault { command }) => match command {
+            VaultCommands::Init => vault::init()?,
+            VaultCommands::Set { name, stdin } => vault::set(&name, stdin)?,
+            VaultCommands::Get { name } => vault::get(&name)?,
+            VaultCommands::List => vault::list()?,
+            VaultCommands::Rm { name } => vault::remove(&name)?,
+        },
+        Some(Commands::History { command }) => match command {
+            None => history::list(None, DEFAULT_HISTORY_LIMIT)?,
+            Some(HistoryCommands::List { query, limit }) => history::list(query.as_deref(), limit)?,
+            Some(HistoryCommands::Rerun { id }) => {
+                let cfg = load_config(cli.config.as_deref())?;
+                history::rerun(&cfg, id)?;
             }
         },
         Some(Commands::Completion { shell }) => {
             let mut cmd = Cli::command();
             generate(shell, &mut cmd, "stool", &mut io::stdout());
         }
+        Some(Commands::Askpass { prompt }) => askpass::respond(prompt.as_deref().unwrap_or(""))?,
         None => unreachable!("arg_required_else_help ensures a subcommand is provided"),
     }
 
     Ok(())
 }
+
+/// Resolves the config and validates it before any module uses it.
+///
+/// Warnings are printed; errors abort the command.
+fn load_config(path: Option<&str>) -> Result<Config> {
+    let cfg = Config::resolve(path)?;
+    for warning in cfg.validate().into_result()? {
+        warn!("{:#}", warning);
+    }
+    Ok(cfg)
+}
+
+/// Returns the `execution:` settings, or the defaults if the config cannot
+/// be loaded.
+///
+/// For commands that need nothing else from the config, so a broken or
+/// half-edited file does not block them; the config is not validated.
+fn execution_config(path: Option<&str>) -> ExecutionConfig {
+    match Config::resolve(path) {
+        Ok(cfg) => cfg.execution,
+        Err(e) => {
+            warn!("Using default timeouts and retries: {}", e);
+            ExecutionConfig::default()
+        }
+    }
+}
+
+/// Returns the active context, warning instead of failing if it is unusable.
+fn active_context(cfg: &Config) -> Option<&Context> {
+    cfg.active_context().unwrap_or_else(|e| {
+        warn!("{}", e);
+        None
+    })
+}
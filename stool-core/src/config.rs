@@ -1,23 +1,204 @@
 //! Configuration management for server connections.
 //!
-//! This module handles loading and parsing YAML configuration files
-//! containing server connection details (SSH, SCP).
+//! This module handles loading and parsing configuration files (YAML, TOML
+//! or JSON) containing server connection details (SSH, SCP).
+//!
+//! Configuration is resolved from several layers (embedded, user,
+//! `$STOOL_CONFIG`, `--config`) that are merged by entry name. Each layer
+//! may pull in further files via `include:`, and the user config directory
+//! may contain `config.d/*.yaml` fragments.
 
 use crate::error::{Result, StoolError, StoolErrorType};
+use crate::paths;
+use crate::ssh_config;
+use crate::state::State;
+use execution::ExecutionConfig;
+use format::ConfigFormat;
 use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt;
 use std::fs;
+use std::path::{Path, PathBuf};
+
+pub mod document;
+pub mod execution;
+pub mod format;
+mod locate;
+pub mod migration;
+pub mod validation;
 
 /// Server connection configuration.
 ///
 /// Represents a single server with authentication details.
 /// Supports multiple authentication methods via optional fields.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Server {
     pub name: String,
     pub ip: String,
     pub user: String,
+    /// Credentials used to log in.
+    #[serde(default, skip_serializing_if = "ServerAuth::is_empty")]
+    pub auth: ServerAuth,
+    /// SSH port (default: 22).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Jump host: name of another server, or a raw `[user@]host[:port]` spec.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_jump: Option<String>,
+    /// Forward the SSH agent to the server.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub forward_agent: bool,
+    /// Additional `ssh -o` options, e.g. `ServerAliveInterval: "30"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ssh_options: BTreeMap<String, String>,
+    /// Group the server is listed under in menus, e.g. a project.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Free-form labels for filtering.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Deployment environment, e.g. `prod` or `staging`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+}
+
+impl Server {
+    /// Returns the configured server that `proxy_jump` refers to by name.
+    ///
+    /// Returns `None` if there is no jump host or it is a raw host spec.
+    ///
+    /// # Arguments
+    /// * `servers` - Servers to look the jump host up in
+    pub fn jump_server<'a>(&self, servers: &'a [Server]) -> Option<&'a Server> {
+        let jump = self.proxy_jump.as_deref()?;
+        servers.iter().find(|s| s.name == jump)
+    }
+}
+
+/// Authentication settings of a server (the `auth:` block).
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ServerAuth {
+    /// Password or secret reference (`env:`, `file:`, `cmd:`), see [`crate::secret`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub password: Option<String>,
+    /// Path to a private key (PEM) file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub key_path: Option<String>,
+    /// Path to the SSH agent socket (`IdentityAgent`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_agent: Option<String>,
+}
+
+impl ServerAuth {
+    /// Returns `true` if no credentials are set.
+    pub fn is_empty(&self) -> bool {
+        self.password.is_none() && self.key_path.is_none() && self.identity_agent.is_none()
+    }
+}
+
+/// Narrows servers down by group, tags and environment.
+///
+/// Values are compared case-insensitively. A server matches if it has the
+/// given group and environment and carries every given tag; unset criteria
+/// match everything.
+#[derive(Debug, Clone, Default)]
+pub struct ServerFilter {
+    pub group: Option<String>,
+    pub tags: Vec<String>,
+    pub environment: Option<String>,
+}
+
+impl ServerFilter {
+    /// Returns `true` if no criteria are set.
+    pub fn is_empty(&self) -> bool {
+        self.group.is_none() && self.tags.is_empty() && self.environment.is_none()
+    }
+
+    /// Returns `true` if the server satisfies every criterion.
+    pub fn matches(&self, server: &Server) -> bool {
+        let same = |want: &Option<String>, have: &Option<String>| {
+            want.as_ref()
+                .is_none_or(|w| have.as_ref().is_some_and(|h| h.eq_ignore_ascii_case(w)))
+        };
+        same(&self.group, &server.group)
+            && same(&self.environment, &server.environment)
+            && self
+                .tags
+                .iter()
+                .all(|t| server.tags.iter().any(|st| st.eq_ignore_ascii_case(t)))
+    }
+
+    /// Returns the matching servers in their original order.
+    pub fn apply<'a>(&self, servers: &'a [Server]) -> Vec<&'a Server> {
+        servers.iter().filter(|s| self.matches(s)).collect()
+    }
+}
+
+impl fmt::Display for ServerFilter {
+    /// Formats the criteria as `group=x, env=y, tag=z`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(group) = &self.group {
+            parts.push(format!("group={}", group));
+        }
+        if let Some(env) = &self.environment {
+            parts.push(format!("env={}", env));
+        }
+        for tag in &self.tags {
+            parts.push(format!("tag={}", tag));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Outcome of looking a server up by name, see [`match_server_name`].
+#[derive(Debug)]
+pub enum NameMatch<'a> {
+    /// One server matched better than all others.
+    Unique(&'a Server),
+    /// Several servers matched equally well, in their original order.
+    Ambiguous(Vec<&'a Server>),
+    /// No server matched.
+    None,
+}
+
+/// Looks a server up by name: exactly, by prefix, by substring, then fuzzily.
+///
+/// Names are compared case-insensitively. The fuzzy level accepts names
+/// containing the query's characters in order, so `papi` finds `prod-api`.
+/// The first level with any match decides the outcome.
+///
+/// # Arguments
+/// * `servers` - Candidate servers, e.g. already narrowed by a [`ServerFilter`]
+/// * `query` - Name typed by the user
+pub fn match_server_name<'a>(servers: &[&'a Server], query: &str) -> NameMatch<'a> {
+    let query = query.to_lowercase();
+    let is_subsequence = |name: &str| {
+        let mut chars = name.chars();
+        query.chars().all(|q| chars.any(|c| c == q))
+    };
+    let levels: [&dyn Fn(&str) -> bool; 4] = [
+        &|name| name == query,
+        &|name| name.starts_with(&query),
+        &|name| name.contains(&query),
+        &is_subsequence,
+    ];
+
+    for level in levels {
+        let found: Vec<&Server> = servers
+            .iter()
+            .copied()
+            .filter(|s| level(&s.name.to_lowercase()))
+            .collect();
+        match found.len() {
+            0 => continue,
+            1 => return NameMatch::Unique(found[0]),
+            _ => return NameMatch::Ambiguous(found),
+        }
+    }
+    NameMatch::None
 }
 
 /// ECR registry configuration.
@@ -31,6 +212,7 @@ pub struct EcrRegistry {
     #[serde(default)]
     pub images: Vec<String>,
     /// SSO profile name. If set, uses SSO authentication for ECR login.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub sso_profile: Option<String>,
 }
 
@@ -55,36 +237,314 @@ fn default_output_format() -> String {
     "json".to_string()
 }
 
+/// Named set of defaults selected with `stool context use`.
+///
+/// Pre-selects the matching entries in server, registry and SSO menus.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Context {
+    pub name: String,
+    /// Server group pre-selected in server menus.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Name of the ECR registry pre-selected in registry menus.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ecr_registry: Option<String>,
+    /// SSO profile pre-selected in profile menus.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sso_profile: Option<String>,
+}
+
+/// Source of the active context name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextSource {
+    /// `$STOOL_CONTEXT`
+    Env,
+    /// State file written by `stool context use`.
+    State,
+}
+
+/// Environment variable naming an additional config file.
+pub const CONFIG_ENV_VAR: &str = "STOOL_CONFIG";
+
+/// Environment variable overriding the active context for one invocation.
+pub const CONTEXT_ENV_VAR: &str = "STOOL_CONTEXT";
+
+/// File name of the user config inside the config directory.
+///
+/// `config.yml`, `config.toml` and `config.json` are used instead if one of
+/// them exists and `config.yaml` does not.
+const CONFIG_FILE_NAME: &str = "config.yaml";
+
+/// Fragment directory inside the config directory.
+const FRAGMENT_DIR_NAME: &str = "config.d";
+
+/// Top-level key listing additional files to load.
+const INCLUDE_KEY: &str = "include";
+
+/// Top-level key enabling servers imported from `~/.ssh/config`.
+const IMPORT_SSH_CONFIG_KEY: &str = "import_ssh_config";
+
+/// Index of the layer that last defined each named entry.
+type Origins = HashMap<(Section, String), usize>;
+
+/// Group of the synthetic layer holding imported ssh config hosts.
+const SSH_CONFIG_GROUP: usize = usize::MAX;
+
+/// Config embedded at build time from the project root.
+const EMBEDDED_CONFIG: &str = include_str!("../../config.yaml");
+
+/// Origin of a configuration layer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Config embedded into the binary at build time.
+    Embedded,
+    /// User config at `$XDG_CONFIG_HOME/stool/config.yaml`.
+    User(PathBuf),
+    /// File named by `$STOOL_CONFIG`.
+    Env(PathBuf),
+    /// File passed via `--config`.
+    Cli(PathBuf),
+    /// File listed under `include:` of another layer.
+    Include(PathBuf),
+    /// Fragment from `$XDG_CONFIG_HOME/stool/config.d/`.
+    Fragment(PathBuf),
+    /// Hosts imported from an ssh client config (`import_ssh_config: true`).
+    SshConfig(PathBuf),
+}
+
+impl ConfigSource {
+    /// Returns the candidate file layers in priority order (lowest first).
+    ///
+    /// The embedded config is not included; it is always the base layer.
+    ///
+    /// # Arguments
+    /// * `cli_path` - Path passed via `--config`, if any
+    pub fn candidates(cli_path: Option<&str>) -> Vec<ConfigSource> {
+        let mut sources = Vec::new();
+        if let Some(dir) = paths::config_dir() {
+            sources.push(ConfigSource::User(user_config_path(&dir)));
+        }
+        if let Some(path) = env::var_os(CONFIG_ENV_VAR).filter(|p| !p.is_empty()) {
+            sources.push(ConfigSource::Env(PathBuf::from(path)));
+        }
+        if let Some(path) = cli_path {
+            sources.push(ConfigSource::Cli(paths::expand_tilde(path)));
+        }
+        sources
+    }
+
+    /// Returns the file path backing this source, if any.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Embedded => None,
+            Self::User(p)
+            | Self::Env(p)
+            | Self::Cli(p)
+            | Self::Include(p)
+            | Self::Fragment(p)
+            | Self::SshConfig(p) => Some(p),
+        }
+    }
+
+    /// Whether a missing file for this source is an error.
+    ///
+    /// Explicitly requested files must exist; the user config is optional.
+    pub fn is_required(&self) -> bool {
+        !matches!(self, Self::Embedded | Self::User(_) | Self::SshConfig(_))
+    }
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Embedded => write!(f, "embedded"),
+            Self::User(p) => write!(f, "{} (user)", p.display()),
+            Self::Env(p) => write!(f, "{} (${})", p.display(), CONFIG_ENV_VAR),
+            Self::Cli(p) => write!(f, "{} (--config)", p.display()),
+            Self::Include(p) => write!(f, "{} (include)", p.display()),
+            Self::Fragment(p) => write!(f, "{} (config.d)", p.display()),
+            Self::SshConfig(p) => write!(f, "{} (ssh config)", p.display()),
+        }
+    }
+}
+
+/// A single configuration file as read from its source.
+#[derive(Debug, Clone)]
+pub struct ConfigLayer {
+    pub source: ConfigSource,
+    /// Raw file content, kept for diagnostics.
+    pub content: String,
+    /// Format of `content`, from the file extension or the content itself.
+    pub format: ConfigFormat,
+    /// Index of the top-level source this file belongs to.
+    ///
+    /// A top-level file, its includes and (for the user config) its
+    /// `config.d` fragments form one group. Names must be unique within a
+    /// group; later groups override earlier ones.
+    pub group: usize,
+}
+
+/// Named configuration sections that are merged entry by entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Section {
+    Servers,
+    EcrRegistries,
+    SsoConfigs,
+    Contexts,
+}
+
+impl Section {
+    /// All named sections.
+    pub const ALL: [Section; 4] = [
+        Self::Servers,
+        Self::EcrRegistries,
+        Self::SsoConfigs,
+        Self::Contexts,
+    ];
+
+    /// YAML key of the section.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::Servers => "servers",
+            Self::EcrRegistries => "ecr_registries",
+            Self::SsoConfigs => "sso_configs",
+            Self::Contexts => "contexts",
+        }
+    }
+
+    /// Field identifying an entry within the section.
+    pub fn name_field(&self) -> &'static str {
+        match self {
+            Self::Servers | Self::EcrRegistries | Self::Contexts => "name",
+            Self::SsoConfigs => "profile_name",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.key() == key)
+    }
+}
+
 /// Configuration container for server list and ECR registries.
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// Built from one or more layers; entries in later layers override
+/// entries with the same name in earlier ones.
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Config {
+    /// Schema version; older files are migrated when loaded.
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
     pub servers: Vec<Server>,
     #[serde(default)]
     pub ecr_registries: Vec<EcrRegistry>,
     #[serde(default)]
     pub sso_configs: Vec<SsoConfig>,
+    #[serde(default)]
+    pub contexts: Vec<Context>,
+    /// Also offer hosts from `~/.ssh/config` as servers.
+    ///
+    /// Imported hosts have the lowest priority; configured servers with the
+    /// same name win.
+    #[serde(default)]
+    pub import_ssh_config: bool,
+    /// Timeouts and retries for external commands.
+    #[serde(default, skip_serializing_if = "ExecutionConfig::is_empty")]
+    pub execution: ExecutionConfig,
+    #[serde(skip)]
+    layers: Vec<ConfigLayer>,
+    #[serde(skip)]
+    origins: Origins,
 }
 
 impl Config {
-    /// Loads configuration from an external YAML file.
+    /// Resolves configuration from all available layers.
+    ///
+    /// Layers are merged in this order, later ones taking precedence:
+    /// 1. Embedded config (build time)
+    /// 2. `$XDG_CONFIG_HOME/stool/config.yaml` (if present) and
+    ///    `$XDG_CONFIG_HOME/stool/config.d/*.yaml` in file name order
+    /// 3. `$STOOL_CONFIG`
+    /// 4. `--config`
+    ///
+    /// Each file may list further files under `include:`; these are loaded
+    /// right after the including file, relative to its directory.
+    ///
+    /// Servers, ECR registries and SSO configs are merged by name, so a
+    /// later layer can add entries or override individual fields.
     ///
     /// # Arguments
-    /// * `path` - Path to the YAML configuration file
+    /// * `cli_path` - Path passed via `--config`, if any
     ///
     /// # Errors
-    /// Returns error if file cannot be read or parsed as YAML
-    pub fn load(path: &str) -> Result<Self> {
-        let content = fs::read_to_string(path).map_err(|e| {
-            StoolError::new(StoolErrorType::ConfigLoadFailed)
-                .with_message(format!("Failed to read config file: {}", path))
-                .with_source(e)
-        })?;
+    /// Returns error if a required file is missing or any layer cannot be parsed
+    pub fn resolve(cli_path: Option<&str>) -> Result<Self> {
+        Self::from_layers(Self::read_layers(cli_path)?)
+    }
 
-        serde_yaml::from_str(&content).map_err(|e| {
-            StoolError::new(StoolErrorType::YamlParseError)
-                .with_message("Failed to parse YAML config")
-                .with_source(e)
-        })
+    /// Reads all available layers without merging them.
+    ///
+    /// # Arguments
+    /// * `cli_path` - Path passed via `--config`, if any
+    ///
+    /// # Errors
+    /// Returns error if a required file is missing or unreadable
+    pub fn read_layers(cli_path: Option<&str>) -> Result<Vec<ConfigLayer>> {
+        Self::read_layers_with(cli_path, None)
+    }
+
+    /// Reads all layers, using `replacement` as the content of one file.
+    ///
+    /// Used to check an edited file in context before writing it. The
+    /// replaced file is treated as existing even if it is not on disk yet.
+    ///
+    /// # Arguments
+    /// * `cli_path` - Path passed via `--config`, if any
+    /// * `replacement` - Path and content to use instead of reading the file
+    ///
+    /// # Errors
+    /// Returns error if a required file is missing or unreadable
+    pub fn read_layers_with(
+        cli_path: Option<&str>,
+        replacement: Option<(&Path, &str)>,
+    ) -> Result<Vec<ConfigLayer>> {
+        let mut reader = LayerReader::new(replacement);
+        reader.push(ConfigSource::Embedded, EMBEDDED_CONFIG.to_string(), 0)?;
+
+        for (i, source) in ConfigSource::candidates(cli_path).into_iter().enumerate() {
+            let group = i + 1;
+            let fragment_dir = match &source {
+                ConfigSource::User(p) => p.parent().map(|d| d.join(FRAGMENT_DIR_NAME)),
+                _ => None,
+            };
+
+            if let Some(content) = reader.read(&source)? {
+                reader.push(source, content, group)?;
+            }
+            if let Some(dir) = fragment_dir {
+                reader.push_fragments(&dir, group)?;
+            }
+        }
+
+        Ok(reader.layers)
+    }
+
+    /// Loads configuration from an external file.
+    ///
+    /// The parser is picked by file extension (`.yaml`, `.yml`, `.toml`,
+    /// `.json`), or by sniffing the content for other names.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the configuration file
+    ///
+    /// # Errors
+    /// Returns error if file cannot be read or parsed
+    pub fn load(path: &str) -> Result<Self> {
+        let path = paths::expand_tilde(path);
+        let content = read_file(&path)?;
+        let mut reader = LayerReader::new(None);
+        reader.push(ConfigSource::Cli(path), content, 0)?;
+        Self::from_layers(reader.layers)
     }
 
     /// Loads configuration embedded at build time.
@@ -94,11 +554,680 @@ impl Config {
     /// # Errors
     /// Returns error if embedded YAML cannot be parsed
     pub fn load_embedded() -> Result<Self> {
-        let content = include_str!("../../config.yaml");
-        serde_yaml::from_str(content).map_err(|e| {
-            StoolError::new(StoolErrorType::YamlParseError)
-                .with_message("Failed to parse embedded YAML config")
-                .with_source(e)
+        let mut reader = LayerReader::new(None);
+        reader.push(ConfigSource::Embedded, EMBEDDED_CONFIG.to_string(), 0)?;
+        Self::from_layers(reader.layers)
+    }
+
+    /// Returns the layers this configuration was built from (lowest first).
+    pub fn layers(&self) -> &[ConfigLayer] {
+        &self.layers
+    }
+
+    /// Returns the named context.
+    pub fn context(&self, name: &str) -> Option<&Context> {
+        self.contexts.iter().find(|c| c.name == name)
+    }
+
+    /// Returns the name of the active context and where it was set.
+    ///
+    /// `$STOOL_CONTEXT` takes precedence over the state file.
+    ///
+    /// # Errors
+    /// Returns error if the state file cannot be read
+    pub fn active_context_name() -> Result<Option<(String, ContextSource)>> {
+        if let Some(name) = env::var(CONTEXT_ENV_VAR).ok().filter(|n| !n.is_empty()) {
+            return Ok(Some((name, ContextSource::Env)));
+        }
+        Ok(State::load()?
+            .context
+            .map(|name| (name, ContextSource::State)))
+    }
+
+    /// Returns the active context, if one is selected.
+    ///
+    /// # Errors
+    /// Returns `ConfigParseError` if the selected context is not defined, or
+    /// error if the state file cannot be read
+    pub fn active_context(&self) -> Result<Option<&Context>> {
+        let Some((name, source)) = Self::active_context_name()? else {
+            return Ok(None);
+        };
+        self.context(&name).map(Some).ok_or_else(|| {
+            let hint = match source {
+                ContextSource::Env => format!("unset {}", CONTEXT_ENV_VAR),
+                ContextSource::State => "run `stool context clear`".to_string(),
+            };
+            StoolError::new(StoolErrorType::ConfigParseError)
+                .with_message(format!("Active context '{}' is not defined", name))
+                .with_hint(hint)
         })
     }
+
+    /// Returns the layer that last defined the named entry.
+    ///
+    /// # Arguments
+    /// * `section` - Section containing the entry
+    /// * `name` - Entry name (`name` or `profile_name`)
+    pub fn source_of(&self, section: Section, name: &str) -> Option<&ConfigSource> {
+        self.origins
+            .get(&(section, name.to_string()))
+            .and_then(|&i| self.layers.get(i))
+            .map(|l| &l.source)
+    }
+
+    /// Merges the given layers (lowest priority first) into a config.
+    ///
+    /// # Errors
+    /// Returns error if any layer is not valid YAML or the merged result
+    /// does not match the config structure
+    pub fn from_layers(mut layers: Vec<ConfigLayer>) -> Result<Self> {
+        let (mut merged, mut origins) = merge_all(&layers)?;
+
+        if merged.get(IMPORT_SSH_CONFIG_KEY).and_then(Value::as_bool) == Some(true)
+            && let Some(layer) = ssh_config_layer()?
+        {
+            layers.insert(0, layer);
+            (merged, origins) = merge_all(&layers)?;
+        }
+
+        let mut config: Config = serde_yaml::from_value(Value::Mapping(merged)).map_err(|e| {
+            StoolError::new(StoolErrorType::ConfigParseError)
+                .with_message("Invalid config structure")
+                .with_source(e)
+        })?;
+        config.layers = layers;
+        config.origins = origins;
+        Ok(config)
+    }
+}
+
+/// Merges layers in order, recording which layer last defined each entry.
+fn merge_all(layers: &[ConfigLayer]) -> Result<(Mapping, Origins)> {
+    let mut merged = Mapping::new();
+    let mut origins = HashMap::new();
+
+    for (index, layer) in layers.iter().enumerate() {
+        let mut mapping = parse_layer(layer)?;
+        mapping.remove(INCLUDE_KEY);
+        merge_layer(&mut merged, mapping, index, &mut origins);
+    }
+    Ok((merged, origins))
+}
+
+/// Builds a synthetic layer from the hosts in `~/.ssh/config`.
+fn ssh_config_layer() -> Result<Option<ConfigLayer>> {
+    let Some(path) = ssh_config::default_path().filter(|p| p.exists()) else {
+        return Ok(None);
+    };
+
+    let servers: Vec<Server> = ssh_config::load(&path)?
+        .iter()
+        .map(|h| h.to_server())
+        .collect();
+
+    let mut mapping = Mapping::new();
+    mapping.insert(
+        Value::from(migration::VERSION_KEY),
+        Value::from(migration::CURRENT_VERSION),
+    );
+    mapping.insert(
+        Value::from(Section::Servers.key()),
+        serde_yaml::to_value(servers).map_err(|e| {
+            StoolError::new(StoolErrorType::ConfigParseError)
+                .with_message("Failed to convert ssh config hosts")
+                .with_source(e)
+        })?,
+    );
+    let content = serde_yaml::to_string(&mapping).map_err(|e| {
+        StoolError::new(StoolErrorType::ConfigParseError)
+            .with_message("Failed to convert ssh config hosts")
+            .with_source(e)
+    })?;
+
+    Ok(Some(ConfigLayer {
+        source: ConfigSource::SshConfig(path),
+        content,
+        format: ConfigFormat::Yaml,
+        group: SSH_CONFIG_GROUP,
+    }))
+}
+
+/// Collects layers while following `include:` lists.
+struct LayerReader<'a> {
+    layers: Vec<ConfigLayer>,
+    /// Canonical paths of files currently being included, for cycle detection.
+    stack: Vec<PathBuf>,
+    replacement: Option<(&'a Path, &'a str)>,
+}
+
+impl<'a> LayerReader<'a> {
+    fn new(replacement: Option<(&'a Path, &'a str)>) -> Self {
+        Self {
+            layers: Vec::new(),
+            stack: Vec::new(),
+            replacement,
+        }
+    }
+
+    /// Reads a source's file, returning `None` for an optional missing file.
+    fn read(&self, source: &ConfigSource) -> Result<Option<String>> {
+        let Some(path) = source.path() else {
+            return Ok(None);
+        };
+        if let Some((replaced, content)) = self.replacement
+            && replaced == path
+        {
+            return Ok(Some(content.to_string()));
+        }
+        if !source.is_required() && !path.exists() {
+            return Ok(None);
+        }
+        read_file(path).map(Some)
+    }
+
+    /// Adds a layer followed by the files it includes.
+    fn push(&mut self, source: ConfigSource, content: String, group: usize) -> Result<()> {
+        let format = ConfigFormat::detect(source.path(), &content);
+        let layer = ConfigLayer {
+            source,
+            content,
+            format,
+            group,
+        };
+        let includes = include_list(&layer)?;
+        let base_dir = match layer.source.path() {
+            Some(p) => p.parent().map(Path::to_path_buf),
+            None => paths::config_dir(),
+        };
+        let canonical = layer.source.path().and_then(|p| fs::canonicalize(p).ok());
+
+        self.layers.push(layer);
+        if includes.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(c) = &canonical {
+            self.stack.push(c.clone());
+        }
+        for include in includes {
+            let path = paths::expand_tilde(&include);
+            let path = match &base_dir {
+                Some(dir) if path.is_relative() => dir.join(path),
+                _ => path,
+            };
+            if let Ok(c) = fs::canonicalize(&path)
+                && self.stack.contains(&c)
+            {
+                return Err(StoolError::new(StoolErrorType::ConfigParseError)
+                    .with_message(format!("Include cycle detected at {}", path.display())));
+            }
+
+            let source = ConfigSource::Include(path);
+            if let Some(content) = self.read(&source)? {
+                self.push(source, content, group)?;
+            }
+        }
+        if canonical.is_some() {
+            self.stack.pop();
+        }
+        Ok(())
+    }
+
+    /// Adds all config files (`*.yaml`, `*.yml`, `*.toml`, `*.json`) in `dir`,
+    /// sorted by file name.
+    fn push_fragments(&mut self, dir: &Path, group: usize) -> Result<()> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Ok(());
+        };
+
+        let mut fragments: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && ConfigFormat::from_path(p).is_some())
+            .collect();
+        fragments.sort();
+
+        for path in fragments {
+            let source = ConfigSource::Fragment(path);
+            if let Some(content) = self.read(&source)? {
+                self.push(source, content, group)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the `include:` list of a layer.
+fn include_list(layer: &ConfigLayer) -> Result<Vec<String>> {
+    let Some(value) = parse_layer(layer)?.remove(INCLUDE_KEY) else {
+        return Ok(Vec::new());
+    };
+    serde_yaml::from_value(value).map_err(|e| {
+        StoolError::new(StoolErrorType::ConfigParseError)
+            .with_message(format!(
+                "'{}' must be a list of paths: {}",
+                INCLUDE_KEY, layer.source
+            ))
+            .with_source(e)
+    })
+}
+
+fn read_file(path: &Path) -> Result<String> {
+    fs::read_to_string(path).map_err(|e| {
+        StoolError::new(StoolErrorType::ConfigLoadFailed)
+            .with_message(format!("Failed to read config file: {}", path.display()))
+            .with_source(e)
+    })
+}
+
+/// Returns the user config file in `dir`, preferring `config.yaml`.
+fn user_config_path(dir: &Path) -> PathBuf {
+    let default = dir.join(CONFIG_FILE_NAME);
+    if default.exists() {
+        return default;
+    }
+    ConfigFormat::EXTENSIONS
+        .iter()
+        .map(|ext| default.with_extension(ext))
+        .find(|p| p.exists())
+        .unwrap_or(default)
+}
+
+/// Parses a layer into its top-level mapping, migrated to the current schema.
+pub(crate) fn parse_layer(layer: &ConfigLayer) -> Result<Mapping> {
+    let value = layer.format.parse(&layer.content).map_err(|e| {
+        let message = format!("Failed to parse {} config: {}", layer.format, layer.source);
+        e.with_message(message)
+    })?;
+
+    let mut mapping = match value {
+        Value::Mapping(m) => m,
+        Value::Null => Mapping::new(),
+        _ => {
+            return Err(StoolError::new(StoolErrorType::ConfigParseError)
+                .with_message(format!("Top level must be a mapping: {}", layer.source)));
+        }
+    };
+
+    migration::migrate(&mut mapping).map_err(|e| {
+        let message = e.message.clone().unwrap_or_default();
+        e.with_message(format!("{}: {}", message, layer.source))
+    })?;
+    Ok(mapping)
+}
+
+/// Merges a layer's mapping into the accumulated config.
+///
+/// Named sections are merged entry by entry; everything else is deep-merged.
+fn merge_layer(base: &mut Mapping, layer: Mapping, index: usize, origins: &mut Origins) {
+    for (key, value) in layer {
+        let section = key.as_str().and_then(Section::from_key);
+        match (section, value) {
+            (Some(section), Value::Sequence(entries)) => {
+                let slot = base
+                    .entry(key)
+                    .or_insert_with(|| Value::Sequence(Vec::new()));
+                if !slot.is_sequence() {
+                    *slot = Value::Sequence(Vec::new());
+                }
+                if let Value::Sequence(existing) = slot {
+                    merge_entries(existing, entries, section, index, origins);
+                }
+            }
+            (_, value) => match base.get_mut(&key) {
+                Some(existing) => deep_merge(existing, value),
+                None => {
+                    base.insert(key, value);
+                }
+            },
+        }
+    }
+}
+
+/// Merges named entries, overriding existing entries with the same name.
+fn merge_entries(
+    existing: &mut Vec<Value>,
+    entries: Vec<Value>,
+    section: Section,
+    index: usize,
+    origins: &mut Origins,
+) {
+    let field = section.name_field();
+    for entry in entries {
+        let Some(name) = entry.get(field).and_then(Value::as_str).map(str::to_string) else {
+            existing.push(entry);
+            continue;
+        };
+
+        let previous = existing
+            .iter_mut()
+            .find(|e| e.get(field).and_then(Value::as_str) == Some(name.as_str()));
+        match previous {
+            Some(prev) => deep_merge(prev, entry),
+            None => existing.push(entry),
+        }
+        origins.insert((section, name), index);
+    }
+}
+
+/// Recursively merges `overlay` into `base`; non-mapping values are replaced.
+fn deep_merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => deep_merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_filter_matches_case_insensitively() {
+        let servers: Vec<Server> = serde_yaml::from_str(
+            "
+- {name: web-1, ip: 10.0.0.1, user: deploy, group: shop, environment: prod, tags: [web, public]}
+- {name: web-2, ip: 10.0.0.2, user: deploy, group: Shop, environment: staging, tags: [web]}
+- {name: db-1, ip: 10.0.0.3, user: deploy, group: shop, environment: PROD, tags: [db]}
+",
+        )
+        .unwrap();
+        let filter = ServerFilter {
+            group: Some("SHOP".to_string()),
+            environment: Some("prod".to_string()),
+            ..ServerFilter::default()
+        };
+
+        let names: Vec<&str> = filter
+            .apply(&servers)
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+
+        assert_eq!(names, ["web-1", "db-1"]);
+    }
+
+    #[test]
+    fn server_filter_requires_every_tag() {
+        let servers: Vec<Server> = serde_yaml::from_str(
+            "
+- {name: web-1, ip: 10.0.0.1, user: deploy, tags: [web, public]}
+- {name: web-2, ip: 10.0.0.2, user: deploy, tags: [web]}
+",
+        )
+        .unwrap();
+        let filter = ServerFilter {
+            tags: vec!["WEB".to_string(), "public".to_string()],
+            ..ServerFilter::default()
+        };
+
+        let names: Vec<&str> = filter
+            .apply(&servers)
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+
+        assert_eq!(names, ["web-1"]);
+    }
+
+    #[test]
+    fn server_filter_criteria_need_a_value_on_the_server() {
+        let server: Server =
+            serde_yaml::from_str("{name: bastion, ip: 10.0.0.1, user: deploy}").unwrap();
+        let filter = ServerFilter {
+            group: Some("shop".to_string()),
+            ..ServerFilter::default()
+        };
+
+        assert!(!filter.matches(&server));
+        assert!(ServerFilter::default().matches(&server));
+    }
+
+    #[test]
+    fn server_filter_formats_its_criteria() {
+        let filter = ServerFilter {
+            group: Some("shop".to_string()),
+            tags: vec!["web".to_string(), "public".to_string()],
+            environment: Some("prod".to_string()),
+        };
+
+        assert_eq!(
+            filter.to_string(),
+            "group=shop, env=prod, tag=web, tag=public"
+        );
+        assert!(!filter.is_empty());
+        assert!(ServerFilter::default().is_empty());
+        assert_eq!(ServerFilter::default().to_string(), "");
+    }
+
+    #[test]
+    fn match_server_name_prefers_exact_then_prefix_then_substring() {
+        let servers: Vec<Server> = serde_yaml::from_str(
+            "
+- {name: api, ip: 10.0.0.1, user: deploy}
+- {name: api-2, ip: 10.0.0.2, user: deploy}
+- {name: prod-api, ip: 10.0.0.3, user: deploy}
+",
+        )
+        .unwrap();
+        let servers: Vec<&Server> = servers.iter().collect();
+
+        assert!(
+            matches!(match_server_name(&servers, "API"), NameMatch::Unique(s) if s.name == "api")
+        );
+        assert!(
+            matches!(match_server_name(&servers, "api-"), NameMatch::Unique(s) if s.name == "api-2")
+        );
+        assert!(
+            matches!(match_server_name(&servers, "d-a"), NameMatch::Unique(s) if s.name == "prod-api")
+        );
+    }
+
+    #[test]
+    fn match_server_name_falls_back_to_characters_in_order() {
+        let servers: Vec<Server> = serde_yaml::from_str(
+            "
+- {name: prod-api, ip: 10.0.0.1, user: deploy}
+- {name: staging-api, ip: 10.0.0.2, user: deploy}
+",
+        )
+        .unwrap();
+        let servers: Vec<&Server> = servers.iter().collect();
+
+        assert!(
+            matches!(match_server_name(&servers, "papi"), NameMatch::Unique(s) if s.name == "prod-api")
+        );
+        assert!(matches!(
+            match_server_name(&servers, "xyz"),
+            NameMatch::None
+        ));
+    }
+
+    #[test]
+    fn match_server_name_reports_ties_in_original_order() {
+        let servers: Vec<Server> = serde_yaml::from_str(
+            "
+- {name: web-2, ip: 10.0.0.2, user: deploy}
+- {name: db, ip: 10.0.0.3, user: deploy}
+- {name: web-1, ip: 10.0.0.1, user: deploy}
+",
+        )
+        .unwrap();
+        let servers: Vec<&Server> = servers.iter().collect();
+
+        let NameMatch::Ambiguous(found) = match_server_name(&servers, "web") else {
+            panic!("expected an ambiguous match");
+        };
+
+        let names: Vec<&str> = found.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["web-2", "web-1"]);
+    }
+
+    /// Creates an empty directory for one test's config files.
+    fn scratch_dir(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("stool-config-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn includes_load_right_after_the_including_file() {
+        let dir = scratch_dir("include-order");
+        fs::create_dir(dir.join("projects")).unwrap();
+        fs::write(
+            dir.join("main.yaml"),
+            "include:\n  - projects/a.yaml\n  - b.json\nservers: []\n",
+        )
+        .unwrap();
+        fs::write(dir.join("projects/a.yaml"), "include: [c.toml]\n").unwrap();
+        fs::write(dir.join("projects/c.toml"), "").unwrap();
+        fs::write(dir.join("b.json"), "{}").unwrap();
+
+        let config = Config::load(dir.join("main.yaml").to_str().unwrap()).unwrap();
+
+        let sources: Vec<String> = config
+            .layers()
+            .iter()
+            .map(|l| l.source.to_string())
+            .collect();
+        assert_eq!(
+            sources,
+            [
+                format!("{} (--config)", dir.join("main.yaml").display()),
+                format!("{} (include)", dir.join("projects/a.yaml").display()),
+                format!("{} (include)", dir.join("projects/c.toml").display()),
+                format!("{} (include)", dir.join("b.json").display()),
+            ]
+        );
+        assert!(config.layers().iter().all(|l| l.group == 0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_includes_are_errors() {
+        let dir = scratch_dir("include-missing");
+        fs::write(dir.join("main.yaml"), "include: [gone.yaml]\n").unwrap();
+
+        let error = Config::load(dir.join("main.yaml").to_str().unwrap()).unwrap_err();
+
+        assert_eq!(error.error_type, StoolErrorType::ConfigLoadFailed);
+        assert!(error.message.unwrap().contains("gone.yaml"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn include_cycles_are_rejected() {
+        let dir = scratch_dir("include-cycle");
+        fs::write(dir.join("a.yaml"), "include: [b.yaml]\n").unwrap();
+        fs::write(dir.join("b.yaml"), "include: [a.yaml]\n").unwrap();
+
+        let error = Config::load(dir.join("a.yaml").to_str().unwrap()).unwrap_err();
+
+        assert_eq!(error.error_type, StoolErrorType::ConfigParseError);
+        assert!(error.message.unwrap().starts_with("Include cycle detected"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fragments_load_in_file_name_order() {
+        let dir = scratch_dir("fragments");
+        fs::write(dir.join("20-search.yaml"), "").unwrap();
+        fs::write(dir.join("10-billing.toml"), "").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        let mut reader = LayerReader::new(None);
+
+        reader.push_fragments(&dir, 1).unwrap();
+
+        let paths: Vec<&Path> = reader
+            .layers
+            .iter()
+            .filter_map(|l| l.source.path())
+            .collect();
+        assert_eq!(
+            paths,
+            [dir.join("10-billing.toml"), dir.join("20-search.yaml")]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn names_repeated_across_fragments_are_duplicates() {
+        let dir = scratch_dir("fragment-duplicates");
+        fs::write(
+            dir.join("10-a.yaml"),
+            "servers:\n  - name: web\n    ip: 10.0.0.1\n    user: deploy\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("20-b.yaml"),
+            "servers:\n  - name: web\n    ip: 10.0.0.2\n    user: deploy\n",
+        )
+        .unwrap();
+        let mut reader = LayerReader::new(None);
+        reader.push_fragments(&dir, 1).unwrap();
+
+        let report = Config::from_layers(reader.layers).unwrap().validate();
+
+        let errors: Vec<String> = report.errors().map(|d| d.message.clone()).collect();
+        assert_eq!(
+            errors,
+            [format!(
+                "duplicate name 'web' (first defined at servers[0] in {} (config.d), line 2)",
+                dir.join("10-a.yaml").display()
+            )]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn later_groups_override_fields_by_name() {
+        let dir = scratch_dir("group-override");
+        fs::write(
+            dir.join("base.yaml"),
+            "servers:\n  - name: web\n    ip: 10.0.0.1\n    user: deploy\n    port: 2222\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("override.yaml"),
+            "servers:\n  - name: web\n    ip: 10.0.0.9\n  - name: db\n    ip: 10.0.0.2\n    user: postgres\n",
+        )
+        .unwrap();
+        let mut reader = LayerReader::new(None);
+        reader
+            .push(
+                ConfigSource::Cli(dir.join("base.yaml")),
+                fs::read_to_string(dir.join("base.yaml")).unwrap(),
+                1,
+            )
+            .unwrap();
+        reader
+            .push(
+                ConfigSource::Include(dir.join("override.yaml")),
+                fs::read_to_string(dir.join("override.yaml")).unwrap(),
+                2,
+            )
+            .unwrap();
+
+        let config = Config::from_layers(reader.layers).unwrap();
+
+        assert!(config.validate().diagnostics.is_empty());
+        assert_eq!(config.servers.len(), 2);
+        assert_eq!(config.servers[0].ip, "10.0.0.9");
+        assert_eq!(config.servers[0].port, Some(2222));
+        assert_eq!(
+            config.source_of(Section::Servers, "web"),
+            Some(&ConfigSource::Include(dir.join("override.yaml")))
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
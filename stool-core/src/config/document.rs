@@ -0,0 +1,480 @@
+//! Entry-level edits of config files.
+//!
+//! Rewriting a YAML config through `serde_yaml` drops comments and reorders
+//! keys, so YAML entries are edited in the raw text instead. Only
+//! block-style sections are supported; other layouts are reported as errors.
+//! Entries are added, replaced or removed as a whole; the rest of the file
+//! is left untouched.
+//!
+//! TOML and JSON files are parsed, edited and written back in full, keeping
+//! key order but not TOML comments.
+
+use super::Section;
+use super::format::ConfigFormat;
+use super::locate;
+use crate::error::{Result, StoolError, StoolErrorType};
+use serde_yaml::{Mapping, Value};
+
+/// Default indentation of list entries in a new section.
+const DEFAULT_INDENT: usize = 2;
+
+/// Appends entries to the end of a section, creating it if missing.
+///
+/// Existing content, comments and ordering are kept; new entries use the
+/// indentation of existing ones.
+///
+/// # Arguments
+/// * `content` - Raw content of the file
+/// * `format` - Format of the file
+/// * `section` - Section to append to
+/// * `entries` - Entries to append (serializable mappings)
+///
+/// # Errors
+/// Returns error if the section is not block-style or an entry cannot be serialized
+pub fn insert_entries(
+    content: &str,
+    format: ConfigFormat,
+    section: Section,
+    entries: &[Value],
+) -> Result<String> {
+    if format != ConfigFormat::Yaml {
+        return edit_parsed(content, format, section, |list| {
+            list.extend(entries.iter().cloned());
+            Ok(())
+        });
+    }
+
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let spans = locate::section_entries(content, section.key(), section.name_field());
+
+    let (at, indent) = match (spans.last(), locate::section_header(content, section.key())) {
+        (Some(last), _) => (last.end, indent_of(&lines[last.start])),
+        (None, Some(header)) => {
+            let value = lines[header]
+                .split_once(':')
+                .map(|(_, v)| v.split(" #").next().unwrap_or("").trim().to_string())
+                .unwrap_or_default();
+            match value.as_str() {
+                "" | "[]" | "~" | "null" => {
+                    lines[header] = format!("{}:", section.key());
+                    (header + 1, DEFAULT_INDENT)
+                }
+                _ => {
+                    return Err(
+                        StoolError::new(StoolErrorType::ConfigParseError).with_message(format!(
+                            "Cannot edit non block-style section '{}'",
+                            section.key()
+                        )),
+                    );
+                }
+            }
+        }
+        (None, None) => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(format!("{}:", section.key()));
+            (lines.len(), DEFAULT_INDENT)
+        }
+    };
+
+    let mut rendered = Vec::new();
+    for entry in entries {
+        rendered.extend(render_entry(entry, indent)?);
+    }
+    lines.splice(at..at, rendered);
+
+    let mut result = lines.join("\n");
+    result.push('\n');
+    Ok(result)
+}
+
+/// Replaces an entry in place, keeping its position and indentation.
+///
+/// Everything outside the entry is kept as-is; comments inside it are lost.
+///
+/// # Arguments
+/// * `content` - Raw content of the file
+/// * `format` - Format of the file
+/// * `section` - Section containing the entry
+/// * `name` - Value of the entry's name field
+/// * `entry` - New entry (serializable mapping)
+///
+/// # Errors
+/// Returns `EntryNotFound` if the file has no such entry, or error if the
+/// entry cannot be serialized
+pub fn replace_entry(
+    content: &str,
+    format: ConfigFormat,
+    section: Section,
+    name: &str,
+    entry: &Value,
+) -> Result<String> {
+    if format != ConfigFormat::Yaml {
+        return edit_parsed(content, format, section, |list| {
+            let i = position(list, section, name)?;
+            list[i] = entry.clone();
+            Ok(())
+        });
+    }
+
+    let span = find(content, section, name)?;
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+
+    let rendered = render_entry(entry, indent_of(&lines[span.start]))?;
+    lines.splice(span.start..span.end, rendered);
+
+    let mut result = lines.join("\n");
+    result.push('\n');
+    Ok(result)
+}
+
+/// Removes an entry together with the comment lines directly above it.
+///
+/// A section left without entries is written as `[]` so that it does not
+/// become `null`.
+///
+/// # Arguments
+/// * `content` - Raw content of the file
+/// * `format` - Format of the file
+/// * `section` - Section containing the entry
+/// * `name` - Value of the entry's name field
+///
+/// # Errors
+/// Returns `EntryNotFound` if the file has no such entry
+pub fn remove_entry(
+    content: &str,
+    format: ConfigFormat,
+    section: Section,
+    name: &str,
+) -> Result<String> {
+    if format != ConfigFormat::Yaml {
+        return edit_parsed(content, format, section, |list| {
+            let i = position(list, section, name)?;
+            list.remove(i);
+            Ok(())
+        });
+    }
+
+    let span = find(content, section, name)?;
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+
+    let indent = indent_of(&lines[span.start]);
+    let mut start = span.start;
+    while start > 0
+        && lines[start - 1].trim_start().starts_with('#')
+        && indent_of(&lines[start - 1]) == indent
+    {
+        start -= 1;
+    }
+    // Drop one of the blank lines that separated the entry from its
+    // neighbours, or the one below it when it was the first entry
+    let mut end = span.end;
+    let first = start > 0 && locate::section_header(content, section.key()) == Some(start - 1);
+    if (start == 0 || first || lines[start - 1].trim().is_empty())
+        && lines.get(end).is_some_and(|l| l.trim().is_empty())
+    {
+        end += 1;
+    }
+    lines.drain(start..end);
+
+    let mut result = lines.join("\n");
+    result.push('\n');
+
+    if locate::section_entries(&result, section.key(), section.name_field()).is_empty()
+        && let Some(header) = locate::section_header(&result, section.key())
+    {
+        let mut lines: Vec<String> = result.lines().map(str::to_string).collect();
+        lines[header] = format!("{}: []", section.key());
+        result = lines.join("\n");
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+fn find(content: &str, section: Section, name: &str) -> Result<locate::EntrySpan> {
+    locate::find_entry(content, section.key(), section.name_field(), name)
+        .ok_or_else(|| not_found(section, name))
+}
+
+/// Applies `edit` to a section list of a parsed file and renders it again.
+fn edit_parsed(
+    content: &str,
+    format: ConfigFormat,
+    section: Section,
+    edit: impl FnOnce(&mut Vec<Value>) -> Result<()>,
+) -> Result<String> {
+    let mut mapping = match format.parse(content)? {
+        Value::Mapping(m) => m,
+        Value::Null => Mapping::new(),
+        _ => {
+            return Err(StoolError::new(StoolErrorType::ConfigParseError)
+                .with_message("Top level must be a mapping"));
+        }
+    };
+
+    let slot = mapping
+        .entry(Value::from(section.key()))
+        .or_insert_with(|| Value::Sequence(Vec::new()));
+    let Value::Sequence(list) = slot else {
+        return Err(StoolError::new(StoolErrorType::ConfigParseError)
+            .with_message(format!("Section '{}' must be a list", section.key())));
+    };
+    edit(list)?;
+
+    format.render(&Value::Mapping(mapping))
+}
+
+/// Returns the index of the named entry in a parsed section list.
+fn position(list: &[Value], section: Section, name: &str) -> Result<usize> {
+    list.iter()
+        .position(|e| e.get(section.name_field()).and_then(Value::as_str) == Some(name))
+        .ok_or_else(|| not_found(section, name))
+}
+
+fn not_found(section: Section, name: &str) -> StoolError {
+    StoolError::new(StoolErrorType::EntryNotFound).with_message(format!(
+        "'{}' not found in {}",
+        name,
+        section.key()
+    ))
+}
+
+/// Renders a mapping as a list item at the given indentation.
+fn render_entry(entry: &Value, indent: usize) -> Result<Vec<String>> {
+    let yaml = serde_yaml::to_string(entry).map_err(|e| {
+        StoolError::new(StoolErrorType::ConfigParseError)
+            .with_message("Failed to serialize config entry")
+            .with_source(e)
+    })?;
+
+    let pad = " ".repeat(indent);
+    Ok(yaml
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 {
+                format!("{}- {}", pad, line)
+            } else {
+                format!("{}  {}", pad, line)
+            }
+        })
+        .collect())
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_appends_after_the_last_entry() {
+        let content = "\
+servers:
+  - name: web
+    ip: 10.0.0.1
+
+# registries
+ecr_registries: []
+";
+        let entry: Value = serde_yaml::from_str("name: app\nip: 10.0.0.3").unwrap();
+
+        let result =
+            insert_entries(content, ConfigFormat::Yaml, Section::Servers, &[entry]).unwrap();
+
+        assert_eq!(
+            result,
+            "\
+servers:
+  - name: web
+    ip: 10.0.0.1
+  - name: app
+    ip: 10.0.0.3
+
+# registries
+ecr_registries: []
+"
+        );
+    }
+
+    #[test]
+    fn insert_uses_the_indentation_of_existing_entries() {
+        let content = "servers:\n- name: web\n  ip: 10.0.0.1\n";
+        let entry: Value = serde_yaml::from_str("name: app").unwrap();
+
+        let result =
+            insert_entries(content, ConfigFormat::Yaml, Section::Servers, &[entry]).unwrap();
+
+        assert_eq!(
+            result,
+            "servers:\n- name: web\n  ip: 10.0.0.1\n- name: app\n"
+        );
+    }
+
+    #[test]
+    fn insert_fills_an_empty_section() {
+        let content = "servers: []  # none yet\n";
+        let entry: Value = serde_yaml::from_str("name: app").unwrap();
+
+        let result =
+            insert_entries(content, ConfigFormat::Yaml, Section::Servers, &[entry]).unwrap();
+
+        assert_eq!(result, "servers:\n  - name: app\n");
+    }
+
+    #[test]
+    fn insert_creates_a_missing_section() {
+        let entry: Value = serde_yaml::from_str("name: app").unwrap();
+
+        let result =
+            insert_entries("version: 2", ConfigFormat::Yaml, Section::Servers, &[entry]).unwrap();
+
+        assert_eq!(result, "version: 2\n\nservers:\n  - name: app\n");
+    }
+
+    #[test]
+    fn insert_rejects_flow_style_sections() {
+        let content = "servers: [{name: web, ip: 10.0.0.1}]\n";
+        let entry: Value = serde_yaml::from_str("name: app").unwrap();
+
+        let error =
+            insert_entries(content, ConfigFormat::Yaml, Section::Servers, &[entry]).unwrap_err();
+
+        assert_eq!(error.error_type, StoolErrorType::ConfigParseError);
+    }
+
+    #[test]
+    fn replace_keeps_the_entries_around_it() {
+        let content = "\
+servers:
+  # web tier
+  - name: web
+    ip: 10.0.0.1
+    tags:
+      - web
+
+  # database
+  - name: db
+    ip: 10.0.0.2
+";
+        let entry: Value = serde_yaml::from_str("name: web\nip: 10.0.0.9").unwrap();
+
+        let result =
+            replace_entry(content, ConfigFormat::Yaml, Section::Servers, "web", &entry).unwrap();
+
+        assert_eq!(
+            result,
+            "\
+servers:
+  # web tier
+  - name: web
+    ip: 10.0.0.9
+
+  # database
+  - name: db
+    ip: 10.0.0.2
+"
+        );
+    }
+
+    #[test]
+    fn remove_keeps_the_comment_of_the_next_entry() {
+        let content = "\
+servers:
+  # web tier
+  - name: web
+    ip: 10.0.0.1
+
+  # database
+  - name: db
+    ip: 10.0.0.2
+";
+
+        let result = remove_entry(content, ConfigFormat::Yaml, Section::Servers, "web").unwrap();
+
+        assert_eq!(
+            result,
+            "\
+servers:
+  # database
+  - name: db
+    ip: 10.0.0.2
+"
+        );
+    }
+
+    #[test]
+    fn remove_takes_the_comment_above_the_entry() {
+        let content = "\
+servers:
+  - name: web
+    ip: 10.0.0.1
+
+  # database
+  - name: db
+    ip: 10.0.0.2
+
+# registries
+ecr_registries: []
+";
+
+        let result = remove_entry(content, ConfigFormat::Yaml, Section::Servers, "db").unwrap();
+
+        assert_eq!(
+            result,
+            "\
+servers:
+  - name: web
+    ip: 10.0.0.1
+
+# registries
+ecr_registries: []
+"
+        );
+    }
+
+    #[test]
+    fn removing_the_last_entry_leaves_an_empty_list() {
+        let content = "servers:\n  - name: web\n    ip: 10.0.0.1\nsso_configs: []\n";
+
+        let result = remove_entry(content, ConfigFormat::Yaml, Section::Servers, "web").unwrap();
+
+        assert_eq!(result, "servers: []\nsso_configs: []\n");
+    }
+
+    #[test]
+    fn removing_a_missing_entry_is_reported() {
+        let content = "servers:\n  - name: web\n    ip: 10.0.0.1\n";
+
+        let error = remove_entry(content, ConfigFormat::Yaml, Section::Servers, "db").unwrap_err();
+
+        assert_eq!(error.error_type, StoolErrorType::EntryNotFound);
+    }
+
+    #[test]
+    fn toml_files_are_edited_through_the_parsed_document() {
+        let content = "version = 2\n\n[[servers]]\nname = \"web\"\nip = \"10.0.0.1\"\n";
+        let entry: Value = serde_yaml::from_str("name: app\nip: 10.0.0.3").unwrap();
+
+        let added =
+            insert_entries(content, ConfigFormat::Toml, Section::Servers, &[entry]).unwrap();
+        let removed = remove_entry(&added, ConfigFormat::Toml, Section::Servers, "web").unwrap();
+
+        assert_eq!(
+            removed,
+            "version = 2\n\n[[servers]]\nname = \"app\"\nip = \"10.0.0.3\"\n"
+        );
+    }
+
+    #[test]
+    fn json_entries_are_found_by_name() {
+        let content = r#"{"servers": [{"name": "web"}]}"#;
+
+        let error = remove_entry(content, ConfigFormat::Json, Section::Servers, "db").unwrap_err();
+
+        assert_eq!(error.error_type, StoolErrorType::EntryNotFound);
+    }
+}
@@ -0,0 +1,182 @@
+//! Timeout and retry settings for external commands (the `execution:` block).
+//!
+//! ```yaml
+//! execution:
+//!   timeout: 10m          # applies to every operation
+//!   retries: 1
+//!   retry_delay: 2s       # doubled after each failed attempt
+//!   operations:
+//!     docker_push:
+//!       timeout: 30m
+//!       retries: 3
+//! ```
+//!
+//! Durations are written as `90s`, `5m`, `1h30m` or a plain number of
+//! seconds. Per-operation values win over the global ones, which win over
+//! the built-in defaults (no retries, network queries time out after 2m).
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Delay before the first retry when none is configured.
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(2);
+/// Time limit for short network queries when none is configured.
+const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// External command kinds that can be tuned separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    /// `docker build`
+    DockerBuild,
+    /// `docker tag`
+    DockerTag,
+    /// `docker push`
+    DockerPush,
+    /// `docker login` to ECR
+    DockerLogin,
+    /// `aws` queries for ECR (`describe-images`, `get-login-password`,
+    /// the SSO session check)
+    AwsEcr,
+    /// `brew update` / `brew upgrade`
+    Brew,
+    /// `rustup update`
+    Rustup,
+}
+
+impl Operation {
+    /// Returns the built-in timeout for the operation, if any.
+    fn default_timeout(self) -> Option<Duration> {
+        match self {
+            Self::DockerLogin | Self::AwsEcr => Some(DEFAULT_QUERY_TIMEOUT),
+            _ => None,
+        }
+    }
+}
+
+/// Timeout and retry settings as written in config; unset fields fall back.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyConfig {
+    /// Time limit for a single attempt.
+    #[serde(
+        default,
+        with = "optional_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timeout: Option<Duration>,
+    /// Extra attempts after a failure or timeout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// Wait before the first retry; doubled for each further retry.
+    #[serde(
+        default,
+        with = "optional_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub retry_delay: Option<Duration>,
+}
+
+impl PolicyConfig {
+    /// Returns `true` if no field is set.
+    pub fn is_empty(&self) -> bool {
+        self.timeout.is_none() && self.retries.is_none() && self.retry_delay.is_none()
+    }
+}
+
+/// The `execution:` block: global settings plus per-operation overrides.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionConfig {
+    #[serde(flatten)]
+    pub defaults: PolicyConfig,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub operations: BTreeMap<Operation, PolicyConfig>,
+}
+
+impl ExecutionConfig {
+    /// Returns `true` if nothing is configured.
+    pub fn is_empty(&self) -> bool {
+        self.defaults.is_empty() && self.operations.is_empty()
+    }
+
+    /// Resolves the effective policy for an operation.
+    pub fn policy(&self, operation: Operation) -> CommandPolicy {
+        let specific = self.operations.get(&operation);
+        let pick = |field: fn(&PolicyConfig) -> Option<Duration>| {
+            specific.and_then(field).or_else(|| field(&self.defaults))
+        };
+        CommandPolicy {
+            timeout: pick(|p| p.timeout).or_else(|| operation.default_timeout()),
+            retries: specific
+                .and_then(|p| p.retries)
+                .or(self.defaults.retries)
+                .unwrap_or(0),
+            retry_delay: pick(|p| p.retry_delay).unwrap_or(DEFAULT_RETRY_DELAY),
+        }
+    }
+}
+
+/// Effective timeout and retry policy for one external command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandPolicy {
+    /// Time limit for a single attempt; `None` waits indefinitely.
+    pub timeout: Option<Duration>,
+    /// Extra attempts after a failure or timeout.
+    pub retries: u32,
+    /// Wait before the first retry; doubled for each further retry.
+    pub retry_delay: Duration,
+}
+
+impl Default for CommandPolicy {
+    /// Runs once without a time limit.
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            retries: 0,
+            retry_delay: DEFAULT_RETRY_DELAY,
+        }
+    }
+}
+
+impl CommandPolicy {
+    /// Returns the wait before attempt `attempt` (2 for the first retry).
+    pub fn delay_before(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(2).min(16);
+        self.retry_delay.saturating_mul(1 << doublings)
+    }
+}
+
+/// Serde support for `Option<Duration>` written as text or seconds.
+mod optional_duration {
+    use crate::time::{HumanDuration, parse_duration};
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Seconds(u64),
+        Text(String),
+    }
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(d) => serializer.serialize_str(&HumanDuration(*d).to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        match Option::<Raw>::deserialize(deserializer)? {
+            None => Ok(None),
+            Some(Raw::Seconds(secs)) => Ok(Some(Duration::from_secs(secs))),
+            Some(Raw::Text(text)) => parse_duration(&text).map(Some).map_err(D::Error::custom),
+        }
+    }
+}
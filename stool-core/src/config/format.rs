@@ -0,0 +1,265 @@
+//! Config file formats.
+//!
+//! Config files may be written in YAML, TOML or JSON. Every format is parsed
+//! into the same YAML value model, so merging, migration and validation work
+//! the same regardless of the format a layer was written in.
+
+use crate::error::{Result, StoolError, StoolErrorType};
+use serde_yaml::Value;
+use std::fmt;
+use std::path::Path;
+
+/// Format of a config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConfigFormat {
+    #[default]
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// File extensions recognized as config files.
+    pub const EXTENSIONS: [&'static str; 4] = ["yaml", "yml", "toml", "json"];
+
+    /// Returns the format for a file extension (`.yaml`, `.yml`, `.toml`, `.json`).
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Some(Self::Yaml),
+            "toml" => Some(Self::Toml),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    /// Picks the format by file extension, falling back to the content.
+    ///
+    /// # Arguments
+    /// * `path` - File the content was read from, if any
+    /// * `content` - Raw file content
+    pub fn detect(path: Option<&Path>, content: &str) -> Self {
+        path.and_then(Self::from_path)
+            .unwrap_or_else(|| Self::sniff(content))
+    }
+
+    /// Guesses the format from the first significant line.
+    ///
+    /// `{` starts JSON; a `[table]` header or `key = value` line means TOML;
+    /// anything else is read as YAML.
+    fn sniff(content: &str) -> Self {
+        let Some(line) = content
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with('#'))
+        else {
+            return Self::Yaml;
+        };
+        if line.starts_with('{') {
+            return Self::Json;
+        }
+        let is_table = line.starts_with('[') && line.ends_with(']');
+        let is_assignment = line.split_once('=').is_some_and(|(key, _)| {
+            let key = key.trim();
+            !key.is_empty() && !key.contains([':', ' '])
+        });
+        if is_table || is_assignment {
+            Self::Toml
+        } else {
+            Self::Yaml
+        }
+    }
+
+    /// Parses content into a YAML value; blank content is `null`.
+    ///
+    /// # Errors
+    /// Returns `YamlParseError`, `TomlParseError` or `JsonParseError` if the
+    /// content is malformed
+    pub fn parse(&self, content: &str) -> Result<Value> {
+        if content.trim().is_empty() {
+            return Ok(Value::Null);
+        }
+        match self {
+            Self::Yaml => serde_yaml::from_str(content).map_err(|e| self.error(e)),
+            Self::Toml => toml::from_str(content).map_err(|e| self.error(e)),
+            Self::Json => serde_json::from_str(content).map_err(|e| self.error(e)),
+        }
+    }
+
+    /// Serializes a value in this format.
+    ///
+    /// # Errors
+    /// Returns the format's parse error type if the value cannot be
+    /// represented, e.g. `null` in TOML
+    pub fn render(&self, value: &Value) -> Result<String> {
+        let rendered = match self {
+            Self::Yaml => serde_yaml::to_string(value).map_err(|e| self.error(e)),
+            Self::Toml => toml::to_string(value).map_err(|e| self.error(e)),
+            Self::Json => serde_json::to_string_pretty(value)
+                .map(|json| json + "\n")
+                .map_err(|e| self.error(e)),
+        };
+        rendered.map_err(|e| {
+            let message = format!("Failed to serialize config as {}", self);
+            e.with_message(message)
+        })
+    }
+
+    /// Returns the error type used for this format.
+    pub fn error_type(&self) -> StoolErrorType {
+        match self {
+            Self::Yaml => StoolErrorType::YamlParseError,
+            Self::Toml => StoolErrorType::TomlParseError,
+            Self::Json => StoolErrorType::JsonParseError,
+        }
+    }
+
+    fn error(&self, source: impl std::error::Error + Send + Sync + 'static) -> StoolError {
+        StoolError::new(self.error_type())
+            .with_message(format!("Failed to parse {} config", self))
+            .with_source(source)
+    }
+}
+
+impl fmt::Display for ConfigFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Yaml => write!(f, "YAML"),
+            Self::Toml => write!(f, "TOML"),
+            Self::Json => write!(f, "JSON"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extensions_pick_the_format() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("config.YML")),
+            Some(ConfigFormat::Yaml)
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("servers.toml")),
+            Some(ConfigFormat::Toml)
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("/etc/stool/config.json")),
+            Some(ConfigFormat::Json)
+        );
+        assert_eq!(ConfigFormat::from_path(Path::new("config.yaml.bak")), None);
+        assert_eq!(ConfigFormat::from_path(Path::new("config")), None);
+    }
+
+    #[test]
+    fn json_is_sniffed_from_a_leading_brace() {
+        let content = "\n  {\n  \"version\": 2\n}";
+
+        assert_eq!(ConfigFormat::detect(None, content), ConfigFormat::Json);
+    }
+
+    #[test]
+    fn toml_is_sniffed_from_tables_and_assignments() {
+        assert_eq!(
+            ConfigFormat::detect(None, "[[servers]]\nname = \"web\""),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::detect(Some(Path::new("config.conf")), "# stool\nversion = 2"),
+            ConfigFormat::Toml
+        );
+    }
+
+    #[test]
+    fn yaml_is_the_fallback() {
+        // '=' inside a YAML value is not an assignment
+        assert_eq!(
+            ConfigFormat::detect(None, "url: https://example.com/?a=b"),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::detect(None, "- name: web"),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(ConfigFormat::detect(None, ""), ConfigFormat::Yaml);
+        assert_eq!(
+            ConfigFormat::detect(None, "# nothing yet\n"),
+            ConfigFormat::Yaml
+        );
+    }
+
+    #[test]
+    fn the_extension_wins_over_the_content() {
+        assert_eq!(
+            ConfigFormat::detect(Some(Path::new("config.yaml")), "version = 2"),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::detect(Some(Path::new("config.json")), "version: 2"),
+            ConfigFormat::Json
+        );
+    }
+
+    #[test]
+    fn toml_and_json_parse_to_the_yaml_value() {
+        let yaml = "version: 2\nservers:\n  - name: web\n    port: 2222\n";
+        let toml = "version = 2\n\n[[servers]]\nname = \"web\"\nport = 2222\n";
+        let json = r#"{"version": 2, "servers": [{"name": "web", "port": 2222}]}"#;
+
+        let expected = ConfigFormat::Yaml.parse(yaml).unwrap();
+
+        assert_eq!(ConfigFormat::Toml.parse(toml).unwrap(), expected);
+        assert_eq!(ConfigFormat::Json.parse(json).unwrap(), expected);
+    }
+
+    #[test]
+    fn rendered_toml_parses_back() {
+        let value = ConfigFormat::Yaml
+            .parse("version: 2\nservers:\n  - name: web\n    tags: [a, b]\n")
+            .unwrap();
+
+        let rendered = ConfigFormat::Toml.render(&value).unwrap();
+
+        assert_eq!(ConfigFormat::Toml.parse(&rendered).unwrap(), value);
+    }
+
+    #[test]
+    fn blank_content_is_null() {
+        assert_eq!(ConfigFormat::Toml.parse(" \n\t\n").unwrap(), Value::Null);
+        assert_eq!(ConfigFormat::Json.parse("").unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn malformed_json_reports_the_format() {
+        let error = ConfigFormat::Json.parse("{\"version\": 2,}").unwrap_err();
+
+        assert_eq!(error.error_type, StoolErrorType::JsonParseError);
+        assert_eq!(
+            error.message.as_deref(),
+            Some("Failed to parse JSON config")
+        );
+    }
+
+    #[test]
+    fn malformed_toml_reports_the_format() {
+        let error = ConfigFormat::Toml.parse("version = ").unwrap_err();
+
+        assert_eq!(error.error_type, StoolErrorType::TomlParseError);
+        assert_eq!(
+            error.message.as_deref(),
+            Some("Failed to parse TOML config")
+        );
+    }
+
+    #[test]
+    fn null_values_cannot_be_rendered_as_toml() {
+        let value = ConfigFormat::Yaml
+            .parse("servers:\n  - name: web\n    port: ~\n")
+            .unwrap();
+
+        let error = ConfigFormat::Toml.render(&value).unwrap_err();
+
+        assert_eq!(error.error_type, StoolErrorType::TomlParseError);
+    }
+}
@@ -0,0 +1,285 @@
+//! Line lookup for block-style YAML config files.
+//!
+//! `serde_yaml` does not keep source positions, so diagnostics locate
+//! entries by scanning the raw text of the layer they came from.
+//! Flow-style sections are not recognized and yield no location.
+
+/// Location of a list entry within a section.
+#[derive(Debug, Clone)]
+pub(crate) struct EntrySpan {
+    /// Index of the entry within the section list.
+    pub index: usize,
+    /// First line of the entry (0-based).
+    pub start: usize,
+    /// Line after the last line of the entry (0-based, exclusive).
+    pub end: usize,
+    /// Value of the entry's name field, if present.
+    pub name: Option<String>,
+    /// Indentation of the entry's fields.
+    field_indent: usize,
+}
+
+/// Returns the entries of the top-level list `section`.
+pub(crate) fn section_entries(content: &str, section: &str, name_field: &str) -> Vec<EntrySpan> {
+    let lines: Vec<&str> = content.lines().collect();
+    let Some(header) = section_header(content, section) else {
+        return Vec::new();
+    };
+
+    let mut entries: Vec<EntrySpan> = Vec::new();
+    let mut item_indent = None;
+
+    for (i, line) in lines.iter().enumerate().skip(header + 1) {
+        if is_blank(line) {
+            continue;
+        }
+        let indent = indent_of(line);
+        let body = &line[indent..];
+        if indent == 0 && !body.starts_with('-') {
+            break;
+        }
+
+        // An entry ends after its last field; blank lines and comments that
+        // follow belong to the next entry
+        if body.starts_with('-') && item_indent.is_none_or(|ii| ii == indent) {
+            item_indent = Some(indent);
+            let rest = body[1..].trim_start();
+            let field_indent = indent + (body.len() - rest.len());
+            entries.push(EntrySpan {
+                index: entries.len(),
+                start: i,
+                end: i + 1,
+                name: None,
+                field_indent,
+            });
+        }
+
+        if let Some(entry) = entries.last_mut() {
+            entry.end = i + 1;
+            if entry.name.is_none()
+                && let Some((key, value)) = field_at(line, entry.field_indent)
+                && key == name_field
+            {
+                entry.name = Some(value);
+            }
+        }
+    }
+
+    entries
+}
+
+/// Returns the line (0-based) of a top-level section key.
+pub(crate) fn section_header(content: &str, section: &str) -> Option<usize> {
+    content
+        .lines()
+        .position(|l| top_level_key(l).is_some_and(|k| k == section))
+}
+
+/// Returns the line (0-based) of `field` within an entry.
+///
+/// Nested fields are given as dotted paths (`auth.password`). Files that
+/// predate the nesting are handled by falling back to the last component
+/// at entry level.
+pub(crate) fn field_line(content: &str, entry: &EntrySpan, field: &str) -> Option<usize> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut start = entry.start;
+    let mut indent = entry.field_indent;
+    let mut found = None;
+
+    for (depth, key) in field.split('.').enumerate() {
+        if depth > 0 {
+            // Children are indented deeper than their parent key
+            let Some(child) = lines[start + 1..entry.end]
+                .iter()
+                .find(|l| !is_blank(l))
+                .map(|l| indent_of(l))
+                .filter(|&i| i > indent)
+            else {
+                found = None;
+                break;
+            };
+            start += 1;
+            indent = child;
+        }
+        found = (start..entry.end)
+            .take_while(|&i| i == start || is_blank(lines[i]) || indent_of(lines[i]) >= indent)
+            .find(|&i| field_at(lines[i], indent).is_some_and(|(k, _)| k == key));
+        match found {
+            Some(line) => start = line,
+            None => break,
+        }
+    }
+
+    found.or_else(|| {
+        let last = field.rsplit('.').next()?;
+        (last != field).then(|| field_line(content, entry, last))?
+    })
+}
+
+/// Finds an entry by name within a section.
+pub(crate) fn find_entry(
+    content: &str,
+    section: &str,
+    name_field: &str,
+    name: &str,
+) -> Option<EntrySpan> {
+    section_entries(content, section, name_field)
+        .into_iter()
+        .find(|e| e.name.as_deref() == Some(name))
+}
+
+fn top_level_key(line: &str) -> Option<&str> {
+    if line.starts_with([' ', '\t', '-', '#']) {
+        return None;
+    }
+    line.split_once(':').map(|(k, _)| k.trim())
+}
+
+/// Parses `key: value` if it is written at exactly `indent`.
+///
+/// The first field of an entry shares its line with the `-` marker.
+fn field_at(line: &str, indent: usize) -> Option<(String, String)> {
+    if line.len() <= indent || is_blank(line) {
+        return None;
+    }
+    let (prefix, body) = line.split_at(indent);
+    let prefix_ok = prefix.chars().all(|c| c == ' ')
+        || prefix.trim_end().ends_with('-') && prefix.trim_start().starts_with('-');
+    if !prefix_ok || body.starts_with([' ', '#', '-']) {
+        return None;
+    }
+    let (key, value) = body.split_once(':')?;
+    Some((unquote(key.trim()), unquote(strip_comment(value.trim()))))
+}
+
+fn strip_comment(value: &str) -> &str {
+    if value.starts_with(['"', '\'']) {
+        return value;
+    }
+    value.split(" #").next().unwrap_or(value).trim()
+}
+
+fn unquote(value: &str) -> String {
+    for quote in ['"', '\''] {
+        if let Some(rest) = value.strip_prefix(quote)
+            && let Some(end) = rest.find(quote)
+        {
+            return rest[..end].to_string();
+        }
+    }
+    value.to_string()
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn is_blank(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.is_empty() || trimmed.starts_with('#')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_span_their_fields_and_skip_nested_lists() {
+        let content = "\
+servers:
+  # primary web
+  - name: web-1
+    tags:
+      - web
+      - prod
+
+  - \"name\": \"db primary\"
+    ip: 10.0.0.2
+";
+
+        let entries = section_entries(content, "servers", "name");
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            (entries[0].index, entries[0].start, entries[0].end),
+            (0, 2, 6)
+        );
+        assert_eq!(entries[0].name.as_deref(), Some("web-1"));
+        assert_eq!(
+            (entries[1].index, entries[1].start, entries[1].end),
+            (1, 7, 9)
+        );
+        assert_eq!(entries[1].name.as_deref(), Some("db primary"));
+    }
+
+    #[test]
+    fn section_ends_at_the_next_top_level_key() {
+        let content = "servers:\n  - name: web\nname: other\n";
+
+        let entries = section_entries(content, "servers", "name");
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].end, 2);
+    }
+
+    #[test]
+    fn flow_style_and_missing_sections_have_no_entries() {
+        let content = "ecr_registries: [{name: dev}]\n";
+
+        assert!(section_entries(content, "ecr_registries", "name").is_empty());
+        assert!(section_entries(content, "servers", "name").is_empty());
+    }
+
+    #[test]
+    fn section_header_only_matches_top_level_keys() {
+        let content = "version: 2\nservers:\n  - name: web\n    tags: []\n";
+
+        assert_eq!(section_header(content, "servers"), Some(1));
+        assert_eq!(section_header(content, "tags"), None);
+    }
+
+    #[test]
+    fn field_lines_follow_dotted_paths() {
+        let content = "\
+servers:
+  - name: web
+    user: deploy
+    auth:
+      key_path: ~/.ssh/web.pem   # deploy key
+";
+        let entry = find_entry(content, "servers", "name", "web").unwrap();
+
+        assert_eq!(field_line(content, &entry, "name"), Some(1));
+        assert_eq!(field_line(content, &entry, "auth"), Some(3));
+        assert_eq!(field_line(content, &entry, "auth.key_path"), Some(4));
+        assert_eq!(field_line(content, &entry, "auth.password"), None);
+        assert_eq!(field_line(content, &entry, "port"), None);
+    }
+
+    #[test]
+    fn nested_fields_fall_back_to_entry_level() {
+        // Version 1 files keep credentials directly on the server
+        let content = "servers:\n  - name: db\n    password: secret\n";
+        let entry = find_entry(content, "servers", "name", "db").unwrap();
+
+        assert_eq!(field_line(content, &entry, "auth.password"), Some(2));
+    }
+
+    #[test]
+    fn field_values_drop_quotes_and_comments() {
+        assert_eq!(
+            field_at("    user: 'postgres'", 4),
+            Some(("user".to_string(), "postgres".to_string()))
+        );
+        assert_eq!(
+            field_at("  - \"name\": \"db # 1\"", 4),
+            Some(("name".to_string(), "db # 1".to_string()))
+        );
+        assert_eq!(
+            field_at("    key: value # note", 4),
+            Some(("key".to_string(), "value".to_string()))
+        );
+        assert_eq!(field_at("      nested: value", 4), None);
+        assert_eq!(field_at("    # key: value", 4), None);
+    }
+}
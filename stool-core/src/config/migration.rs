@@ -0,0 +1,286 @@
+//! Config schema versions and migrations.
+//!
+//! Every config file carries a top-level `version:`; files without one are
+//! version 1. Older files are upgraded in memory when loaded, one step at a
+//! time, so a file written for any earlier version keeps its meaning.
+//! `stool config migrate` writes the upgraded layout back to disk.
+//!
+//! Versions:
+//! - 1: Flat server credentials (`password`, `key_path`, `identity_agent`)
+//! - 2: Server credentials nested under `auth:`
+
+use super::format::ConfigFormat;
+use crate::error::{Result, StoolError, StoolErrorType};
+use serde_yaml::{Mapping, Value};
+
+/// Schema version written by this build.
+pub const CURRENT_VERSION: u32 = 2;
+
+/// Top-level key holding the schema version.
+pub const VERSION_KEY: &str = "version";
+
+/// Version assumed for files without a `version:` key.
+const UNVERSIONED: u32 = 1;
+
+/// Upgrades a mapping from `from` to `from + 1`.
+type Step = fn(&mut Mapping);
+
+/// Migration steps indexed by source version, with a description.
+const STEPS: &[(u32, &str, Step)] = &[(
+    1,
+    "move server password, key_path and identity_agent into auth blocks",
+    nest_server_auth,
+)];
+
+/// Returns the schema version of a top-level mapping.
+///
+/// # Errors
+/// Returns `ConfigParseError` if `version` is not a positive integer
+pub fn version_of(mapping: &Mapping) -> Result<u32> {
+    let Some(value) = mapping.get(VERSION_KEY) else {
+        return Ok(UNVERSIONED);
+    };
+    value
+        .as_u64()
+        .and_then(|v| u32::try_from(v).ok())
+        .filter(|&v| v > 0)
+        .ok_or_else(|| {
+            StoolError::new(StoolErrorType::ConfigParseError)
+                .with_message("version must be a positive integer")
+        })
+}
+
+/// Upgrades a top-level mapping to [`CURRENT_VERSION`] in place.
+///
+/// Returns the descriptions of the steps applied, empty if the mapping was
+/// already current.
+///
+/// # Errors
+/// Returns `ConfigParseError` if the version is invalid or newer than this
+/// build supports
+pub fn migrate(mapping: &mut Mapping) -> Result<Vec<&'static str>> {
+    let mut version = version_of(mapping)?;
+    if version > CURRENT_VERSION {
+        return Err(
+            StoolError::new(StoolErrorType::ConfigParseError).with_message(format!(
+                "Config version {} is newer than supported version {}; update stool",
+                version, CURRENT_VERSION
+            )),
+        );
+    }
+
+    let mut applied = Vec::new();
+    while version < CURRENT_VERSION {
+        if let Some((_, description, step)) = STEPS.iter().find(|(from, _, _)| *from == version) {
+            step(mapping);
+            applied.push(*description);
+        }
+        version += 1;
+    }
+    mapping.insert(Value::from(VERSION_KEY), Value::from(CURRENT_VERSION));
+    Ok(applied)
+}
+
+/// Migrates raw file content, returning the rewritten document if it changed.
+///
+/// The result is written in the same format and starts with `version`
+/// followed by the remaining keys in their original order. Comments are not
+/// preserved.
+///
+/// # Arguments
+/// * `content` - Raw file content
+/// * `format` - Format of the content, also used for the result
+///
+/// # Errors
+/// Returns error if the content is not a mapping or cannot be migrated
+pub fn migrate_document(
+    content: &str,
+    format: ConfigFormat,
+) -> Result<Option<(String, Vec<&'static str>)>> {
+    let mut mapping = match format.parse(content)? {
+        Value::Mapping(m) => m,
+        Value::Null => Mapping::new(),
+        _ => {
+            return Err(StoolError::new(StoolErrorType::ConfigParseError)
+                .with_message("Top level must be a mapping"));
+        }
+    };
+
+    if version_of(&mapping)? == CURRENT_VERSION {
+        return Ok(None);
+    }
+    let applied = migrate(&mut mapping)?;
+
+    let mut ordered = Mapping::new();
+    if let Some(version) = mapping.remove(VERSION_KEY) {
+        ordered.insert(Value::from(VERSION_KEY), version);
+    }
+    ordered.extend(mapping);
+
+    let rendered = format.render(&Value::Mapping(ordered))?;
+    Ok(Some((rendered, applied)))
+}
+
+/// v1 -> v2: nests flat server credentials under `auth:`.
+///
+/// Values already present in an `auth` block are kept.
+fn nest_server_auth(mapping: &mut Mapping) {
+    const AUTH_FIELDS: [&str; 3] = ["password", "key_path", "identity_agent"];
+
+    let Some(Value::Sequence(servers)) = mapping.get_mut("servers") else {
+        return;
+    };
+    for server in servers.iter_mut() {
+        let Value::Mapping(server) = server else {
+            continue;
+        };
+        let moved: Vec<(Value, Value)> = AUTH_FIELDS
+            .iter()
+            .filter_map(|field| server.remove(*field).map(|v| (Value::from(*field), v)))
+            .collect();
+        if moved.is_empty() {
+            continue;
+        }
+
+        let auth = server
+            .entry(Value::from("auth"))
+            .or_insert_with(|| Value::Mapping(Mapping::new()));
+        if !auth.is_mapping() {
+            *auth = Value::Mapping(Mapping::new());
+        }
+        if let Value::Mapping(auth) = auth {
+            for (key, value) in moved {
+                auth.entry(key).or_insert(value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_one_servers_get_auth_blocks() {
+        let content = "\
+servers:
+  - name: web
+    ip: 10.0.0.1
+    key_path: ~/.ssh/web.pem
+  - name: db
+    ip: 10.0.0.2
+    password: env:DB_PASSWORD
+    identity_agent: ~/agent.sock
+";
+
+        let (migrated, steps) = migrate_document(content, ConfigFormat::Yaml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(steps, [STEPS[0].1]);
+        assert_eq!(
+            migrated,
+            "\
+version: 2
+servers:
+- name: web
+  ip: 10.0.0.1
+  auth:
+    key_path: ~/.ssh/web.pem
+- name: db
+  ip: 10.0.0.2
+  auth:
+    password: env:DB_PASSWORD
+    identity_agent: ~/agent.sock
+"
+        );
+    }
+
+    #[test]
+    fn existing_auth_values_win_over_flat_ones() {
+        let content = "\
+servers:
+  - name: db
+    password: env:OLD
+    auth:
+      password: vault:db
+";
+
+        let (migrated, _) = migrate_document(content, ConfigFormat::Yaml)
+            .unwrap()
+            .unwrap();
+
+        let value: Value = serde_yaml::from_str(&migrated).unwrap();
+        assert_eq!(value["servers"][0]["auth"]["password"], "vault:db");
+        assert!(value["servers"][0].get("password").is_none());
+    }
+
+    #[test]
+    fn current_files_are_left_alone() {
+        let content = "version: 2\nservers: []\n";
+
+        assert_eq!(migrate_document(content, ConfigFormat::Yaml).unwrap(), None);
+    }
+
+    #[test]
+    fn empty_files_become_current() {
+        let (migrated, steps) = migrate_document("", ConfigFormat::Yaml).unwrap().unwrap();
+
+        assert_eq!(migrated, "version: 2\n");
+        assert_eq!(steps, [STEPS[0].1]);
+    }
+
+    #[test]
+    fn missing_version_means_version_one() {
+        assert_eq!(version_of(&Mapping::new()).unwrap(), 1);
+    }
+
+    #[test]
+    fn version_must_be_a_positive_integer() {
+        let content = "version: 0\n";
+
+        let error = migrate_document(content, ConfigFormat::Yaml).unwrap_err();
+
+        assert_eq!(error.error_type, StoolErrorType::ConfigParseError);
+        assert!(migrate_document("version: two\n", ConfigFormat::Yaml).is_err());
+        assert!(migrate_document("version: -1\n", ConfigFormat::Yaml).is_err());
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let mut mapping = Mapping::new();
+        mapping.insert(Value::from(VERSION_KEY), Value::from(CURRENT_VERSION + 1));
+
+        let error = migrate(&mut mapping).unwrap_err();
+
+        assert_eq!(error.error_type, StoolErrorType::ConfigParseError);
+        assert!(error.message.unwrap().contains("update stool"));
+    }
+
+    #[test]
+    fn toml_files_are_migrated_as_toml() {
+        let content = "[[servers]]\nname = \"web\"\npassword = \"env:WEB\"\n";
+
+        let (migrated, _) = migrate_document(content, ConfigFormat::Toml)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            migrated,
+            "version = 2\n\n[[servers]]\nname = \"web\"\n\n[servers.auth]\npassword = \"env:WEB\"\n"
+        );
+    }
+
+    #[test]
+    fn json_files_are_migrated_as_json() {
+        let content = r#"{"servers": [{"name": "web", "key_path": "~/web.pem"}]}"#;
+
+        let (migrated, _) = migrate_document(content, ConfigFormat::Json)
+            .unwrap()
+            .unwrap();
+
+        let value: Value = serde_json::from_str(&migrated).unwrap();
+        assert_eq!(value["version"], 2);
+        assert_eq!(value["servers"][0]["auth"]["key_path"], "~/web.pem");
+    }
+}
@@ -0,0 +1,868 @@
+//! Semantic validation of the resolved configuration.
+//!
+//! Catches configs that parse but are logically broken, e.g. duplicate
+//! names, dangling SSO profile references or malformed AWS identifiers.
+//! Each problem is reported with its YAML path and, where possible, the
+//! file and line it comes from.
+
+use super::format::ConfigFormat;
+use super::locate;
+use super::{Config, ConfigLayer, ConfigSource, Section, Server};
+use crate::error::{Result, StoolError, StoolErrorType};
+use crate::paths;
+use crate::secret::SecretRef;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Severity of a validation finding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The config must not be used.
+    Error,
+    /// The config is usable but likely not what was intended.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "Error"),
+            Self::Warning => write!(f, "Warning"),
+        }
+    }
+}
+
+/// A single validation finding.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// YAML path of the offending value, e.g. `servers[2].auth.key_path`.
+    pub path: String,
+    /// Layer the value was read from, if known.
+    pub source: Option<ConfigSource>,
+    /// 1-based line number within the source, if known.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// Formats as `severity: path: message (source, line N)`; the alternate
+/// form (`{:#}`) leaves out the severity, for output that shows it already.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !f.alternate() {
+            write!(f, "{}: ", self.severity)?;
+        }
+        write!(f, "{}: {}", self.path, self.message)?;
+        match (&self.source, self.line) {
+            (Some(source), Some(line)) => write!(f, " ({}, line {})", source, line),
+            (Some(source), None) => write!(f, " ({})", source),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Result of validating a configuration.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    /// Whether any finding is an error.
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// Returns all error findings.
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+    }
+
+    /// Returns all warning findings.
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Warning)
+    }
+
+    /// Converts the report into a result, keeping warnings on success.
+    ///
+    /// # Errors
+    /// Returns `ConfigParseError` listing every error finding
+    pub fn into_result(self) -> Result<Vec<Diagnostic>> {
+        if !self.has_errors() {
+            return Ok(self.diagnostics);
+        }
+
+        let lines: Vec<String> = self.errors().map(|d| format!("  {}", d)).collect();
+        Err(
+            StoolError::new(StoolErrorType::ConfigParseError).with_message(format!(
+                "{} config error(s)\n{}",
+                lines.len(),
+                lines.join("\n")
+            )),
+        )
+    }
+
+    fn push(&mut self, severity: Severity, at: Location, message: impl Into<String>) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            path: at.path,
+            source: at.source,
+            line: at.line,
+            message: message.into(),
+        });
+    }
+}
+
+/// Where a finding points to.
+struct Location {
+    path: String,
+    source: Option<ConfigSource>,
+    line: Option<usize>,
+}
+
+impl Config {
+    /// Runs semantic validation over the resolved configuration.
+    ///
+    /// Checks performed:
+    /// - Duplicate or empty entry names within a file and its includes (error)
+    /// - `EcrRegistry.sso_profile` not defined in `sso_configs` (error)
+    /// - Context registry or SSO profile not defined (error)
+    /// - Port 0, jump host cycles, invalid `ssh_options` names (error)
+    /// - Account IDs that are not 12 digits (error)
+    /// - Invalid AWS region names (error)
+    /// - Secret references with an empty target (error)
+    /// - `auth.key_path` or `file:` secret that does not exist (warning);
+    ///   skipped for embedded servers, whose paths refer to the build machine
+    /// - Both `auth.password` and `auth.key_path` set (warning)
+    /// - Context group without any server (warning)
+    pub fn validate(&self) -> Report {
+        let mut report = Report::default();
+
+        let mut groups: Vec<Vec<&ConfigLayer>> = Vec::new();
+        for layer in &self.layers {
+            match groups.last_mut() {
+                Some(group) if group[0].group == layer.group => group.push(layer),
+                _ => groups.push(vec![layer]),
+            }
+        }
+        for group in &groups {
+            check_duplicates(group, &mut report);
+        }
+        self.check_servers(&mut report);
+        self.check_registries(&mut report);
+        self.check_sso_configs(&mut report);
+        self.check_contexts(&mut report);
+
+        report
+    }
+
+    fn check_servers(&self, report: &mut Report) {
+        let section = Section::Servers;
+        for (i, server) in self.servers.iter().enumerate() {
+            let check_files =
+                self.source_of(section, &server.name) != Some(&ConfigSource::Embedded);
+            for (field, value) in [("ip", &server.ip), ("user", &server.user)] {
+                if value.trim().is_empty() {
+                    let at = self.locate(section, i, &server.name, Some(field));
+                    report.push(Severity::Error, at, format!("{} must not be empty", field));
+                }
+            }
+
+            if let Some(password) = &server.auth.password {
+                match SecretRef::parse(password) {
+                    SecretRef::Env("")
+                    | SecretRef::File("")
+                    | SecretRef::Cmd("")
+                    | SecretRef::Vault("") => {
+                        let at = self.locate(section, i, &server.name, Some("auth.password"));
+                        report.push(Severity::Error, at, "secret reference target is empty");
+                    }
+                    SecretRef::File(path) if check_files && !paths::expand_tilde(path).exists() => {
+                        let at = self.locate(section, i, &server.name, Some("auth.password"));
+                        report.push(
+                            Severity::Warning,
+                            at,
+                            format!("secret file does not exist: {}", path),
+                        );
+                    }
+                    _ => {}
+                }
+            }
+
+            let labels = [
+                ("group", server.group.as_ref()),
+                ("environment", server.environment.as_ref()),
+            ]
+            .into_iter()
+            .filter_map(|(field, value)| value.map(|v| (field, v)))
+            .chain(server.tags.iter().map(|t| ("tags", t)));
+            for (field, value) in labels {
+                if value.trim().is_empty() {
+                    let at = self.locate(section, i, &server.name, Some(field));
+                    report.push(Severity::Error, at, format!("{} must not be empty", field));
+                }
+            }
+
+            if server.port == Some(0) {
+                let at = self.locate(section, i, &server.name, Some("port"));
+                report.push(Severity::Error, at, "port must be between 1 and 65535");
+            }
+
+            if let Some(jump) = &server.proxy_jump {
+                let at = self.locate(section, i, &server.name, Some("proxy_jump"));
+                if jump.trim().is_empty() {
+                    report.push(Severity::Error, at, "proxy_jump must not be empty");
+                } else if let Some(cycle) = self.jump_cycle(server) {
+                    report.push(
+                        Severity::Error,
+                        at,
+                        format!("jump hosts form a cycle: {}", cycle.join(" -> ")),
+                    );
+                }
+            }
+
+            for key in server.ssh_options.keys() {
+                if key.is_empty() || key.contains(|c: char| c.is_whitespace() || c == '=') {
+                    let at = self.locate(section, i, &server.name, Some("ssh_options"));
+                    report.push(
+                        Severity::Error,
+                        at,
+                        format!("invalid ssh option name: '{}'", key),
+                    );
+                }
+            }
+
+            if let Some(key) = &server.auth.key_path {
+                if check_files && !paths::expand_tilde(key).exists() {
+                    let at = self.locate(section, i, &server.name, Some("auth.key_path"));
+                    report.push(
+                        Severity::Warning,
+                        at,
+                        format!("key file does not exist: {}", key),
+                    );
+                }
+                if server.auth.password.is_some() {
+                    let at = self.locate(section, i, &server.name, Some("auth.password"));
+                    report.push(
+                        Severity::Warning,
+                        at,
+                        "password is ignored because key_path is set",
+                    );
+                }
+            }
+        }
+    }
+
+    /// Follows `proxy_jump` references by name and returns them if they loop.
+    fn jump_cycle<'a>(&'a self, server: &'a Server) -> Option<Vec<&'a str>> {
+        let mut chain = vec![server.name.as_str()];
+        let mut current = server;
+        while let Some(next) = current.jump_server(&self.servers) {
+            chain.push(&next.name);
+            if next.name == server.name {
+                return Some(chain);
+            }
+            if chain[..chain.len() - 1].contains(&next.name.as_str()) {
+                // Loop further down the chain; reported on its own members
+                return None;
+            }
+            current = next;
+        }
+        None
+    }
+
+    fn check_registries(&self, report: &mut Report) {
+        let section = Section::EcrRegistries;
+        for (i, reg) in self.ecr_registries.iter().enumerate() {
+            if !is_account_id(&reg.account_id) {
+                let at = self.locate(section, i, &reg.name, Some("account_id"));
+                report.push(
+                    Severity::Error,
+                    at,
+                    format!("account ID must be 12 digits: '{}'", reg.account_id),
+                );
+            }
+            if !is_region(&reg.region) {
+                let at = self.locate(section, i, &reg.name, Some("region"));
+                report.push(
+                    Severity::Error,
+                    at,
+                    format!("invalid AWS region: '{}'", reg.region),
+                );
+            }
+            if let Some(profile) = &reg.sso_profile
+                && !self.sso_configs.iter().any(|s| &s.profile_name == profile)
+            {
+                let at = self.locate(section, i, &reg.name, Some("sso_profile"));
+                report.push(
+                    Severity::Error,
+                    at,
+                    format!("SSO profile '{}' is not defined in sso_configs", profile),
+                );
+            }
+        }
+    }
+
+    fn check_sso_configs(&self, report: &mut Report) {
+        let section = Section::SsoConfigs;
+        for (i, sso) in self.sso_configs.iter().enumerate() {
+            if !is_account_id(&sso.sso_account_id) {
+                let at = self.locate(section, i, &sso.profile_name, Some("sso_account_id"));
+                report.push(
+                    Severity::Error,
+                    at,
+                    format!("account ID must be 12 digits: '{}'", sso.sso_account_id),
+                );
+            }
+            if !is_region(&sso.region) {
+                let at = self.locate(section, i, &sso.profile_name, Some("region"));
+                report.push(
+                    Severity::Error,
+                    at,
+                    format!("invalid AWS region: '{}'", sso.region),
+                );
+            }
+        }
+    }
+
+    fn check_contexts(&self, report: &mut Report) {
+        let section = Section::Contexts;
+        for (i, ctx) in self.contexts.iter().enumerate() {
+            if let Some(group) = &ctx.group
+                && !self.servers.iter().any(|s| {
+                    s.group
+                        .as_ref()
+                        .is_some_and(|g| g.eq_ignore_ascii_case(group))
+                })
+            {
+                let at = self.locate(section, i, &ctx.name, Some("group"));
+                report.push(
+                    Severity::Warning,
+                    at,
+                    format!("no server is in group '{}'", group),
+                );
+            }
+            if let Some(registry) = &ctx.ecr_registry
+                && !self.ecr_registries.iter().any(|r| &r.name == registry)
+            {
+                let at = self.locate(section, i, &ctx.name, Some("ecr_registry"));
+                report.push(
+                    Severity::Error,
+                    at,
+                    format!(
+                        "ECR registry '{}' is not defined in ecr_registries",
+                        registry
+                    ),
+                );
+            }
+            if let Some(profile) = &ctx.sso_profile
+                && !self.sso_configs.iter().any(|c| &c.profile_name == profile)
+            {
+                let at = self.locate(section, i, &ctx.name, Some("sso_profile"));
+                report.push(
+                    Severity::Error,
+                    at,
+                    format!("SSO profile '{}' is not defined in sso_configs", profile),
+                );
+            }
+        }
+    }
+
+    /// Locates a field of a merged entry.
+    ///
+    /// Searches layers from highest priority down, so the reported line is
+    /// the one whose value actually took effect.
+    /// Paths use the entry's index within the file it was found in, so they
+    /// agree with the reported line; the merged index is used otherwise.
+    fn locate(&self, section: Section, index: usize, name: &str, field: Option<&str>) -> Location {
+        let path = |index: usize| match field {
+            Some(field) => format!("{}[{}].{}", section.key(), index, field),
+            None => format!("{}[{}]", section.key(), index),
+        };
+
+        for layer in self.layers.iter().rev() {
+            // Only YAML sources can be scanned for lines
+            if layer.format != ConfigFormat::Yaml {
+                if self.source_of(section, name) == Some(&layer.source) {
+                    return Location {
+                        path: path(index),
+                        source: Some(layer.source.clone()),
+                        line: None,
+                    };
+                }
+                continue;
+            }
+            let Some(entry) =
+                locate::find_entry(&layer.content, section.key(), section.name_field(), name)
+            else {
+                continue;
+            };
+            // Imported hosts are synthesized, so their lines mean nothing
+            if matches!(layer.source, ConfigSource::SshConfig(_)) {
+                return Location {
+                    path: path(index),
+                    source: Some(layer.source.clone()),
+                    line: None,
+                };
+            }
+            let line = match field {
+                Some(f) => match locate::field_line(&layer.content, &entry, f) {
+                    Some(line) => line,
+                    None => continue,
+                },
+                None => entry.start,
+            };
+            return Location {
+                path: path(entry.index),
+                source: Some(layer.source.clone()),
+                line: Some(line + 1),
+            };
+        }
+
+        Location {
+            path: path(index),
+            source: self.source_of(section, name).cloned(),
+            line: None,
+        }
+    }
+}
+
+/// Reports entries that share a name (or have none) within one group.
+///
+/// A group is a top-level file with its includes and fragments, so a
+/// duplicate may span two files; both are named in the finding.
+fn check_duplicates(group: &[&ConfigLayer], report: &mut Report) {
+    for section in Section::ALL {
+        let mut seen: HashMap<String, (&ConfigLayer, usize, Option<usize>)> = HashMap::new();
+
+        for layer in group {
+            for (index, name, line) in layer_entries(layer, section) {
+                let at = Location {
+                    path: format!("{}[{}].{}", section.key(), index, section.name_field()),
+                    source: Some(layer.source.clone()),
+                    line,
+                };
+                let name = match name {
+                    Some(name) if !name.is_empty() => name,
+                    _ => {
+                        report.push(
+                            Severity::Error,
+                            at,
+                            format!("{} must not be empty", section.name_field()),
+                        );
+                        continue;
+                    }
+                };
+
+                if let Some((first_layer, first_index, first_line)) = seen.get(&name) {
+                    let mut place = if first_layer.source == layer.source {
+                        String::new()
+                    } else {
+                        format!(" in {}", first_layer.source)
+                    };
+                    if let Some(line) = first_line {
+                        place.push_str(&format!(", line {}", line));
+                    }
+                    report.push(
+                        Severity::Error,
+                        at,
+                        format!(
+                            "duplicate name '{}' (first defined at {}[{}]{})",
+                            name,
+                            section.key(),
+                            first_index,
+                            place
+                        ),
+                    );
+                } else {
+                    seen.insert(name, (layer, index, line));
+                }
+            }
+        }
+    }
+}
+
+/// Returns index, name and line (1-based) of each entry of a layer's section.
+///
+/// Entries are read from the parsed value, so every layout is covered;
+/// lines are attached where the YAML text can be scanned for them.
+fn layer_entries(
+    layer: &ConfigLayer,
+    section: Section,
+) -> Vec<(usize, Option<String>, Option<usize>)> {
+    let Ok(mapping) = super::parse_layer(layer) else {
+        return Vec::new();
+    };
+    let Some(entries) = mapping.get(section.key()).and_then(|v| v.as_sequence()) else {
+        return Vec::new();
+    };
+    let spans = match layer.format {
+        ConfigFormat::Yaml => {
+            locate::section_entries(&layer.content, section.key(), section.name_field())
+        }
+        _ => Vec::new(),
+    };
+
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let name = entry
+                .get(section.name_field())
+                .and_then(|v| v.as_str())
+                .map(str::to_string);
+            let line = spans
+                .iter()
+                .find(|s| s.index == i && s.name == name)
+                .map(|s| s.start + 1);
+            (i, name, line)
+        })
+        .collect()
+}
+
+fn is_account_id(value: &str) -> bool {
+    value.len() == 12 && value.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Checks the shape of an AWS region name, e.g. `ap-northeast-2` or `us-gov-west-1`.
+fn is_region(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('-').collect();
+    let [prefix, middle @ .., number] = parts.as_slice() else {
+        return false;
+    };
+    prefix.len() == 2
+        && prefix.bytes().all(|b| b.is_ascii_lowercase())
+        && !middle.is_empty()
+        && middle
+            .iter()
+            .all(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_lowercase()))
+        && !number.is_empty()
+        && number.bytes().all(|b| b.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn layer(path: &str, content: &str, group: usize) -> ConfigLayer {
+        let path = PathBuf::from(path);
+        ConfigLayer {
+            format: ConfigFormat::detect(Some(&path), content),
+            source: ConfigSource::Cli(path),
+            content: content.to_string(),
+            group,
+        }
+    }
+
+    fn validate(content: &str) -> Report {
+        let layer = layer("/etc/stool/config.yaml", content, 1);
+        Config::from_layers(vec![layer]).unwrap().validate()
+    }
+
+    #[test]
+    fn duplicate_names_point_at_both_entries() {
+        let report = validate(
+            "\
+servers:
+  - name: web
+    ip: 10.0.0.1
+    user: deploy
+  - name: web
+    ip: 10.0.0.2
+    user: deploy
+",
+        );
+
+        let errors: Vec<String> = report.errors().map(|d| d.to_string()).collect();
+        assert_eq!(
+            errors,
+            [
+                "Error: servers[1].name: duplicate name 'web' (first defined at servers[0], line 2) \
+                 (/etc/stool/config.yaml (--config), line 5)"
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_names_in_flow_style_sections_are_reported() {
+        let report = validate(
+            "ecr_registries: [{name: a, account_id: '123456789012', region: us-east-1}, \
+             {name: a, account_id: '123456789012', region: us-east-2}]\n",
+        );
+
+        let errors: Vec<&Diagnostic> = report.errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, "ecr_registries[1].name");
+        assert_eq!(errors[0].line, None);
+        assert!(errors[0].message.starts_with("duplicate name 'a'"));
+    }
+
+    #[test]
+    fn duplicate_names_across_an_include_name_the_first_file() {
+        let main = layer(
+            "/etc/stool/config.yaml",
+            "servers:\n  - name: web\n    ip: 10.0.0.1\n    user: deploy\n",
+            1,
+        );
+        let include = layer(
+            "/etc/stool/more.json",
+            r#"{"servers": [{"name": "web", "ip": "10.0.0.2", "user": "deploy"}]}"#,
+            1,
+        );
+
+        let report = Config::from_layers(vec![main, include]).unwrap().validate();
+
+        let errors: Vec<&Diagnostic> = report.errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message,
+            "duplicate name 'web' (first defined at servers[0] in /etc/stool/config.yaml (--config), line 2)"
+        );
+    }
+
+    #[test]
+    fn later_groups_may_override_names() {
+        let base = layer(
+            "/etc/stool/config.yaml",
+            "servers:\n  - name: web\n    ip: 10.0.0.1\n    user: deploy\n",
+            1,
+        );
+        let user = layer(
+            "/home/me/.config/stool/config.yaml",
+            "servers:\n  - name: web\n    ip: 10.0.0.9\n",
+            2,
+        );
+
+        let config = Config::from_layers(vec![base, user]).unwrap();
+
+        assert!(config.validate().diagnostics.is_empty());
+        assert_eq!(config.servers[0].ip, "10.0.0.9");
+    }
+
+    #[test]
+    fn empty_names_are_errors() {
+        let report = validate("servers:\n  - name: ''\n    ip: 10.0.0.1\n    user: deploy\n");
+
+        let errors: Vec<&Diagnostic> = report.errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, "servers[0].name");
+        assert_eq!(errors[0].message, "name must not be empty");
+    }
+
+    #[test]
+    fn empty_fields_are_reported_with_path_and_line() {
+        let report = validate(
+            "\
+servers:
+  - name: web
+    ip: 10.0.0.1
+    user: deploy
+  - name: db
+    ip: ''
+    user: postgres
+",
+        );
+
+        let errors: Vec<&Diagnostic> = report.errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, "servers[1].ip");
+        assert_eq!(errors[0].line, Some(6));
+        assert_eq!(errors[0].message, "ip must not be empty");
+    }
+
+    #[test]
+    fn undefined_sso_profiles_are_errors() {
+        let report = validate(
+            "\
+ecr_registries:
+  - name: dev
+    account_id: '123456789012'
+    region: ap-northeast-2
+    sso_profile: missing
+contexts:
+  - name: dev
+    sso_profile: missing
+",
+        );
+
+        let errors: Vec<String> = report.errors().map(|d| format!("{:#}", d)).collect();
+        assert_eq!(
+            errors,
+            [
+                "ecr_registries[0].sso_profile: SSO profile 'missing' is not defined in sso_configs \
+                 (/etc/stool/config.yaml (--config), line 5)",
+                "contexts[0].sso_profile: SSO profile 'missing' is not defined in sso_configs \
+                 (/etc/stool/config.yaml (--config), line 8)",
+            ]
+        );
+    }
+
+    #[test]
+    fn account_ids_and_regions_are_checked() {
+        let report = validate(
+            "\
+ecr_registries:
+  - name: dev
+    account_id: '12345'
+    region: Seoul
+",
+        );
+
+        let paths: Vec<&str> = report.errors().map(|d| d.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["ecr_registries[0].account_id", "ecr_registries[0].region"]
+        );
+    }
+
+    #[test]
+    fn account_ids_have_twelve_digits() {
+        assert!(is_account_id("123456789012"));
+        assert!(!is_account_id("12345678901"));
+        assert!(!is_account_id("1234567890123"));
+        assert!(!is_account_id("12345678901a"));
+    }
+
+    #[test]
+    fn regions_have_a_prefix_a_name_and_a_number() {
+        assert!(is_region("ap-northeast-2"));
+        assert!(is_region("us-gov-west-1"));
+        assert!(!is_region("us-east"));
+        assert!(!is_region("US-EAST-1"));
+        assert!(!is_region("useast-1"));
+        assert!(!is_region("us--1"));
+        assert!(!is_region("us-east-"));
+        assert!(!is_region(""));
+    }
+
+    #[test]
+    fn secret_references_need_a_target() {
+        let report = validate(
+            "\
+servers:
+  - name: web
+    ip: 10.0.0.1
+    user: deploy
+    auth:
+      password: 'env:'
+  - name: db
+    ip: 10.0.0.2
+    user: postgres
+    auth:
+      password: env:DB_PASSWORD
+",
+        );
+
+        let errors: Vec<&Diagnostic> = report.errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, "servers[0].auth.password");
+        assert_eq!(errors[0].line, Some(6));
+        assert_eq!(errors[0].message, "secret reference target is empty");
+    }
+
+    #[test]
+    fn jump_host_cycles_are_reported_on_each_member() {
+        let report = validate(
+            "\
+servers:
+  - name: a
+    ip: 10.0.0.1
+    user: deploy
+    proxy_jump: b
+  - name: b
+    ip: 10.0.0.2
+    user: deploy
+    proxy_jump: a
+  - name: c
+    ip: 10.0.0.3
+    user: deploy
+    proxy_jump: a
+",
+        );
+
+        let errors: Vec<String> = report.errors().map(|d| format!("{:#}", d)).collect();
+        assert_eq!(
+            errors,
+            [
+                "servers[0].proxy_jump: jump hosts form a cycle: a -> b -> a \
+                 (/etc/stool/config.yaml (--config), line 5)",
+                "servers[1].proxy_jump: jump hosts form a cycle: b -> a -> b \
+                 (/etc/stool/config.yaml (--config), line 9)",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_key_files_are_warnings() {
+        let report = validate(
+            "\
+servers:
+  - name: web
+    ip: 10.0.0.1
+    user: deploy
+    auth:
+      key_path: /nonexistent/web.pem
+",
+        );
+
+        assert!(!report.has_errors());
+        let warnings: Vec<&Diagnostic> = report.warnings().collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, "servers[0].auth.key_path");
+        assert_eq!(warnings[0].line, Some(6));
+    }
+
+    #[test]
+    fn embedded_servers_skip_file_checks() {
+        let layer = ConfigLayer {
+            source: ConfigSource::Embedded,
+            content: "servers:\n  - name: web\n    ip: 10.0.0.1\n    user: deploy\n    \
+                      auth:\n      key_path: /nonexistent/web.pem\n"
+                .to_string(),
+            format: ConfigFormat::Yaml,
+            group: 0,
+        };
+
+        let report = Config::from_layers(vec![layer]).unwrap().validate();
+
+        assert!(report.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn toml_findings_have_a_path_but_no_line() {
+        let layer = layer(
+            "/etc/stool/config.toml",
+            "[[servers]]\nname = \"web\"\nip = \"10.0.0.1\"\nuser = \"deploy\"\nport = 0\n",
+            1,
+        );
+
+        let report = Config::from_layers(vec![layer]).unwrap().validate();
+
+        let errors: Vec<String> = report.errors().map(|d| format!("{:#}", d)).collect();
+        assert_eq!(
+            errors,
+            [
+                "servers[0].port: port must be between 1 and 65535 (/etc/stool/config.toml (--config))"
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_are_listed_in_the_result() {
+        let report = validate("servers:\n  - name: web\n    ip: ''\n    user: deploy\n");
+
+        let error = report.into_result().unwrap_err();
+
+        assert_eq!(error.error_type, StoolErrorType::ConfigParseError);
+        assert_eq!(
+            error.message.unwrap(),
+            "1 config error(s)\n  Error: servers[0].ip: ip must not be empty \
+             (/etc/stool/config.yaml (--config), line 3)"
+        );
+    }
+}
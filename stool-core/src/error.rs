@@ -2,8 +2,13 @@
 //!
 //! This module provides a unified error handling system using [`StoolError`]
 //! and [`StoolErrorType`] enum for all stool operations.
+//!
+//! Each error type maps to a stable process exit code (see
+//! [`StoolErrorType::exit_code`]) so scripts can tell failures apart.
 
+use crate::time::HumanDuration;
 use std::fmt;
+use std::time::Duration;
 
 /// Error types for all stool operations.
 ///
@@ -16,7 +21,12 @@ pub enum StoolErrorType {
     SshConnectionFailed,
     SshAuthenticationFailed,
     ServerNotFound,
+    /// Never produced since passwords are served by the askpass helper;
+    /// kept so its exit code stays reserved.
+    #[deprecated(note = "password authentication no longer uses expect; see `AskpassFailed`")]
     ExpectCommandFailed,
+    AskpassFailed,
+    RemoteCommandFailed,
 
     // File search related
     FileNotFound,
@@ -32,6 +42,17 @@ pub enum StoolErrorType {
     ConfigLoadFailed,
     ConfigParseError,
     YamlParseError,
+    TomlParseError,
+    JsonParseError,
+    SecretResolutionFailed,
+    EntryNotFound,
+
+    // History related
+    HistoryEntryNotFound,
+
+    // Vault related
+    VaultNotFound,
+    VaultAccessFailed,
 
     // Update related
     BrewUpdateFailed,
@@ -53,13 +74,72 @@ pub enum StoolErrorType {
     Cancelled,
 }
 
+impl StoolErrorType {
+    /// Returns the process exit code for this error type.
+    ///
+    /// Codes group related failures and are part of the CLI interface;
+    /// existing assignments must not change.
+    ///
+    /// | Code | Meaning |
+    /// |------|---------|
+    /// | 1 | External command failed (`stool ssh exec`: the remote exit code) |
+    /// | 2 | Command-line usage error (reported by clap) |
+    /// | 3 | Invalid input |
+    /// | 4 | Server, file, entry or vault not found |
+    /// | 5 | Authentication or secret access failed |
+    /// | 6 | Required tool not installed |
+    /// | 7 | Config error |
+    /// | 8 | SSH connection failed |
+    /// | 9 | File transfer failed |
+    /// | 10 | I/O error |
+    /// | 11 | Permission denied |
+    /// | 130 | Cancelled by the user |
+    #[allow(deprecated)]
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::CommandExecutionFailed
+            | Self::ExpectCommandFailed
+            | Self::BrewUpdateFailed
+            | Self::RustupUpdateFailed
+            | Self::DockerCommandFailed
+            | Self::AwsCommandFailed
+            | Self::RemoteCommandFailed => 1,
+            Self::InvalidInput | Self::SearchPatternInvalid => 3,
+            Self::ServerNotFound
+            | Self::FileNotFound
+            | Self::SourceFileNotFound
+            | Self::EntryNotFound
+            | Self::HistoryEntryNotFound
+            | Self::VaultNotFound => 4,
+            Self::SshAuthenticationFailed
+            | Self::AskpassFailed
+            | Self::SecretResolutionFailed
+            | Self::VaultAccessFailed => 5,
+            Self::DockerNotInstalled | Self::AwsCliNotInstalled => 6,
+            Self::ConfigLoadFailed
+            | Self::ConfigParseError
+            | Self::YamlParseError
+            | Self::TomlParseError
+            | Self::JsonParseError => 7,
+            Self::SshConnectionFailed => 8,
+            Self::FileTransferFailed | Self::ScpCommandFailed | Self::SftpCommandFailed => 9,
+            Self::IoError => 10,
+            Self::PermissionDenied => 11,
+            Self::Cancelled => 130,
+        }
+    }
+}
+
 impl fmt::Display for StoolErrorType {
+    #[allow(deprecated)]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::SshConnectionFailed => write!(f, "SSH connection failed"),
             Self::SshAuthenticationFailed => write!(f, "SSH authentication failed"),
             Self::ServerNotFound => write!(f, "Server not found"),
             Self::ExpectCommandFailed => write!(f, "expect command failed"),
+            Self::AskpassFailed => write!(f, "Password helper failed"),
+            Self::RemoteCommandFailed => write!(f, "Remote command failed"),
 
             Self::FileNotFound => write!(f, "File not found"),
             Self::SearchPatternInvalid => write!(f, "Invalid search pattern"),
@@ -72,6 +152,15 @@ impl fmt::Display for StoolErrorType {
             Self::ConfigLoadFailed => write!(f, "Config load failed"),
             Self::ConfigParseError => write!(f, "Config parse error"),
             Self::YamlParseError => write!(f, "YAML parse error"),
+            Self::TomlParseError => write!(f, "TOML parse error"),
+            Self::JsonParseError => write!(f, "JSON parse error"),
+            Self::SecretResolutionFailed => write!(f, "Secret resolution failed"),
+            Self::EntryNotFound => write!(f, "Config entry not found"),
+
+            Self::HistoryEntryNotFound => write!(f, "History entry not found"),
+
+            Self::VaultNotFound => write!(f, "Vault not found"),
+            Self::VaultAccessFailed => write!(f, "Vault access failed"),
 
             Self::BrewUpdateFailed => write!(f, "brew update failed"),
             Self::RustupUpdateFailed => write!(f, "rustup update failed"),
@@ -91,15 +180,46 @@ impl fmt::Display for StoolErrorType {
     }
 }
 
+/// Exit code and trailing stderr of a failed child process.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessFailure {
+    /// Exit code, or `None` if the process was terminated by a signal.
+    pub code: Option<i32>,
+    /// Last lines the process wrote to stderr.
+    pub stderr: Vec<String>,
+    /// Time limit the process was killed after, if it timed out.
+    pub timed_out: Option<Duration>,
+    /// Number of attempts made, including retries (0 if not tracked).
+    pub attempts: u32,
+}
+
+impl fmt::Display for ProcessFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.timed_out, self.code) {
+            (Some(limit), _) => write!(f, "timed out after {}", HumanDuration(limit))?,
+            (None, Some(code)) => write!(f, "exited with code {}", code)?,
+            (None, None) => write!(f, "terminated by signal")?,
+        }
+        if self.attempts > 1 {
+            write!(f, " (after {} attempts)", self.attempts)?;
+        }
+        Ok(())
+    }
+}
+
 /// Error type with additional context.
 ///
-/// Wraps a [`StoolErrorType`] with optional message and source error
-/// for detailed error reporting.
+/// Wraps a [`StoolErrorType`] with optional message, source error, failed
+/// process output and remediation hint for detailed error reporting.
 #[derive(Debug)]
 pub struct StoolError {
     pub error_type: StoolErrorType,
     pub message: Option<String>,
     pub source: Option<Box<dyn std::error::Error + Send + Sync>>,
+    /// Exit code and stderr of the external command that failed.
+    pub process: Option<Box<ProcessFailure>>,
+    /// Suggested remediation shown after the error.
+    pub hint: Option<String>,
 }
 
 impl StoolError {
@@ -109,6 +229,8 @@ impl StoolError {
             error_type,
             message: None,
             source: None,
+            process: None,
+            hint: None,
         }
     }
 
@@ -123,6 +245,60 @@ impl StoolError {
         self.source = Some(Box::new(source));
         self
     }
+
+    /// Attaches the exit code and stderr of a failed external command.
+    pub fn with_process(mut self, process: ProcessFailure) -> Self {
+        self.process = Some(Box::new(process));
+        self
+    }
+
+    /// Adds a remediation hint telling the user how to fix the error.
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    /// Returns the process exit code for this error.
+    ///
+    /// A failed remote command passes on its own exit code, so
+    /// `stool ssh exec` exits like the command it ran.
+    pub fn exit_code(&self) -> u8 {
+        if self.error_type == StoolErrorType::RemoteCommandFailed
+            && let Some(code) = self.process.as_ref().and_then(|p| p.code)
+            && let Ok(code) = u8::try_from(code)
+            && code != 0
+        {
+            return code;
+        }
+        self.error_type.exit_code()
+    }
+
+    /// Formats the error followed by its chain of sources, one per line,
+    /// the failed command's exit code and stderr, and the hint if attached.
+    ///
+    /// ```text
+    /// Command execution failed: Docker command failed: docker push app:1.0.0
+    ///   exited with code 1
+    ///   | denied: Your authorization token has expired.
+    /// ```
+    pub fn report(&self) -> String {
+        let mut report = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(err) = source {
+            report.push_str(&format!("\n  caused by: {}", err));
+            source = err.source();
+        }
+        if let Some(process) = &self.process {
+            report.push_str(&format!("\n  {}", process));
+            for line in &process.stderr {
+                report.push_str(&format!("\n  | {}", line));
+            }
+        }
+        if let Some(hint) = &self.hint {
+            report.push_str(&format!("\n  hint: {}", hint));
+        }
+        report
+    }
 }
 
 impl fmt::Display for StoolError {
@@ -153,3 +329,113 @@ impl From<std::io::Error> for StoolError {
 ///
 /// Uses [`StoolError`] as the error type for all stool functions.
 pub type Result<T> = std::result::Result<T, StoolError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(deprecated)]
+    fn error_types_map_to_stable_exit_codes() {
+        use StoolErrorType::*;
+
+        // Part of the CLI interface: these values must never change
+        assert_eq!(CommandExecutionFailed.exit_code(), 1);
+        assert_eq!(ExpectCommandFailed.exit_code(), 1);
+        assert_eq!(BrewUpdateFailed.exit_code(), 1);
+        assert_eq!(RustupUpdateFailed.exit_code(), 1);
+        assert_eq!(DockerCommandFailed.exit_code(), 1);
+        assert_eq!(AwsCommandFailed.exit_code(), 1);
+        assert_eq!(RemoteCommandFailed.exit_code(), 1);
+        assert_eq!(InvalidInput.exit_code(), 3);
+        assert_eq!(SearchPatternInvalid.exit_code(), 3);
+        assert_eq!(ServerNotFound.exit_code(), 4);
+        assert_eq!(FileNotFound.exit_code(), 4);
+        assert_eq!(SourceFileNotFound.exit_code(), 4);
+        assert_eq!(EntryNotFound.exit_code(), 4);
+        assert_eq!(HistoryEntryNotFound.exit_code(), 4);
+        assert_eq!(VaultNotFound.exit_code(), 4);
+        assert_eq!(SshAuthenticationFailed.exit_code(), 5);
+        assert_eq!(AskpassFailed.exit_code(), 5);
+        assert_eq!(SecretResolutionFailed.exit_code(), 5);
+        assert_eq!(VaultAccessFailed.exit_code(), 5);
+        assert_eq!(DockerNotInstalled.exit_code(), 6);
+        assert_eq!(AwsCliNotInstalled.exit_code(), 6);
+        assert_eq!(ConfigLoadFailed.exit_code(), 7);
+        assert_eq!(ConfigParseError.exit_code(), 7);
+        assert_eq!(YamlParseError.exit_code(), 7);
+        assert_eq!(TomlParseError.exit_code(), 7);
+        assert_eq!(JsonParseError.exit_code(), 7);
+        assert_eq!(SshConnectionFailed.exit_code(), 8);
+        assert_eq!(FileTransferFailed.exit_code(), 9);
+        assert_eq!(ScpCommandFailed.exit_code(), 9);
+        assert_eq!(SftpCommandFailed.exit_code(), 9);
+        assert_eq!(IoError.exit_code(), 10);
+        assert_eq!(PermissionDenied.exit_code(), 11);
+        assert_eq!(Cancelled.exit_code(), 130);
+    }
+
+    #[test]
+    fn failed_remote_commands_pass_on_their_exit_code() {
+        let failure = |code| {
+            StoolError::new(StoolErrorType::RemoteCommandFailed).with_process(ProcessFailure {
+                code,
+                ..ProcessFailure::default()
+            })
+        };
+
+        assert_eq!(failure(Some(42)).exit_code(), 42);
+        // Killed by a signal, or a code that does not fit an exit status
+        assert_eq!(failure(None).exit_code(), 1);
+        assert_eq!(failure(Some(300)).exit_code(), 1);
+        assert_eq!(
+            StoolError::new(StoolErrorType::RemoteCommandFailed).exit_code(),
+            1
+        );
+    }
+
+    #[test]
+    fn other_errors_ignore_the_process_exit_code() {
+        let error =
+            StoolError::new(StoolErrorType::SshConnectionFailed).with_process(ProcessFailure {
+                code: Some(255),
+                ..ProcessFailure::default()
+            });
+
+        assert_eq!(error.exit_code(), 8);
+    }
+
+    #[test]
+    fn report_lists_causes_process_output_and_hint() {
+        let error = StoolError::new(StoolErrorType::DockerCommandFailed)
+            .with_message("docker push app:1.0.0")
+            .with_source(std::io::Error::other("connection reset"))
+            .with_process(ProcessFailure {
+                code: Some(1),
+                stderr: vec!["denied: Your authorization token has expired.".to_string()],
+                attempts: 3,
+                ..ProcessFailure::default()
+            })
+            .with_hint("run `stool aws ecr login`");
+
+        assert_eq!(
+            error.report(),
+            "Docker command failed: docker push app:1.0.0\n  \
+             caused by: connection reset\n  \
+             exited with code 1 (after 3 attempts)\n  \
+             | denied: Your authorization token has expired.\n  \
+             hint: run `stool aws ecr login`"
+        );
+    }
+
+    #[test]
+    fn timed_out_processes_name_the_limit() {
+        let failure = ProcessFailure {
+            code: None,
+            timed_out: Some(Duration::from_secs(90)),
+            ..ProcessFailure::default()
+        };
+
+        assert_eq!(failure.to_string(), "timed out after 1m30s");
+    }
+}
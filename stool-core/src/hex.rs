@@ -0,0 +1,49 @@
+//! Lowercase hexadecimal encoding.
+//!
+//! Used for vault nonces and ciphertext and for random tokens, e.g. the
+//! askpass helper's.
+
+/// Encodes bytes as lowercase hex, two characters per byte.
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decodes hex written by [`encode`]; either case is accepted.
+///
+/// Returns `None` if the text has an odd length or a non-hex character.
+pub fn decode(text: &str) -> Option<Vec<u8>> {
+    // from_str_radix alone would also accept a `+` sign
+    if !text.len().is_multiple_of(2) || !text.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            text.get(i..i + 2)
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let bytes = [0x00, 0x0f, 0xa5, 0xff];
+        assert_eq!(encode(&bytes), "000fa5ff");
+        assert_eq!(decode("000fa5ff").as_deref(), Some(&bytes[..]));
+        assert_eq!(decode("000FA5FF").as_deref(), Some(&bytes[..]));
+        assert_eq!(encode(&[]), "");
+        assert_eq!(decode(""), Some(Vec::new()));
+    }
+
+    #[test]
+    fn rejects_malformed_text() {
+        assert_eq!(decode("abc"), None);
+        assert_eq!(decode("zz"), None);
+        assert_eq!(decode("+1"), None);
+        assert_eq!(decode("é"), None);
+    }
+}
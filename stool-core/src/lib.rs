@@ -3,6 +3,20 @@
 //! This crate provides fundamental components used across all stool modules:
 //! - Error handling with unified error types
 //! - Configuration loading from YAML files
+//! - XDG-aware filesystem locations
+//! - Hex encoding
+//! - Secret references for credentials
+//! - Encrypted secrets vault
+//! - OpenSSH client config import
+//! - Persistent state (active context)
+//! - UTC timestamps and durations
 
 pub mod config;
 pub mod error;
+pub mod hex;
+pub mod paths;
+pub mod secret;
+pub mod ssh_config;
+pub mod state;
+pub mod time;
+pub mod vault;
@@ -0,0 +1,64 @@
+//! Filesystem locations used by stool.
+//!
+//! Resolves user directories following the XDG Base Directory
+//! specification, falling back to the conventional `~/.config`-style
+//! locations when the corresponding variables are not set.
+
+use std::env;
+use std::path::PathBuf;
+
+/// Application directory name under XDG base directories.
+const APP_DIR: &str = "stool";
+
+/// Returns the user's home directory from `$HOME`.
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Returns stool's configuration directory.
+///
+/// Uses `$XDG_CONFIG_HOME/stool`, or `~/.config/stool` if unset.
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// Returns stool's data directory.
+///
+/// Uses `$XDG_DATA_HOME/stool`, or `~/.local/share/stool` if unset.
+pub fn data_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// Returns stool's state directory.
+///
+/// Uses `$XDG_STATE_HOME/stool`, or `~/.local/state/stool` if unset.
+pub fn state_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+/// Expands a leading `~` or `~/` to the user's home directory.
+///
+/// Paths without a leading tilde are returned unchanged.
+pub fn expand_tilde(path: &str) -> PathBuf {
+    let Some(home) = home_dir() else {
+        return PathBuf::from(path);
+    };
+    if path == "~" {
+        home
+    } else if let Some(rest) = path.strip_prefix("~/") {
+        home.join(rest)
+    } else {
+        PathBuf::from(path)
+    }
+}
+
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| home_dir().map(|h| h.join(fallback)))
+        .map(|p| p.join(APP_DIR))
+}
@@ -0,0 +1,230 @@
+//! Secret references for configuration values.
+//!
+//! Config values such as `ServerAuth.password` may name where a secret lives
+//! instead of containing it:
+//! - `env:NAME` - environment variable
+//! - `file:PATH` - file content (`~` expanded, trailing newline removed)
+//! - `cmd:COMMAND` - stdout of a shell command (e.g. `cmd:pass show prod`)
+//! - `vault:NAME` - entry in the encrypted stool vault
+//! - `plain:VALUE` - literal value (escape for values with a prefix above)
+//!
+//! Any other value is used literally. References are resolved lazily,
+//! resolved values are never included in error messages and their buffers
+//! are wiped when dropped.
+
+use crate::error::{Result, StoolError, StoolErrorType};
+use crate::paths;
+use crate::vault::{self, Vault};
+use std::fs;
+use std::io;
+use std::process::{Command, Output, Stdio};
+use zeroize::Zeroizing;
+
+/// A parsed secret reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretRef<'a> {
+    /// Literal value.
+    Plain(&'a str),
+    /// Environment variable name.
+    Env(&'a str),
+    /// Path to a file containing the secret.
+    File(&'a str),
+    /// Shell command printing the secret.
+    Cmd(&'a str),
+    /// Entry name in the stool vault.
+    Vault(&'a str),
+}
+
+impl<'a> SecretRef<'a> {
+    /// Parses a config value into a secret reference.
+    pub fn parse(value: &'a str) -> Self {
+        if let Some(name) = value.strip_prefix("env:") {
+            Self::Env(name.trim())
+        } else if let Some(path) = value.strip_prefix("file:") {
+            Self::File(path.trim())
+        } else if let Some(cmd) = value.strip_prefix("cmd:") {
+            Self::Cmd(cmd.trim())
+        } else if let Some(name) = value.strip_prefix("vault:") {
+            Self::Vault(name.trim())
+        } else if let Some(literal) = value.strip_prefix("plain:") {
+            Self::Plain(literal)
+        } else {
+            Self::Plain(value)
+        }
+    }
+
+    /// Whether the value is stored directly in the config.
+    pub fn is_plain(&self) -> bool {
+        matches!(self, Self::Plain(_))
+    }
+
+    /// Resolves the reference to the secret value.
+    ///
+    /// Vault references take the passphrase from `$STOOL_VAULT_PASSPHRASE`
+    /// and `cmd:` references are run directly.
+    ///
+    /// # Errors
+    /// Returns `SecretResolutionFailed` if the variable is unset, the file is
+    /// unreadable, the command fails, or the result is empty
+    pub fn resolve(&self) -> Result<Zeroizing<String>> {
+        self.resolve_with(vault::passphrase_from_env, Command::output)
+    }
+
+    /// Resolves the reference, asking `passphrase` for the vault passphrase
+    /// and running `cmd:` references with `run`.
+    ///
+    /// `passphrase` is only called for `vault:` references and `run` only
+    /// for `cmd:` references, with the command built by [`secret_command`].
+    ///
+    /// # Errors
+    /// Same as [`SecretRef::resolve`], plus vault access errors
+    pub fn resolve_with(
+        &self,
+        passphrase: impl FnOnce() -> Result<Zeroizing<String>>,
+        run: impl FnOnce(&mut Command) -> io::Result<Output>,
+    ) -> Result<Zeroizing<String>> {
+        let value = match *self {
+            Self::Plain(value) => return Ok(Zeroizing::new(value.to_string())),
+            Self::Env(name) => Zeroizing::new(std::env::var(name).map_err(|e| {
+                StoolError::new(StoolErrorType::SecretResolutionFailed)
+                    .with_message(format!("Environment variable not set: {}", name))
+                    .with_source(e)
+                    .with_hint(format!("export {} before running stool", name))
+            })?),
+            Self::File(path) => {
+                let expanded = paths::expand_tilde(path);
+                let mut content = Zeroizing::new(fs::read_to_string(&expanded).map_err(|e| {
+                    StoolError::new(StoolErrorType::SecretResolutionFailed)
+                        .with_message(format!(
+                            "Failed to read secret file: {}",
+                            expanded.display()
+                        ))
+                        .with_source(e)
+                })?);
+                trim_newline(&mut content);
+                content
+            }
+            Self::Cmd(cmd) => run_secret_command(cmd, run)?,
+            Self::Vault(name) => resolve_vault(name, passphrase)?,
+        };
+
+        if value.is_empty() {
+            return Err(
+                StoolError::new(StoolErrorType::SecretResolutionFailed).with_message(format!(
+                    "Secret reference resolved to empty value: {}",
+                    self
+                )),
+            );
+        }
+        Ok(value)
+    }
+}
+
+impl std::fmt::Display for SecretRef<'_> {
+    /// Formats the reference without revealing literal values.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Plain(_) => write!(f, "<plaintext>"),
+            Self::Env(name) => write!(f, "env:{}", name),
+            Self::File(path) => write!(f, "file:{}", path),
+            Self::Cmd(cmd) => write!(f, "cmd:{}", cmd),
+            Self::Vault(name) => write!(f, "vault:{}", name),
+        }
+    }
+}
+
+/// Resolves a config value that may be a secret reference.
+///
+/// # Errors
+/// Returns error if the reference cannot be resolved
+pub fn resolve(value: &str) -> Result<Zeroizing<String>> {
+    SecretRef::parse(value).resolve()
+}
+
+/// Resolves a secret reference, asking `passphrase` for vault references
+/// and running `cmd:` references with `run`.
+///
+/// # Errors
+/// Returns error if the reference cannot be resolved
+pub fn resolve_with(
+    value: &str,
+    passphrase: impl FnOnce() -> Result<Zeroizing<String>>,
+    run: impl FnOnce(&mut Command) -> io::Result<Output>,
+) -> Result<Zeroizing<String>> {
+    SecretRef::parse(value).resolve_with(passphrase, run)
+}
+
+/// Builds the shell command run for a `cmd:` reference.
+///
+/// Stdin and stderr stay attached to the terminal so password managers can
+/// prompt (e.g. for a GPG passphrase).
+pub fn secret_command(cmd: &str) -> Command {
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(cmd)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    command
+}
+
+fn resolve_vault(
+    name: &str,
+    passphrase: impl FnOnce() -> Result<Zeroizing<String>>,
+) -> Result<Zeroizing<String>> {
+    let path = Vault::default_path().ok_or_else(|| {
+        StoolError::new(StoolErrorType::VaultNotFound)
+            .with_message("Cannot determine vault location (HOME not set)")
+    })?;
+    let vault = Vault::open(&path, &passphrase()?)?;
+    vault
+        .get(name)
+        .map(|value| Zeroizing::new(value.to_string()))
+        .ok_or_else(|| {
+            StoolError::new(StoolErrorType::SecretResolutionFailed)
+                .with_message(format!("Vault entry not found: {}", name))
+                .with_hint(format!("run `stool vault set {}` to store it", name))
+        })
+}
+
+/// Runs a command through the shell with `run` and returns its trimmed stdout.
+fn run_secret_command(
+    cmd: &str,
+    run: impl FnOnce(&mut Command) -> io::Result<Output>,
+) -> Result<Zeroizing<String>> {
+    let output = run(&mut secret_command(cmd)).map_err(|e| {
+        StoolError::new(StoolErrorType::SecretResolutionFailed)
+            .with_message(format!("Failed to run secret command: {}", cmd))
+            .with_source(e)
+    })?;
+
+    if !output.status.success() {
+        return Err(
+            StoolError::new(StoolErrorType::SecretResolutionFailed).with_message(format!(
+                "Secret command failed ({}): {}",
+                output.status, cmd
+            )),
+        );
+    }
+
+    let mut stdout = Zeroizing::new(String::from_utf8(output.stdout).map_err(|e| {
+        // The error owns the output; wipe it instead of passing it on
+        let source = e.utf8_error();
+        drop(Zeroizing::new(e.into_bytes()));
+        StoolError::new(StoolErrorType::SecretResolutionFailed)
+            .with_message(format!("Secret command output is not UTF-8: {}", cmd))
+            .with_source(source)
+    })?);
+    trim_newline(&mut stdout);
+    Ok(stdout)
+}
+
+/// Removes a single trailing newline, keeping other whitespace intact.
+fn trim_newline(value: &mut String) {
+    if value.ends_with('\n') {
+        value.pop();
+        if value.ends_with('\r') {
+            value.pop();
+        }
+    }
+}
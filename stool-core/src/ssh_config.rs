@@ -0,0 +1,353 @@
+//! OpenSSH client config (`~/.ssh/config`) parsing.
+//!
+//! Extracts concrete `Host` entries so they can be used as stool servers.
+//! Options are resolved the way `ssh` does: blocks are evaluated in file
+//! order and the first value obtained for each keyword wins, so wildcard
+//! blocks such as `Host *` supply defaults. Wildcard and negated patterns
+//! never become entries themselves. `Match` blocks and `Include` are ignored.
+
+use crate::config::{Server, ServerAuth};
+use crate::error::{Result, StoolError, StoolErrorType};
+use crate::paths;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Port `ssh` uses when none is configured.
+const DEFAULT_PORT: u16 = 22;
+
+/// Host entry resolved from an ssh config.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SshHost {
+    /// Alias given after `Host`.
+    pub alias: String,
+    pub host_name: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub identity_file: Option<String>,
+    pub proxy_jump: Option<String>,
+    pub identity_agent: Option<String>,
+    pub forward_agent: Option<bool>,
+}
+
+impl SshHost {
+    /// Converts the host into a stool server entry.
+    ///
+    /// `HostName` defaults to the alias and `User` to `$USER`.
+    pub fn to_server(&self) -> Server {
+        Server {
+            name: self.alias.clone(),
+            ip: self.host_name.clone().unwrap_or_else(|| self.alias.clone()),
+            user: self
+                .user
+                .clone()
+                .or_else(|| std::env::var("USER").ok())
+                .unwrap_or_default(),
+            auth: ServerAuth {
+                password: None,
+                key_path: self.identity_file.clone(),
+                identity_agent: self.identity_agent.clone().filter(|a| a != "none"),
+            },
+            port: self.port.filter(|&p| p != DEFAULT_PORT),
+            proxy_jump: self.proxy_jump.clone().filter(|j| j != "none"),
+            forward_agent: self.forward_agent.unwrap_or(false),
+            ssh_options: BTreeMap::new(),
+            group: None,
+            tags: Vec::new(),
+            environment: None,
+        }
+    }
+}
+
+/// Returns the default ssh client config path (`~/.ssh/config`).
+pub fn default_path() -> Option<PathBuf> {
+    paths::home_dir().map(|h| h.join(".ssh").join("config"))
+}
+
+/// Reads and parses an ssh client config file.
+///
+/// # Errors
+/// Returns error if the file cannot be read
+pub fn load(path: &Path) -> Result<Vec<SshHost>> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        StoolError::new(StoolErrorType::ConfigLoadFailed)
+            .with_message(format!("Failed to read ssh config: {}", path.display()))
+            .with_source(e)
+    })?;
+    Ok(parse(&content))
+}
+
+/// Parses ssh client config content into concrete host entries.
+pub fn parse(content: &str) -> Vec<SshHost> {
+    let blocks = parse_blocks(content);
+
+    let mut aliases: Vec<&str> = Vec::new();
+    for block in &blocks {
+        for pattern in &block.patterns {
+            if !is_pattern(pattern) && !aliases.contains(&pattern.as_str()) {
+                aliases.push(pattern);
+            }
+        }
+    }
+
+    aliases
+        .into_iter()
+        .map(|alias| {
+            let mut host = SshHost {
+                alias: alias.to_string(),
+                ..SshHost::default()
+            };
+            // A keyword counts as set even if its value is unusable
+            let mut seen: Vec<&str> = Vec::new();
+            for block in blocks.iter().filter(|b| b.matches(alias)) {
+                for (keyword, value) in &block.options {
+                    if !seen.contains(&keyword.as_str()) {
+                        seen.push(keyword);
+                        apply_option(&mut host, keyword, value);
+                    }
+                }
+            }
+            host
+        })
+        .collect()
+}
+
+/// A `Host` block and its options in file order.
+struct Block {
+    patterns: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl Block {
+    /// Matches if any positive pattern matches and no negated pattern does.
+    fn matches(&self, alias: &str) -> bool {
+        let mut matched = false;
+        for pattern in &self.patterns {
+            if let Some(negated) = pattern.strip_prefix('!') {
+                if glob_match(negated, alias) {
+                    return false;
+                }
+            } else if glob_match(pattern, alias) {
+                matched = true;
+            }
+        }
+        matched
+    }
+}
+
+fn parse_blocks(content: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    // Options before the first Host line apply to every host
+    let mut current = Some(Block {
+        patterns: vec!["*".to_string()],
+        options: Vec::new(),
+    });
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((keyword, value)) = split_option(line) else {
+            continue;
+        };
+
+        match keyword.as_str() {
+            "host" => {
+                blocks.extend(current.take());
+                current = Some(Block {
+                    patterns: value.split_whitespace().map(str::to_string).collect(),
+                    options: Vec::new(),
+                });
+            }
+            "match" => blocks.extend(current.take()),
+            _ => {
+                if let Some(block) = current.as_mut() {
+                    block.options.push((keyword, value));
+                }
+            }
+        }
+    }
+    blocks.extend(current);
+    blocks
+}
+
+/// Splits `Keyword value` or `Keyword=value`; keywords are case-insensitive.
+fn split_option(line: &str) -> Option<(String, String)> {
+    let end = line.find(|c: char| c.is_whitespace() || c == '=')?;
+    let (keyword, rest) = line.split_at(end);
+    let value = rest
+        .trim_start()
+        .strip_prefix('=')
+        .unwrap_or(rest)
+        .trim()
+        .trim_matches('"');
+    Some((keyword.to_ascii_lowercase(), value.to_string()))
+}
+
+/// Sets the first value obtained for a keyword; an invalid port is
+/// dropped.
+fn apply_option(host: &mut SshHost, keyword: &str, value: &str) {
+    match keyword {
+        "hostname" => host.host_name = Some(value.replace("%h", &host.alias)),
+        "user" => host.user = Some(value.to_string()),
+        "port" => host.port = value.parse().ok(),
+        "identityfile" => host.identity_file = Some(value.to_string()),
+        "proxyjump" => host.proxy_jump = Some(value.to_string()),
+        "identityagent" => host.identity_agent = Some(value.to_string()),
+        "forwardagent" => host.forward_agent = Some(value.eq_ignore_ascii_case("yes")),
+        _ => {}
+    }
+}
+
+fn is_pattern(alias: &str) -> bool {
+    alias.contains(['*', '?']) || alias.starts_with('!')
+}
+
+/// Matches ssh host patterns (`*` and `?` wildcards).
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            backtrack = Some((pi, ti));
+            pi += 1;
+        } else if let Some((bp, bt)) = backtrack {
+            pi = bp + 1;
+            ti = bt + 1;
+            backtrack = Some((bp, bt + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_match_handles_wildcards() {
+        assert!(glob_match("*", "web-1"));
+        assert!(glob_match("web-*", "web-1"));
+        assert!(glob_match("web-?", "web-1"));
+        assert!(glob_match("*.example.com", "db.prod.example.com"));
+        assert!(glob_match("w*b*1", "web-server-1"));
+        assert!(!glob_match("web-?", "web-10"));
+        assert!(!glob_match("web-*", "db-1"));
+        assert!(!glob_match("web", "web-1"));
+    }
+
+    #[test]
+    fn negated_patterns_exclude_hosts() {
+        let block = Block {
+            patterns: vec!["*.prod".to_string(), "!db.prod".to_string()],
+            options: Vec::new(),
+        };
+        assert!(block.matches("web.prod"));
+        assert!(!block.matches("db.prod"));
+        assert!(!block.matches("web.staging"));
+    }
+
+    #[test]
+    fn negated_patterns_alone_match_nothing() {
+        let block = Block {
+            patterns: vec!["!db".to_string()],
+            options: Vec::new(),
+        };
+        assert!(!block.matches("web"));
+    }
+
+    #[test]
+    fn split_option_accepts_space_and_equals() {
+        assert_eq!(
+            split_option("HostName 10.0.1.5"),
+            Some(("hostname".to_string(), "10.0.1.5".to_string()))
+        );
+        assert_eq!(
+            split_option("Port=2222"),
+            Some(("port".to_string(), "2222".to_string()))
+        );
+        assert_eq!(
+            split_option("User = deploy"),
+            Some(("user".to_string(), "deploy".to_string()))
+        );
+        assert_eq!(split_option("Host"), None);
+    }
+
+    #[test]
+    fn split_option_removes_quotes() {
+        assert_eq!(
+            split_option(r#"IdentityFile "~/.ssh/my key""#),
+            Some(("identityfile".to_string(), "~/.ssh/my key".to_string()))
+        );
+    }
+
+    #[test]
+    fn first_value_wins() {
+        let hosts = parse(
+            "Host web
+    User deploy
+    Port 2222
+
+Host *
+    User root
+    Port 22
+    ForwardAgent yes
+",
+        );
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].alias, "web");
+        assert_eq!(hosts[0].user.as_deref(), Some("deploy"));
+        assert_eq!(hosts[0].port, Some(2222));
+        assert_eq!(hosts[0].forward_agent, Some(true));
+    }
+
+    #[test]
+    fn invalid_port_still_counts_as_set() {
+        let hosts = parse(
+            "Host web
+    Port ssh
+
+Host *
+    Port 2222
+",
+        );
+        assert_eq!(hosts[0].port, None);
+    }
+
+    #[test]
+    fn options_before_the_first_host_apply_to_all() {
+        let hosts = parse(
+            "User admin
+
+Host web db
+    HostName %h.example.com
+",
+        );
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[1].alias, "db");
+        assert_eq!(hosts[1].user.as_deref(), Some("admin"));
+        assert_eq!(hosts[1].host_name.as_deref(), Some("db.example.com"));
+    }
+
+    #[test]
+    fn patterns_and_match_blocks_are_not_entries() {
+        let hosts = parse(
+            "Host web-* !web-old
+    User deploy
+
+Match host db
+    User postgres
+",
+        );
+        assert!(hosts.is_empty());
+    }
+}
@@ -0,0 +1,75 @@
+//! Persistent CLI state.
+//!
+//! Holds small pieces of state that outlive a single invocation, such as
+//! the active context. Stored as YAML in `$XDG_STATE_HOME/stool/state.yaml`
+//! and kept separate from config, which stool never writes implicitly.
+
+use crate::error::{Result, StoolError, StoolErrorType};
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// State file name under the state directory.
+const STATE_FILE_NAME: &str = "state.yaml";
+
+/// State persisted between invocations.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct State {
+    /// Name of the context selected with `stool context use`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+}
+
+impl State {
+    /// Returns the state file location (`$XDG_STATE_HOME/stool/state.yaml`).
+    pub fn path() -> Option<PathBuf> {
+        paths::state_dir().map(|d| d.join(STATE_FILE_NAME))
+    }
+
+    /// Loads the state file, or empty state if it does not exist.
+    ///
+    /// # Errors
+    /// Returns error if the file exists but cannot be read or parsed
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path().filter(|p| p.exists()) else {
+            return Ok(Self::default());
+        };
+        let content = fs::read_to_string(&path).map_err(|e| {
+            StoolError::new(StoolErrorType::IoError)
+                .with_message(format!("Failed to read state: {}", path.display()))
+                .with_source(e)
+        })?;
+        serde_yaml::from_str::<Option<Self>>(&content)
+            .map(Option::unwrap_or_default)
+            .map_err(|e| {
+                StoolError::new(StoolErrorType::YamlParseError)
+                    .with_message(format!("Malformed state file: {}", path.display()))
+                    .with_source(e)
+            })
+    }
+
+    /// Writes the state file, creating its directory if needed.
+    ///
+    /// # Errors
+    /// Returns error if the state directory is unknown or the file cannot be written
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().ok_or_else(|| {
+            StoolError::new(StoolErrorType::IoError)
+                .with_message("Cannot determine state directory (HOME not set)")
+        })?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let content = serde_yaml::to_string(self).map_err(|e| {
+            StoolError::new(StoolErrorType::IoError)
+                .with_message("Failed to serialize state")
+                .with_source(e)
+        })?;
+        fs::write(&path, content).map_err(|e| {
+            StoolError::new(StoolErrorType::IoError)
+                .with_message(format!("Failed to write state: {}", path.display()))
+                .with_source(e)
+        })
+    }
+}
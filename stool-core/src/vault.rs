@@ -0,0 +1,419 @@
+//! Passphrase-encrypted secrets vault.
+//!
+//! Stores named secrets (SSH passwords, tokens) in a single local file,
+//! referenced from config as `vault:NAME`. The whole entry map is encrypted
+//! with XChaCha20-Poly1305 using a key derived from the passphrase with
+//! Argon2id. Keys, passphrases and decrypted values are zeroized on drop.
+
+use crate::error::{Result, StoolError, StoolErrorType};
+use crate::{hex, paths};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Environment variable holding the vault passphrase for non-interactive use.
+pub const PASSPHRASE_ENV_VAR: &str = "STOOL_VAULT_PASSPHRASE";
+
+/// Current vault file format version.
+const FORMAT_VERSION: u32 = 1;
+
+/// Vault file name under the data directory.
+const VAULT_FILE_NAME: &str = "vault.yaml";
+
+const KDF_ALGORITHM: &str = "argon2id";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+
+/// On-disk vault representation; only KDF parameters are in the clear.
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    kdf: KdfParams,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct KdfParams {
+    algorithm: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: String,
+}
+
+impl KdfParams {
+    fn generate() -> Result<Self> {
+        let defaults = Params::default();
+        Ok(Self {
+            algorithm: KDF_ALGORITHM.to_string(),
+            m_cost: defaults.m_cost(),
+            t_cost: defaults.t_cost(),
+            p_cost: defaults.p_cost(),
+            salt: hex::encode(&random_bytes::<SALT_LEN>()?),
+        })
+    }
+
+    fn derive_key(&self, passphrase: &str) -> Result<Zeroizing<[u8; KEY_LEN]>> {
+        if self.algorithm != KDF_ALGORITHM {
+            return Err(StoolError::new(StoolErrorType::VaultAccessFailed)
+                .with_message(format!("Unsupported KDF: {}", self.algorithm)));
+        }
+        let salt = from_hex(&self.salt)?;
+        let params =
+            Params::new(self.m_cost, self.t_cost, self.p_cost, Some(KEY_LEN)).map_err(|e| {
+                StoolError::new(StoolErrorType::VaultAccessFailed)
+                    .with_message(format!("Invalid KDF parameters: {}", e))
+            })?;
+
+        let mut key = Zeroizing::new([0u8; KEY_LEN]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, key.as_mut())
+            .map_err(|e| {
+                StoolError::new(StoolErrorType::VaultAccessFailed)
+                    .with_message(format!("Key derivation failed: {}", e))
+            })?;
+        Ok(key)
+    }
+}
+
+/// Reads the vault passphrase from `$STOOL_VAULT_PASSPHRASE`.
+///
+/// # Errors
+/// Returns `VaultAccessFailed` if the variable is not set
+pub fn passphrase_from_env() -> Result<Zeroizing<String>> {
+    std::env::var(PASSPHRASE_ENV_VAR)
+        .map(Zeroizing::new)
+        .map_err(|_| {
+            StoolError::new(StoolErrorType::VaultAccessFailed)
+                .with_message("Vault passphrase required")
+                .with_hint(format!(
+                    "export {} with the vault passphrase",
+                    PASSPHRASE_ENV_VAR
+                ))
+        })
+}
+
+/// Decrypted vault held in memory.
+pub struct Vault {
+    path: PathBuf,
+    kdf: KdfParams,
+    key: Zeroizing<[u8; KEY_LEN]>,
+    entries: BTreeMap<String, Zeroizing<String>>,
+}
+
+impl Vault {
+    /// Returns the default vault location (`$XDG_DATA_HOME/stool/vault.yaml`).
+    pub fn default_path() -> Option<PathBuf> {
+        paths::data_dir().map(|d| d.join(VAULT_FILE_NAME))
+    }
+
+    /// Creates a new, empty vault at `path`.
+    ///
+    /// # Errors
+    /// Returns error if a vault already exists or cannot be written
+    pub fn create(path: &Path, passphrase: &str) -> Result<Self> {
+        if path.exists() {
+            return Err(StoolError::new(StoolErrorType::VaultAccessFailed)
+                .with_message(format!("Vault already exists: {}", path.display())));
+        }
+        let kdf = KdfParams::generate()?;
+        let key = kdf.derive_key(passphrase)?;
+        let vault = Self {
+            path: path.to_path_buf(),
+            kdf,
+            key,
+            entries: BTreeMap::new(),
+        };
+        vault.save()?;
+        Ok(vault)
+    }
+
+    /// Opens and decrypts the vault at `path`.
+    ///
+    /// # Errors
+    /// Returns `VaultNotFound` if the file does not exist, or
+    /// `VaultAccessFailed` if the passphrase is wrong or the file is corrupt
+    pub fn open(path: &Path, passphrase: &str) -> Result<Self> {
+        if !path.exists() {
+            return Err(StoolError::new(StoolErrorType::VaultNotFound)
+                .with_message(format!("No vault at {}", path.display()))
+                .with_hint("run `stool vault init` to create one"));
+        }
+        let content = fs::read_to_string(path)?;
+        let file: VaultFile = serde_yaml::from_str(&content).map_err(|e| {
+            StoolError::new(StoolErrorType::VaultAccessFailed)
+                .with_message(format!("Malformed vault file: {}", path.display()))
+                .with_source(e)
+        })?;
+        if file.version != FORMAT_VERSION {
+            return Err(StoolError::new(StoolErrorType::VaultAccessFailed)
+                .with_message(format!("Unsupported vault version: {}", file.version)));
+        }
+
+        let key = file.kdf.derive_key(passphrase)?;
+        let nonce = from_hex(&file.nonce)?;
+        if nonce.len() != NONCE_LEN {
+            return Err(StoolError::new(StoolErrorType::VaultAccessFailed)
+                .with_message("Malformed vault nonce"));
+        }
+        let ciphertext = from_hex(&file.ciphertext)?;
+
+        let plaintext = Zeroizing::new(
+            XChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+                .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+                .map_err(|_| {
+                    StoolError::new(StoolErrorType::VaultAccessFailed)
+                        .with_message("Wrong passphrase or corrupted vault")
+                        .with_hint(format!(
+                            "check the passphrase, or unset {} if it holds a stale value",
+                            PASSPHRASE_ENV_VAR
+                        ))
+                })?,
+        );
+
+        let raw: BTreeMap<String, String> = serde_yaml::from_slice(&plaintext).map_err(|e| {
+            StoolError::new(StoolErrorType::VaultAccessFailed)
+                .with_message("Malformed vault content")
+                .with_source(e)
+        })?;
+        let entries = raw
+            .into_iter()
+            .map(|(k, v)| (k, Zeroizing::new(v)))
+            .collect();
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            kdf: file.kdf,
+            key,
+            entries,
+        })
+    }
+
+    /// Returns the secret stored under `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.get(name).map(|v| v.as_str())
+    }
+
+    /// Stores a secret, replacing any existing value.
+    pub fn set(&mut self, name: &str, value: String) {
+        self.entries.insert(name.to_string(), Zeroizing::new(value));
+    }
+
+    /// Removes a secret. Returns `false` if it did not exist.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.entries.remove(name).is_some()
+    }
+
+    /// Returns the names of all stored secrets in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Returns the file backing this vault.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Encrypts and writes the vault with a fresh nonce.
+    ///
+    /// The file is written with mode 0600 and atomically replaced.
+    ///
+    /// # Errors
+    /// Returns error if encryption or writing fails
+    pub fn save(&self) -> Result<()> {
+        let plain: BTreeMap<&str, &str> = self
+            .entries
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        let mut plaintext = serde_yaml::to_string(&plain).map_err(|e| {
+            StoolError::new(StoolErrorType::VaultAccessFailed)
+                .with_message("Failed to serialize vault")
+                .with_source(e)
+        })?;
+
+        let nonce = random_bytes::<NONCE_LEN>()?;
+        let encrypted = XChaCha20Poly1305::new(Key::from_slice(self.key.as_ref()))
+            .encrypt(XNonce::from_slice(&nonce), plaintext.as_bytes());
+        plaintext.zeroize();
+        let ciphertext = encrypted.map_err(|_| {
+            StoolError::new(StoolErrorType::VaultAccessFailed).with_message("Encryption failed")
+        })?;
+
+        let file = VaultFile {
+            version: FORMAT_VERSION,
+            kdf: self.kdf.clone(),
+            nonce: hex::encode(&nonce),
+            ciphertext: hex::encode(&ciphertext),
+        };
+        let content = serde_yaml::to_string(&file).map_err(|e| {
+            StoolError::new(StoolErrorType::VaultAccessFailed)
+                .with_message("Failed to serialize vault")
+                .with_source(e)
+        })?;
+
+        write_private(&self.path, content.as_bytes())
+    }
+}
+
+/// Writes `data` to `path` with mode 0600 via a temporary file and rename.
+fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp = path.with_extension("tmp");
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&temp)
+        .map_err(|e| {
+            StoolError::new(StoolErrorType::IoError)
+                .with_message(format!("Failed to write vault: {}", temp.display()))
+                .with_source(e)
+        })?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    getrandom::fill(&mut buf).map_err(|e| {
+        StoolError::new(StoolErrorType::VaultAccessFailed)
+            .with_message(format!("Failed to generate random bytes: {}", e))
+    })?;
+    Ok(buf)
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>> {
+    hex::decode(s).ok_or_else(|| {
+        StoolError::new(StoolErrorType::VaultAccessFailed).with_message("Malformed vault file")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Returns a vault path in a fresh directory for one test.
+    fn vault_path(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("stool-vault-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join(VAULT_FILE_NAME)
+    }
+
+    /// Rewrites one field of the vault file on disk.
+    fn tamper(path: &Path, edit: impl FnOnce(&mut VaultFile)) {
+        let mut file: VaultFile = serde_yaml::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        edit(&mut file);
+        fs::write(path, serde_yaml::to_string(&file).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn secrets_survive_a_save_and_open() {
+        let path = vault_path("round-trip");
+        let mut vault = Vault::create(&path, "correct horse").unwrap();
+        vault.set("prod-admin", "s3cret".to_string());
+        vault.save().unwrap();
+
+        let opened = Vault::open(&path, "correct horse").unwrap();
+
+        assert_eq!(opened.get("prod-admin"), Some("s3cret"));
+        assert_eq!(opened.names().collect::<Vec<_>>(), ["prod-admin"]);
+        assert!(!fs::read_to_string(&path).unwrap().contains("s3cret"));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn wrong_passphrase_is_refused() {
+        let path = vault_path("wrong-passphrase");
+        Vault::create(&path, "correct horse").unwrap();
+
+        let error = Vault::open(&path, "battery staple").err().unwrap();
+
+        assert_eq!(error.error_type, StoolErrorType::VaultAccessFailed);
+        assert_eq!(
+            error.message.as_deref(),
+            Some("Wrong passphrase or corrupted vault")
+        );
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn tampered_ciphertext_is_refused() {
+        let path = vault_path("tampered-ciphertext");
+        Vault::create(&path, "correct horse").unwrap();
+        tamper(&path, |file| {
+            let flipped = if file.ciphertext.starts_with('0') {
+                "1"
+            } else {
+                "0"
+            };
+            file.ciphertext.replace_range(..1, flipped);
+        });
+
+        let error = Vault::open(&path, "correct horse").err().unwrap();
+
+        assert_eq!(error.error_type, StoolErrorType::VaultAccessFailed);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn tampered_header_is_refused() {
+        let path = vault_path("tampered-header");
+        Vault::create(&path, "correct horse").unwrap();
+        tamper(&path, |file| file.kdf.salt = "00".repeat(SALT_LEN));
+
+        let error = Vault::open(&path, "correct horse").err().unwrap();
+
+        assert_eq!(error.error_type, StoolErrorType::VaultAccessFailed);
+        tamper(&path, |file| file.nonce.truncate(8));
+        let error = Vault::open(&path, "correct horse").err().unwrap();
+        assert_eq!(error.message.as_deref(), Some("Malformed vault nonce"));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn vault_file_is_private() {
+        let path = vault_path("mode");
+
+        Vault::create(&path, "correct horse").unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn existing_vaults_are_not_overwritten() {
+        let path = vault_path("exists");
+        Vault::create(&path, "correct horse").unwrap();
+
+        let error = Vault::create(&path, "battery staple").err().unwrap();
+
+        assert_eq!(error.error_type, StoolErrorType::VaultAccessFailed);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn missing_vaults_are_not_found() {
+        let error = Vault::open(&vault_path("missing"), "correct horse")
+            .err()
+            .unwrap();
+
+        assert_eq!(error.error_type, StoolErrorType::VaultNotFound);
+    }
+}
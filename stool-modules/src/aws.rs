@@ -3,10 +3,13 @@
 //! Provides simplified access to AWS CLI commands,
 //! including aws configure and ECR login.
 
+use crate::history;
+use log::info;
 use std::process::Command;
-use stool_core::config::{EcrRegistry, SsoConfig};
+use stool_core::config::execution::{CommandPolicy, ExecutionConfig, Operation};
+use stool_core::config::{Context, EcrRegistry, SsoConfig};
 use stool_core::error::{Result, StoolError, StoolErrorType};
-use stool_utils::interactive;
+use stool_utils::{command, interactive, runner};
 
 /// Run aws configure interactively.
 ///
@@ -18,9 +21,7 @@ pub fn configure() -> Result<()> {
     check_aws_cli()?;
 
     // Run aws configure interactively
-    let status = Command::new("aws")
-        .arg("configure")
-        .status()
+    let status = runner::status(Command::new("aws").arg("configure"))
         .map_err(|e| StoolError::new(StoolErrorType::AwsCommandFailed).with_source(e))?;
 
     if !status.success() {
@@ -35,10 +36,11 @@ pub fn configure() -> Result<()> {
 /// Configure AWS SSO interactively.
 ///
 /// Selects SSO config, auto-fills text inputs, manual selection for account/role.
-pub fn sso_configure(configs: &[SsoConfig]) -> Result<()> {
+/// The active context's SSO profile is pre-selected.
+pub fn sso_configure(configs: &[SsoConfig], context: Option<&Context>) -> Result<()> {
     check_aws_cli()?;
 
-    let selected = select_sso_config(configs)?;
+    let selected = select_sso_config(configs, context)?;
 
     let cfg = match selected {
         Some(c) => c,
@@ -49,18 +51,20 @@ pub fn sso_configure(configs: &[SsoConfig]) -> Result<()> {
     // This bypasses AWS CLI's interactive UI which requires CPR support.
     write_sso_config(&cfg)?;
 
-    println!("SSO profile '{}' configured.", cfg.profile_name);
+    info!("SSO profile '{}' configured.", cfg.profile_name);
 
     // Run sso login
-    let status = Command::new("aws")
-        .args(["sso", "login", "--profile", &cfg.profile_name])
-        .status()
-        .map_err(|e| StoolError::new(StoolErrorType::AwsCommandFailed).with_source(e))?;
+    let status =
+        runner::status(Command::new("aws").args(["sso", "login", "--profile", &cfg.profile_name]))
+            .map_err(|e| StoolError::new(StoolErrorType::AwsCommandFailed).with_source(e))?;
 
     if !status.success() {
-        return Err(
-            StoolError::new(StoolErrorType::AwsCommandFailed).with_message("aws sso login failed")
-        );
+        return Err(StoolError::new(StoolErrorType::AwsCommandFailed)
+            .with_message("aws sso login failed")
+            .with_hint(format!(
+                "check sso_start_url and sso_region of '{}' in the config",
+                cfg.profile_name
+            )));
     }
 
     Ok(())
@@ -109,7 +113,7 @@ fn write_sso_config(cfg: &SsoConfig) -> Result<()> {
     let session_header = format!("[sso-session {}]", cfg.sso_session_name);
 
     if existing_content.contains(&profile_header) {
-        println!("Profile '{}' already exists. Skipping.", cfg.profile_name);
+        info!("Profile '{}' already exists. Skipping.", cfg.profile_name);
         return Ok(());
     }
 
@@ -162,7 +166,10 @@ fn write_sso_config(cfg: &SsoConfig) -> Result<()> {
 }
 
 /// Select SSO config from list or manual input.
-fn select_sso_config(configs: &[SsoConfig]) -> Result<Option<SsoConfig>> {
+fn select_sso_config(
+    configs: &[SsoConfig],
+    context: Option<&Context>,
+) -> Result<Option<SsoConfig>> {
     let mut items: Vec<String> = configs
         .iter()
         .enumerate()
@@ -172,7 +179,9 @@ fn select_sso_config(configs: &[SsoConfig]) -> Result<Option<SsoConfig>> {
     items.push(format!("{}. Manual input", items.len() + 1));
     items.push(format!("{}. Cancel", items.len() + 1));
 
-    let selection = interactive::select_from_list("Select SSO config:", &items)?;
+    let default = default_sso_index(configs, context);
+    let selection =
+        interactive::select_from_list_with_default("Select SSO config:", &items, default)?;
 
     if selection == items.len() - 1 {
         return Ok(None);
@@ -203,32 +212,34 @@ fn select_sso_config(configs: &[SsoConfig]) -> Result<Option<SsoConfig>> {
 ///
 /// Executes `aws sso login` command to authenticate via browser.
 /// Selects profile from YAML config (using name as profile) or manual input.
-pub fn sso_login(configs: &[SsoConfig]) -> Result<()> {
+/// The active context's SSO profile is pre-selected.
+pub fn sso_login(configs: &[SsoConfig], context: Option<&Context>) -> Result<()> {
     check_aws_cli()?;
 
-    let profile_name = select_sso_profile(configs)?;
+    let profile_name = select_sso_profile(configs, context)?;
 
     let profile = match profile_name {
         Some(p) => p,
         None => return Ok(()), // User cancelled
     };
 
-    let status = Command::new("aws")
-        .args(["sso", "login", "--profile", &profile])
-        .status()
+    let status = runner::status(Command::new("aws").args(["sso", "login", "--profile", &profile]))
         .map_err(|e| StoolError::new(StoolErrorType::AwsCommandFailed).with_source(e))?;
 
     if !status.success() {
-        return Err(
-            StoolError::new(StoolErrorType::AwsCommandFailed).with_message("aws sso login failed")
-        );
+        return Err(StoolError::new(StoolErrorType::AwsCommandFailed)
+            .with_message("aws sso login failed")
+            .with_hint(format!(
+                "check the profile with `aws configure list --profile {}` or run `stool aws sso`",
+                profile
+            )));
     }
 
     Ok(())
 }
 
 /// Select SSO profile from config or manual input.
-fn select_sso_profile(configs: &[SsoConfig]) -> Result<Option<String>> {
+fn select_sso_profile(configs: &[SsoConfig], context: Option<&Context>) -> Result<Option<String>> {
     let mut items: Vec<String> = configs
         .iter()
         .enumerate()
@@ -238,7 +249,9 @@ fn select_sso_profile(configs: &[SsoConfig]) -> Result<Option<String>> {
     items.push(format!("{}. Manual input", items.len() + 1));
     items.push(format!("{}. Cancel", items.len() + 1));
 
-    let selection = interactive::select_from_list("Select SSO profile:", &items)?;
+    let default = default_sso_index(configs, context);
+    let selection =
+        interactive::select_from_list_with_default("Select SSO profile:", &items, default)?;
 
     if selection == items.len() - 1 {
         return Ok(None);
@@ -261,62 +274,93 @@ fn select_sso_profile(configs: &[SsoConfig]) -> Result<Option<String>> {
 ///
 /// # Arguments
 /// * `registries` - List of ECR registries from configuration
+/// * `context` - Active context; its registry is pre-selected
+/// * `execution` - Timeouts and retries for the aws and docker commands
 ///
 /// # Errors
 /// Returns error if AWS CLI or Docker is not installed, or login fails
-pub fn ecr_login(registries: &[EcrRegistry]) -> Result<()> {
-    // Check if AWS CLI is installed
-    check_aws_cli()?;
-
-    // Check if Docker is installed
-    check_docker()?;
+pub fn ecr_login(
+    registries: &[EcrRegistry],
+    context: Option<&Context>,
+    execution: &ExecutionConfig,
+) -> Result<()> {
+    check_tools()?;
 
     // Select registry or manual input
-    let registry_info = select_ecr_registry(registries)?;
+    let registry_info = select_ecr_registry(registries, context)?;
 
     let (account_id, region, sso_profile) = match registry_info {
         Some(info) => info,
         None => return Ok(()), // User cancelled
     };
 
+    let operation = history::Operation::EcrLogin {
+        account_id: account_id.clone(),
+        region: region.clone(),
+        profile: sso_profile.clone(),
+    };
+    history::record(operation, || {
+        login_registry(&account_id, &region, sso_profile.as_deref(), execution)
+    })
+}
+
+/// Logs Docker in to one ECR registry, refreshing the SSO session first
+/// if `profile` is set.
+///
+/// # Errors
+/// Returns error if the SSO login or ECR login fails
+pub(crate) fn login_registry(
+    account_id: &str,
+    region: &str,
+    profile: Option<&str>,
+    execution: &ExecutionConfig,
+) -> Result<()> {
     // If SSO profile is set, check and login if needed
-    if let Some(ref profile) = sso_profile {
-        ensure_sso_login(profile)?;
+    if let Some(profile) = profile {
+        ensure_sso_login(profile, &execution.policy(Operation::AwsEcr))?;
     }
 
     // Execute ECR login
-    execute_ecr_login(&account_id, &region, sso_profile.as_deref())?;
+    execute_ecr_login(account_id, region, profile, execution)?;
 
-    println!(
+    info!(
         "Successfully logged in to ECR registry: {}.dkr.ecr.{}.amazonaws.com",
         account_id, region
     );
     Ok(())
 }
 
+/// Checks that the AWS CLI and Docker are installed.
+pub(crate) fn check_tools() -> Result<()> {
+    check_aws_cli()?;
+    check_docker()
+}
+
 /// Check SSO session validity and login if expired.
-fn ensure_sso_login(profile: &str) -> Result<()> {
+fn ensure_sso_login(profile: &str, policy: &CommandPolicy) -> Result<()> {
     // Check if SSO session is valid
-    let check = Command::new("aws")
-        .args(["sts", "get-caller-identity", "--profile", profile])
-        .output()
-        .map_err(|e| StoolError::new(StoolErrorType::AwsCommandFailed).with_source(e))?;
+    let check = command::query(
+        Command::new("aws").args(["sts", "get-caller-identity", "--profile", profile]),
+        StoolErrorType::AwsCommandFailed,
+        policy,
+    )?;
 
     if check.status.success() {
         return Ok(());
     }
 
     // SSO session expired, login
-    println!("SSO session expired. Logging in...");
-    let status = Command::new("aws")
-        .args(["sso", "login", "--profile", profile])
-        .status()
+    info!("SSO session expired. Logging in...");
+    let status = runner::status(Command::new("aws").args(["sso", "login", "--profile", profile]))
         .map_err(|e| StoolError::new(StoolErrorType::AwsCommandFailed).with_source(e))?;
 
     if !status.success() {
-        return Err(
-            StoolError::new(StoolErrorType::AwsCommandFailed).with_message("SSO login failed")
-        );
+        return Err(StoolError::new(StoolErrorType::AwsCommandFailed)
+            .with_message("SSO login failed")
+            .with_hint(format!(
+                "run `aws sso login --profile {}` or `stool aws login`",
+                profile
+            )));
     }
 
     Ok(())
@@ -324,14 +368,15 @@ fn ensure_sso_login(profile: &str) -> Result<()> {
 
 /// Check if AWS CLI is installed.
 fn check_aws_cli() -> Result<()> {
-    let check = Command::new("which")
-        .arg("aws")
-        .output()
+    let check = runner::output(Command::new("which").arg("aws"), None)
         .map_err(|e| StoolError::new(StoolErrorType::AwsCliNotInstalled).with_source(e))?;
 
     if !check.status.success() {
         return Err(StoolError::new(StoolErrorType::AwsCliNotInstalled)
-            .with_message("AWS CLI is not installed. Install it via: brew install awscli"));
+            .with_message("AWS CLI is not installed")
+            .with_hint(
+                "install it with `brew install awscli` or see https://aws.amazon.com/cli/",
+            ));
     }
 
     Ok(())
@@ -339,14 +384,15 @@ fn check_aws_cli() -> Result<()> {
 
 /// Check if Docker is installed.
 fn check_docker() -> Result<()> {
-    let check = Command::new("which")
-        .arg("docker")
-        .output()
+    let check = runner::output(Command::new("which").arg("docker"), None)
         .map_err(|e| StoolError::new(StoolErrorType::DockerNotInstalled).with_source(e))?;
 
     if !check.status.success() {
         return Err(StoolError::new(StoolErrorType::DockerNotInstalled)
-            .with_message("Docker is not installed. Install it via: brew install docker"));
+            .with_message("Docker is not installed")
+            .with_hint(
+                "install it with `brew install --cask docker` or `sudo apt install docker.io`",
+            ));
     }
 
     Ok(())
@@ -357,6 +403,7 @@ fn check_docker() -> Result<()> {
 /// Returns (account_id, region, sso_profile) or None if cancelled.
 fn select_ecr_registry(
     registries: &[EcrRegistry],
+    context: Option<&Context>,
 ) -> Result<Option<(String, String, Option<String>)>> {
     let mut items: Vec<String> = registries
         .iter()
@@ -381,7 +428,9 @@ fn select_ecr_registry(
     items.push(format!("{}. Manual input", items.len() + 1));
     items.push(format!("{}. Cancel", items.len() + 1));
 
-    let selection = interactive::select_from_list("Select ECR registry:", &items)?;
+    let default = default_registry_index(registries, context);
+    let selection =
+        interactive::select_from_list_with_default("Select ECR registry:", &items, default)?;
 
     if selection == items.len() - 1 {
         // Cancel
@@ -404,9 +453,34 @@ fn select_ecr_registry(
     Err(StoolError::new(StoolErrorType::InvalidInput))
 }
 
+/// Returns the menu index of the context's SSO profile, or 0.
+fn default_sso_index(configs: &[SsoConfig], context: Option<&Context>) -> usize {
+    context
+        .and_then(|c| c.sso_profile.as_deref())
+        .and_then(|p| configs.iter().position(|cfg| cfg.profile_name == p))
+        .unwrap_or(0)
+}
+
+/// Returns the menu index of the context's ECR registry, or 0.
+///
+/// Shared with the docker module's registry menus.
+pub(crate) fn default_registry_index(
+    registries: &[EcrRegistry],
+    context: Option<&Context>,
+) -> usize {
+    context
+        .and_then(|c| c.ecr_registry.as_deref())
+        .and_then(|name| registries.iter().position(|r| r.name == name))
+        .unwrap_or(0)
+}
+
 /// Execute ECR login command.
-fn execute_ecr_login(account_id: &str, region: &str, profile: Option<&str>) -> Result<()> {
-    use std::io::Write;
+fn execute_ecr_login(
+    account_id: &str,
+    region: &str,
+    profile: Option<&str>,
+    execution: &ExecutionConfig,
+) -> Result<()> {
     use zeroize::Zeroize;
 
     let registry_url = format!("{}.dkr.ecr.{}.amazonaws.com", account_id, region);
@@ -417,54 +491,127 @@ fn execute_ecr_login(account_id: &str, region: &str, profile: Option<&str>) -> R
     if let Some(p) = profile {
         cmd.args(["--profile", p]);
     }
-    let password_output = cmd
-        .output()
-        .map_err(|e| StoolError::new(StoolErrorType::AwsCommandFailed).with_source(e))?;
-
-    if !password_output.status.success() {
-        return Err(StoolError::new(StoolErrorType::AwsCommandFailed)
-            .with_message("Failed to get ECR login password"));
+    let password_output = command::query(
+        &mut cmd,
+        StoolErrorType::AwsCommandFailed,
+        &execution.policy(Operation::AwsEcr),
+    )?;
+
+    if let Err(e) = command::check_output(
+        password_output.status,
+        &password_output.stderr,
+        StoolErrorType::AwsCommandFailed,
+    ) {
+        let hint = match profile {
+            Some(p) => format!(
+                "the SSO session may have expired; run `aws sso login --profile {}`",
+                p
+            ),
+            None => {
+                "check your credentials with `aws sts get-caller-identity` or run `stool aws login`"
+                    .to_string()
+            }
+        };
+        return Err(e
+            .with_message("Failed to get ECR login password")
+            .with_hint(hint));
     }
 
     let mut password = String::from_utf8_lossy(&password_output.stdout)
         .trim()
         .to_string();
 
-    // Docker login
-    let mut docker_login = Command::new("docker")
-        .args([
+    // Docker login, passing the password on stdin
+    let result = command::execute_with_input(
+        Command::new("docker").args([
             "login",
             "--username",
             "AWS",
             "--password-stdin",
             &registry_url,
-        ])
-        .stdin(std::process::Stdio::piped())
-        .spawn()
-        .map_err(|e| {
-            password.zeroize();
-            StoolError::new(StoolErrorType::DockerCommandFailed).with_source(e)
-        })?;
+        ]),
+        Some(password.as_bytes()),
+        StoolErrorType::DockerCommandFailed,
+        &execution.policy(Operation::DockerLogin),
+    );
+    password.zeroize();
 
-    if let Some(mut stdin) = docker_login.stdin.take() {
-        let write_result = stdin
-            .write_all(password.as_bytes())
-            .map_err(|e| StoolError::new(StoolErrorType::DockerCommandFailed).with_source(e));
-        password.zeroize();
-        write_result?;
-        drop(stdin);
-    } else {
-        password.zeroize();
-    }
+    result.map_err(|e| {
+        e.with_message("Docker login failed")
+            .with_hint("make sure the Docker daemon is running (`docker info`)")
+    })
+}
 
-    let status = docker_login
-        .wait()
-        .map_err(|e| StoolError::new(StoolErrorType::DockerCommandFailed).with_source(e))?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{ExitStatus, Output};
+    use std::rc::Rc;
+    use stool_utils::runner::{self, RecordingRunner};
+
+    fn output(code: i32, stdout: &str, stderr: &str) -> Output {
+        Output {
+            status: ExitStatus::from_raw(code << 8),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        }
+    }
 
-    if !status.success() {
-        return Err(StoolError::new(StoolErrorType::DockerCommandFailed)
-            .with_message("Docker login failed"));
+    #[test]
+    fn ecr_login_passes_the_password_on_stdin() {
+        let recorder = Rc::new(RecordingRunner::new());
+        recorder.push(output(0, "ecr-token-123\n", ""));
+
+        runner::with_runner(recorder.clone(), || {
+            execute_ecr_login(
+                "123456789012",
+                "ap-northeast-2",
+                Some("dev"),
+                &ExecutionConfig::default(),
+            )
+        })
+        .unwrap();
+
+        let commands = recorder.commands();
+        assert_eq!(
+            commands,
+            [
+                "aws ecr get-login-password --region ap-northeast-2 --profile dev",
+                "docker login --username AWS --password-stdin \
+                 123456789012.dkr.ecr.ap-northeast-2.amazonaws.com",
+            ]
+        );
+        assert!(commands.iter().all(|c| !c.contains("ecr-token-123")));
+        assert_eq!(recorder.inputs(), [None, Some(b"ecr-token-123".to_vec())]);
     }
 
-    Ok(())
+    #[test]
+    fn ecr_login_stops_when_the_password_cannot_be_fetched() {
+        let recorder = Rc::new(RecordingRunner::new());
+        recorder.push(output(
+            255,
+            "",
+            "Error loading SSO Token: Token has expired",
+        ));
+
+        let error = runner::with_runner(recorder.clone(), || {
+            execute_ecr_login(
+                "123456789012",
+                "ap-northeast-2",
+                Some("dev"),
+                &ExecutionConfig::default(),
+            )
+        })
+        .unwrap_err();
+
+        assert_eq!(error.error_type, StoolErrorType::AwsCommandFailed);
+        assert!(
+            error
+                .hint
+                .as_deref()
+                .is_some_and(|h| h.contains("aws sso login --profile dev"))
+        );
+        assert_eq!(recorder.commands().len(), 1);
+    }
 }
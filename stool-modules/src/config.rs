@@ -0,0 +1,488 @@
+//! Config inspection module.
+//!
+//! Provides commands to inspect and edit the resolved configuration:
+//! - Show the merged config with passwords masked
+//! - List config layers and where they were loaded from
+//! - Validate the config
+//! - Edit the user config in `$EDITOR` with validation before saving
+//! - Import hosts from `~/.ssh/config` as servers
+//! - Migrate config files to the current schema version
+
+use log::{error, info, warn};
+use similar::TextDiff;
+use std::fs::{self, DirBuilder};
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use stool_core::config::format::ConfigFormat;
+use stool_core::config::{Config, ConfigSource, Section, Server, document, migration};
+use stool_core::error::{Result, StoolError, StoolErrorType};
+use stool_core::hex;
+use stool_core::paths;
+use stool_core::secret::SecretRef;
+use stool_core::ssh_config;
+use stool_utils::{command, interactive, runner};
+
+/// Placeholder shown instead of configured passwords.
+const MASKED_PASSWORD: &str = "********";
+
+/// Editor used when neither `$VISUAL` nor `$EDITOR` is set.
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Prints the merged configuration as YAML with passwords masked.
+///
+/// Secret references (`env:`, `file:`, `cmd:`) are shown as-is since they
+/// do not contain the secret itself.
+///
+/// # Arguments
+/// * `cfg` - Resolved configuration
+///
+/// # Errors
+/// Returns error if the config cannot be serialized
+pub fn show(cfg: &Config) -> Result<()> {
+    let mut value = serde_yaml::to_value(cfg).map_err(|e| {
+        StoolError::new(StoolErrorType::ConfigParseError)
+            .with_message("Failed to serialize config")
+            .with_source(e)
+    })?;
+
+    if let Some(servers) = value
+        .get_mut(Section::Servers.key())
+        .and_then(|v| v.as_sequence_mut())
+    {
+        for server in servers {
+            if let Some(password) = server
+                .get_mut("auth")
+                .and_then(|auth| auth.get_mut("password"))
+                && password
+                    .as_str()
+                    .is_none_or(|p| SecretRef::parse(p).is_plain())
+            {
+                *password = serde_yaml::Value::String(MASKED_PASSWORD.to_string());
+            }
+        }
+    }
+
+    let yaml = serde_yaml::to_string(&value).map_err(|e| {
+        StoolError::new(StoolErrorType::ConfigParseError)
+            .with_message("Failed to serialize config")
+            .with_source(e)
+    })?;
+    print!("{}", yaml);
+    Ok(())
+}
+
+/// Prints every config layer in priority order and whether it was used.
+///
+/// # Arguments
+/// * `cli_path` - Path passed via `--config`, if any
+pub fn path(cli_path: Option<&str>) -> Result<()> {
+    println!("Config layers (lowest priority first):");
+    println!("  1. {} [used]", ConfigSource::Embedded);
+
+    for (i, source) in ConfigSource::candidates(cli_path).iter().enumerate() {
+        let status = match source.path() {
+            Some(p) if p.exists() => "used",
+            Some(_) if source.is_required() => "missing",
+            _ => "not found",
+        };
+        println!("  {}. {} [{}]", i + 2, source, status);
+    }
+
+    // Includes and fragments are only known once the layers are read
+    let extra: Vec<ConfigSource> = Config::resolve(cli_path)
+        .map(|cfg| {
+            cfg.layers()
+                .iter()
+                .map(|l| l.source.clone())
+                .filter(|s| {
+                    matches!(
+                        s,
+                        ConfigSource::Include(_)
+                            | ConfigSource::Fragment(_)
+                            | ConfigSource::SshConfig(_)
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if !extra.is_empty() {
+        println!("Additional sources:");
+        for source in extra {
+            println!("  - {}", source);
+        }
+    }
+
+    Ok(())
+}
+
+/// Validates the resolved configuration and prints every finding.
+///
+/// # Arguments
+/// * `cli_path` - Path passed via `--config`, if any
+///
+/// # Errors
+/// Returns error if the configuration is invalid
+pub fn validate(cli_path: Option<&str>) -> Result<()> {
+    let cfg = Config::resolve(cli_path)?;
+    let report = cfg.validate();
+
+    for diagnostic in &report.diagnostics {
+        println!("{}", diagnostic);
+    }
+    let errors = report.errors().count();
+    if errors > 0 {
+        return Err(StoolError::new(StoolErrorType::ConfigParseError)
+            .with_message(format!("{} config error(s)", errors)));
+    }
+
+    println!(
+        "Config is valid: {} servers, {} ECR registries, {} SSO configs from {} layers",
+        cfg.servers.len(),
+        cfg.ecr_registries.len(),
+        cfg.sso_configs.len(),
+        cfg.layers().len()
+    );
+    Ok(())
+}
+
+/// Imports concrete hosts from an ssh client config as servers.
+///
+/// Hosts already configured (by name) are skipped.
+/// Entries are printed as YAML, or appended to the highest-priority config
+/// file with `write`.
+///
+/// # Arguments
+/// * `cli_path` - Path passed via `--config`, if any
+/// * `file` - ssh config to read (default: `~/.ssh/config`)
+/// * `write` - Append entries to the config file instead of printing
+///
+/// # Errors
+/// Returns error if the ssh config cannot be read or the config cannot be written
+pub fn import_ssh(cli_path: Option<&str>, file: Option<&str>, write: bool) -> Result<()> {
+    let ssh_path = file
+        .map(paths::expand_tilde)
+        .or_else(ssh_config::default_path)
+        .ok_or_else(|| {
+            StoolError::new(StoolErrorType::ConfigLoadFailed)
+                .with_message("Cannot determine ssh config path (HOME not set)")
+        })?;
+    let hosts = ssh_config::load(&ssh_path)?;
+
+    let cfg = Config::resolve(cli_path)?;
+    let configured: Vec<&str> = cfg
+        .servers
+        .iter()
+        .filter(|s| {
+            !matches!(
+                cfg.source_of(Section::Servers, &s.name),
+                Some(ConfigSource::SshConfig(_))
+            )
+        })
+        .map(|s| s.name.as_str())
+        .collect();
+
+    let mut servers: Vec<Server> = Vec::new();
+    for host in &hosts {
+        if configured.contains(&host.alias.as_str()) {
+            info!("Skipping '{}': already configured", host.alias);
+        } else {
+            servers.push(host.to_server());
+        }
+    }
+
+    if servers.is_empty() {
+        info!("No new hosts to import from {}", ssh_path.display());
+        return Ok(());
+    }
+
+    let entries = servers
+        .iter()
+        .map(serde_yaml::to_value)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| {
+            StoolError::new(StoolErrorType::ConfigParseError)
+                .with_message("Failed to serialize servers")
+                .with_source(e)
+        })?;
+
+    if !write {
+        print!(
+            "{}",
+            document::insert_entries("", ConfigFormat::Yaml, Section::Servers, &entries)?
+        );
+        return Ok(());
+    }
+
+    let target = edit_target(cli_path)?;
+    let (original, format) = read_target(&target)?;
+    let content = document::insert_entries(&original, format, Section::Servers, &entries)?;
+    validate_with(cli_path, &target, &content)?;
+    write_config(&target, &content)?;
+
+    info!(
+        "Imported {} server(s) into {}",
+        servers.len(),
+        target.display()
+    );
+    Ok(())
+}
+
+/// Opens the highest-priority config file in `$EDITOR`.
+///
+/// The file is edited as a temporary copy and only written back once it
+/// parses and merges cleanly with the other layers. The user config is
+/// created if no file layer exists yet.
+///
+/// # Arguments
+/// * `cli_path` - Path passed via `--config`, if any
+///
+/// # Errors
+/// Returns error if the editor fails or the file cannot be written
+pub fn edit(cli_path: Option<&str>) -> Result<()> {
+    let target = edit_target(cli_path)?;
+
+    let original = if target.exists() {
+        fs::read_to_string(&target)?
+    } else {
+        String::new()
+    };
+
+    let copy = EditCopy::create(&target, &original)?;
+    let result = edit_loop(cli_path, &target, &copy.path);
+    drop(copy);
+
+    let Some(content) = result? else {
+        info!("Changes discarded");
+        return Ok(());
+    };
+
+    if content == original {
+        info!("No changes");
+        return Ok(());
+    }
+
+    write_config(&target, &content)?;
+    info!("Config saved: {}", target.display());
+    Ok(())
+}
+
+/// Upgrades config files written for older schema versions.
+///
+/// For each outdated file the applied steps and a unified diff are shown,
+/// then the file is rewritten after confirmation. The original is kept as
+/// `<file>.bak`. Comments are not preserved in the rewritten file.
+///
+/// # Arguments
+/// * `cli_path` - Path passed via `--config`, if any
+/// * `yes` - Rewrite without asking for confirmation
+///
+/// # Errors
+/// Returns error if a file cannot be parsed, migrated or written
+pub fn migrate(cli_path: Option<&str>, yes: bool) -> Result<()> {
+    let mut outdated = 0;
+
+    for layer in Config::read_layers(cli_path)? {
+        let Some(path) = layer.source.path() else {
+            continue;
+        };
+        let Some((content, steps)) = migration::migrate_document(&layer.content, layer.format)
+            .map_err(|e| {
+                let message = e.message.clone().unwrap_or_default();
+                e.with_message(format!("{}: {}", message, layer.source))
+            })?
+        else {
+            continue;
+        };
+        outdated += 1;
+
+        println!("{} -> version {}", layer.source, migration::CURRENT_VERSION);
+        for step in &steps {
+            println!("  - {}", step);
+        }
+        let old_name = path.display().to_string();
+        let new_name = format!("{} (migrated)", old_name);
+        print!(
+            "{}",
+            TextDiff::from_lines(&layer.content, &content)
+                .unified_diff()
+                .header(&old_name, &new_name)
+        );
+
+        if !yes && !interactive::confirm(&format!("Rewrite {}?", old_name), false)? {
+            info!("Skipped {}", old_name);
+            continue;
+        }
+
+        let backup = backup_path(path);
+        fs::copy(path, &backup).map_err(|e| {
+            StoolError::new(StoolErrorType::IoError)
+                .with_message(format!("Failed to back up config: {}", backup.display()))
+                .with_source(e)
+        })?;
+        write_config(path, &content)?;
+        info!("Migrated {} (backup: {})", old_name, backup.display());
+    }
+
+    if outdated == 0 {
+        info!(
+            "All config files are at version {}",
+            migration::CURRENT_VERSION
+        );
+    }
+    Ok(())
+}
+
+/// Returns `<file>.bak` next to the given file.
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
+/// Returns the highest-priority config file, which edits are written to.
+pub(crate) fn edit_target(cli_path: Option<&str>) -> Result<PathBuf> {
+    let source = ConfigSource::candidates(cli_path).pop().ok_or_else(|| {
+        StoolError::new(StoolErrorType::ConfigLoadFailed)
+            .with_message("Cannot determine config directory (HOME not set)")
+    })?;
+    source
+        .path()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| StoolError::new(StoolErrorType::ConfigLoadFailed))
+}
+
+/// Reads the file entries are written to, along with its format.
+///
+/// A file that does not exist yet starts out with the current `version`.
+pub(crate) fn read_target(target: &Path) -> Result<(String, ConfigFormat)> {
+    if target.exists() {
+        let content = fs::read_to_string(target)?;
+        let format = ConfigFormat::detect(Some(target), &content);
+        return Ok((content, format));
+    }
+
+    let format = ConfigFormat::from_path(target).unwrap_or_default();
+    let mut mapping = serde_yaml::Mapping::new();
+    mapping.insert(
+        migration::VERSION_KEY.into(),
+        migration::CURRENT_VERSION.into(),
+    );
+    Ok((format.render(&mapping.into())?, format))
+}
+
+pub(crate) fn write_config(target: &Path, content: &str) -> Result<()> {
+    if let Some(dir) = target.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(target, content).map_err(|e| {
+        StoolError::new(StoolErrorType::IoError)
+            .with_message(format!("Failed to write config: {}", target.display()))
+            .with_source(e)
+    })
+}
+
+/// Runs the editor until the file validates or the user discards it.
+///
+/// Returns `None` if changes were discarded.
+fn edit_loop(cli_path: Option<&str>, target: &Path, temp: &Path) -> Result<Option<String>> {
+    loop {
+        open_editor(temp)?;
+        let content = fs::read_to_string(temp)?;
+
+        match validate_with(cli_path, target, &content) {
+            Ok(()) => return Ok(Some(content)),
+            Err(e) => {
+                error!("Config is invalid: {}", e.report());
+            }
+        }
+
+        let items = vec!["1. Re-edit".to_string(), "2. Discard changes".to_string()];
+        if interactive::select_from_list("Config is invalid:", &items)? == 1 {
+            return Ok(None);
+        }
+    }
+}
+
+/// Resolves and validates the config with `content` in place of `target`.
+pub(crate) fn validate_with(cli_path: Option<&str>, target: &Path, content: &str) -> Result<()> {
+    let layers = Config::read_layers_with(cli_path, Some((target, content)))?;
+    let warnings = Config::from_layers(layers)?.validate().into_result()?;
+    for warning in warnings {
+        warn!("{:#}", warning);
+    }
+    Ok(())
+}
+
+fn open_editor(path: &Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string());
+
+    // Run through the shell so editors with arguments (e.g. "code -w") work
+    let status = runner::status(
+        Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(path),
+    )
+    .map_err(|e| {
+        StoolError::new(StoolErrorType::CommandExecutionFailed)
+            .with_message(format!("Failed to launch editor: {}", editor))
+            .with_source(e)
+    })?;
+
+    command::check_status(status, StoolErrorType::CommandExecutionFailed)
+}
+
+/// Temporary copy of a config file being edited.
+///
+/// The copy lives in a private (`0700`) directory with a random name and is
+/// only readable by the user (`0600`). The directory, including any swap or
+/// backup files the editor leaves behind, is removed on drop.
+struct EditCopy {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl EditCopy {
+    fn create(target: &Path, content: &str) -> Result<Self> {
+        let mut token = [0u8; 8];
+        getrandom::fill(&mut token).map_err(|e| {
+            StoolError::new(StoolErrorType::IoError)
+                .with_message(format!("Failed to generate random bytes: {}", e))
+        })?;
+        let dir = std::env::temp_dir().join(format!("stool-edit-{}", hex::encode(&token)));
+        DirBuilder::new().mode(0o700).create(&dir).map_err(|e| {
+            StoolError::new(StoolErrorType::IoError)
+                .with_message(format!("Failed to create {}", dir.display()))
+                .with_source(e)
+        })?;
+
+        let name = target
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "config.yaml".to_string());
+        let copy = Self {
+            path: dir.join(name),
+            dir,
+        };
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&copy.path)
+            .and_then(|mut file| file.write_all(content.as_bytes()))?;
+        Ok(copy)
+    }
+}
+
+impl Drop for EditCopy {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
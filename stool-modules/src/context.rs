@@ -0,0 +1,142 @@
+//! Context switching module.
+//!
+//! Manages the active context, a named set of defaults (server group, ECR
+//! registry, SSO profile) defined in config:
+//! - Switch to a context
+//! - List defined contexts
+//! - Show the active context
+//! - Clear the selection
+
+use log::{info, warn};
+use stool_core::config::{CONTEXT_ENV_VAR, Config, Context, ContextSource};
+use stool_core::error::{Result, StoolError, StoolErrorType};
+use stool_core::state::State;
+
+/// Selects a context for subsequent invocations.
+///
+/// # Arguments
+/// * `cfg` - Resolved configuration
+/// * `name` - Context to activate
+///
+/// # Errors
+/// Returns error if the context is not defined or the state cannot be saved
+pub fn use_context(cfg: &Config, name: &str) -> Result<()> {
+    if cfg.context(name).is_none() {
+        let available: Vec<&str> = cfg.contexts.iter().map(|c| c.name.as_str()).collect();
+        return Err(
+            StoolError::new(StoolErrorType::InvalidInput).with_message(format!(
+                "Unknown context '{}' (available: {})",
+                name,
+                if available.is_empty() {
+                    "none".to_string()
+                } else {
+                    available.join(", ")
+                }
+            )),
+        );
+    }
+
+    let mut state = State::load()?;
+    state.context = Some(name.to_string());
+    state.save()?;
+
+    info!("Switched to context '{}'", name);
+    warn_env_override();
+    Ok(())
+}
+
+/// Lists defined contexts, marking the active one with `*`.
+///
+/// # Arguments
+/// * `cfg` - Resolved configuration
+///
+/// # Errors
+/// Returns error if the state file cannot be read
+pub fn list(cfg: &Config) -> Result<()> {
+    if cfg.contexts.is_empty() {
+        println!("No contexts defined");
+        return Ok(());
+    }
+
+    let active = Config::active_context_name()?.map(|(name, _)| name);
+    for ctx in &cfg.contexts {
+        let marker = if active.as_deref() == Some(ctx.name.as_str()) {
+            "*"
+        } else {
+            " "
+        };
+        println!("{} {}{}", marker, ctx.name, describe(ctx));
+    }
+    Ok(())
+}
+
+/// Prints the active context and where it was selected.
+///
+/// # Arguments
+/// * `cfg` - Resolved configuration
+///
+/// # Errors
+/// Returns error if the state file cannot be read
+pub fn show(cfg: &Config) -> Result<()> {
+    let Some((name, source)) = Config::active_context_name()? else {
+        println!("No active context");
+        return Ok(());
+    };
+
+    let origin = match source {
+        ContextSource::Env => format!("${}", CONTEXT_ENV_VAR),
+        ContextSource::State => State::path()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default(),
+    };
+    match cfg.context(&name) {
+        Some(ctx) => println!("{}{} (from {})", ctx.name, describe(ctx), origin),
+        None => println!("{} (from {}) [not defined in config]", name, origin),
+    }
+    Ok(())
+}
+
+/// Clears the selected context.
+///
+/// # Errors
+/// Returns error if the state cannot be saved
+pub fn clear() -> Result<()> {
+    let mut state = State::load()?;
+    if state.context.take().is_none() {
+        info!("No active context");
+        return Ok(());
+    }
+    state.save()?;
+
+    info!("Context cleared");
+    warn_env_override();
+    Ok(())
+}
+
+/// Formats a context's defaults, e.g. `  group=web ecr=Prod sso=admin`.
+fn describe(ctx: &Context) -> String {
+    let mut parts = Vec::new();
+    if let Some(group) = &ctx.group {
+        parts.push(format!("group={}", group));
+    }
+    if let Some(registry) = &ctx.ecr_registry {
+        parts.push(format!("ecr={}", registry));
+    }
+    if let Some(profile) = &ctx.sso_profile {
+        parts.push(format!("sso={}", profile));
+    }
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!("  {}", parts.join(" "))
+    }
+}
+
+fn warn_env_override() {
+    if std::env::var(CONTEXT_ENV_VAR).is_ok_and(|v| !v.is_empty()) {
+        warn!(
+            "${} is set and overrides the selected context",
+            CONTEXT_ENV_VAR
+        );
+    }
+}
@@ -6,10 +6,14 @@
 //! - Push images to AWS ECR registries
 //! - Version increment (major, middle, minor)
 
+use crate::{aws, history};
+use log::info;
+use serde::{Deserialize, Serialize};
 use std::process::Command;
-use stool_core::config::EcrRegistry;
+use stool_core::config::execution::{CommandPolicy, ExecutionConfig, Operation};
+use stool_core::config::{Context, EcrRegistry};
 use stool_core::error::{Result, StoolError, StoolErrorType};
-use stool_utils::interactive;
+use stool_utils::{command, interactive};
 
 /// Default Docker build options for multi-platform support.
 const DEFAULT_BUILD_OPTIONS: &[&str] = &[
@@ -23,7 +27,8 @@ const DEFAULT_BUILD_OPTIONS: &[&str] = &[
 const DEFAULT_TAG: &str = "latest";
 
 /// Version increment type.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum VersionIncrement {
     Latest,
     Major,
@@ -91,8 +96,8 @@ fn select_image_name(registry: &EcrRegistry) -> Result<String> {
 }
 
 /// Builds Docker image with default options.
-fn build_image(image_name: &str) -> Result<()> {
-    println!("Building Docker image: {}:latest", image_name);
+pub(crate) fn build_image(image_name: &str, execution: &ExecutionConfig) -> Result<()> {
+    info!("Building Docker image: {}:latest", image_name);
     let image_tag = format!("{}:latest", image_name);
     let mut build_args = vec!["build"];
     build_args.extend(DEFAULT_BUILD_OPTIONS);
@@ -100,8 +105,8 @@ fn build_image(image_name: &str) -> Result<()> {
     build_args.push(&image_tag);
     build_args.push(".");
 
-    execute_docker(&build_args)?;
-    println!("Build completed successfully");
+    execute_docker(&build_args, &execution.policy(Operation::DockerBuild))?;
+    info!("Build completed successfully");
     Ok(())
 }
 
@@ -109,10 +114,16 @@ fn build_image(image_name: &str) -> Result<()> {
 ///
 /// # Arguments
 /// * `registries` - List of available ECR registries from configuration
+/// * `context` - Active context; its registry is pre-selected
+/// * `execution` - Timeouts and retries for docker and aws commands
 ///
 /// # Errors
 /// Returns error if Docker build fails or user input is invalid
-pub fn build_only(registries: &[EcrRegistry]) -> Result<()> {
+pub fn build_only(
+    registries: &[EcrRegistry],
+    context: Option<&Context>,
+    execution: &ExecutionConfig,
+) -> Result<()> {
     if registries.is_empty() {
         return Err(StoolError::new(StoolErrorType::ConfigLoadFailed)
             .with_message("No ECR registries configured"));
@@ -133,24 +144,38 @@ pub fn build_only(registries: &[EcrRegistry]) -> Result<()> {
         })
         .collect();
 
-    let registry_idx = interactive::select_from_list("Select ECR registry:", &registry_items)?;
+    let registry_idx = interactive::select_from_list_with_default(
+        "Select ECR registry:",
+        &registry_items,
+        aws::default_registry_index(registries, context),
+    )?;
     let registry = &registries[registry_idx];
 
     // Select or input image name
     let image_name = select_image_name(registry)?;
 
     // Build Docker image
-    build_image(&image_name)
+    let built = history::Operation::DockerBuild {
+        registry: registry.name.clone(),
+        image: image_name.clone(),
+    };
+    history::record(built, || build_image(&image_name, execution))
 }
 
 /// Builds, tags, and pushes Docker image to ECR.
 ///
 /// # Arguments
 /// * `registries` - List of available ECR registries from configuration
+/// * `context` - Active context; its registry is pre-selected
+/// * `execution` - Timeouts and retries for docker and aws commands
 ///
 /// # Errors
 /// Returns error if Docker commands fail or user input is invalid
-pub fn push_to_ecr(registries: &[EcrRegistry]) -> Result<()> {
+pub fn push_to_ecr(
+    registries: &[EcrRegistry],
+    context: Option<&Context>,
+    execution: &ExecutionConfig,
+) -> Result<()> {
     if registries.is_empty() {
         return Err(StoolError::new(StoolErrorType::ConfigLoadFailed)
             .with_message("No ECR registries configured"));
@@ -171,110 +196,155 @@ pub fn push_to_ecr(registries: &[EcrRegistry]) -> Result<()> {
         })
         .collect();
 
-    let registry_idx = interactive::select_from_list("Select ECR registry:", &registry_items)?;
+    let registry_idx = interactive::select_from_list_with_default(
+        "Select ECR registry:",
+        &registry_items,
+        aws::default_registry_index(registries, context),
+    )?;
     let registry = &registries[registry_idx];
 
     // Select or input image name
     let image_name = select_image_name(registry)?;
 
-    // Build Docker image
-    build_image(&image_name)?;
-
-    // Get current version from ECR
-    let current_version = get_latest_ecr_version(registry, &image_name)?;
-
-    // Select version increment type
-    let version_items = if let Some(ref ver) = current_version {
-        let v = Version::parse(ver).unwrap_or(Version {
-            major: 0,
-            middle: 1,
-            minor: 0,
-        });
-        vec![
-            format!("1. major ({})", v.increment(VersionIncrement::Major)),
-            format!("2. middle ({})", v.increment(VersionIncrement::Middle)),
+    let pushed = history::Operation::DockerPush {
+        registry: registry.name.clone(),
+        image: image_name.clone(),
+        increment: None,
+        version: None,
+    };
+    history::record_with(pushed, |operation| {
+        // Build Docker image
+        build_image(&image_name, execution)?;
+
+        // Get current version from ECR
+        let current_version =
+            get_latest_ecr_version(registry, &image_name, &execution.policy(Operation::AwsEcr))?;
+        let current = current_version.as_deref();
+
+        // Select version increment type
+        let increments = [
+            VersionIncrement::Major,
+            VersionIncrement::Middle,
+            VersionIncrement::Minor,
+        ];
+        let version_items = vec![
+            format!("1. major ({})", next_version(current, increments[0])),
+            format!("2. middle ({})", next_version(current, increments[1])),
             format!(
                 "3. minor ({}) [default]",
-                v.increment(VersionIncrement::Minor)
+                next_version(current, increments[2])
             ),
-        ]
-    } else {
-        vec![
-            "1. major (1.0.0)".to_string(),
-            "2. middle (0.1.0)".to_string(),
-            "3. minor (0.1.0) [default]".to_string(),
-        ]
-    };
+        ];
+        let version_idx = interactive::select_from_list("Select version type:", &version_items)?;
+        let increment = *increments
+            .get(version_idx)
+            .ok_or_else(|| StoolError::new(StoolErrorType::InvalidInput))?;
+        let new_version = next_version(current, increment);
+        operation.set_pushed_version(increment, &new_version);
+
+        tag_and_push(registry, &image_name, &new_version, execution)
+    })
+}
 
-    let version_idx = interactive::select_from_list("Select version type:", &version_items)?;
-
-    let new_version = if let Some(ref ver) = current_version {
-        let v = Version::parse(ver).unwrap_or(Version {
-            major: 0,
-            middle: 1,
-            minor: 0,
-        });
-        match version_idx {
-            0 => v.increment(VersionIncrement::Major),
-            1 => v.increment(VersionIncrement::Middle),
-            2 => v.increment(VersionIncrement::Minor),
-            _ => return Err(StoolError::new(StoolErrorType::InvalidInput)),
-        }
-    } else {
-        match version_idx {
-            0 => "1.0.0".to_string(),
-            1 | 2 => "0.1.0".to_string(),
-            _ => return Err(StoolError::new(StoolErrorType::InvalidInput)),
-        }
-    };
+/// Returns the version following `current` (the newest ECR tag, if any).
+fn next_version(current: Option<&str>, increment: VersionIncrement) -> String {
+    match current {
+        Some(ver) => Version::parse(ver)
+            .unwrap_or(Version {
+                major: 0,
+                middle: 1,
+                minor: 0,
+            })
+            .increment(increment),
+        None => match increment {
+            VersionIncrement::Latest => DEFAULT_TAG.to_string(),
+            VersionIncrement::Major => "1.0.0".to_string(),
+            VersionIncrement::Middle | VersionIncrement::Minor => "0.1.0".to_string(),
+        },
+    }
+}
 
+/// Looks up the newest version in ECR and returns the one after it.
+///
+/// # Errors
+/// Returns error if the ECR query fails
+pub(crate) fn next_ecr_version(
+    registry: &EcrRegistry,
+    image_name: &str,
+    increment: VersionIncrement,
+    execution: &ExecutionConfig,
+) -> Result<String> {
+    let current =
+        get_latest_ecr_version(registry, image_name, &execution.policy(Operation::AwsEcr))?;
+    Ok(next_version(current.as_deref(), increment))
+}
+
+/// Tags a built image for ECR and pushes it as `latest` and `new_version`.
+///
+/// # Errors
+/// Returns error if any Docker command fails
+pub(crate) fn tag_and_push(
+    registry: &EcrRegistry,
+    image_name: &str,
+    new_version: &str,
+    execution: &ExecutionConfig,
+) -> Result<()> {
     let ecr_url = format!(
         "{}.dkr.ecr.{}.amazonaws.com",
         registry.account_id, registry.region
     );
+    let tag = execution.policy(Operation::DockerTag);
+    let push = execution.policy(Operation::DockerPush);
 
     // Tag for latest
-    println!("Tagging image: {}:{}", image_name, DEFAULT_TAG);
+    info!("Tagging image: {}:{}", image_name, DEFAULT_TAG);
     let local_tag = format!("{}:latest", image_name);
     let ecr_tag_latest = format!("{}/{}:latest", ecr_url, image_name);
-    execute_docker(&["tag", &local_tag, &ecr_tag_latest])?;
+    execute_docker(&["tag", &local_tag, &ecr_tag_latest], &tag)?;
 
     // Tag for version
-    println!("Tagging image: {}:{}", image_name, new_version);
+    info!("Tagging image: {}:{}", image_name, new_version);
     let ecr_tag_version = format!("{}/{}:{}", ecr_url, image_name, new_version);
-    execute_docker(&["tag", &local_tag, &ecr_tag_version])?;
+    execute_docker(&["tag", &local_tag, &ecr_tag_version], &tag)?;
 
     // Push latest
-    println!("Pushing {}/{}:latest", ecr_url, image_name);
-    execute_docker(&["push", &ecr_tag_latest])?;
+    info!("Pushing {}/{}:latest", ecr_url, image_name);
+    execute_docker(&["push", &ecr_tag_latest], &push)?;
 
     // Push version
-    println!("Pushing {}/{}:{}", ecr_url, image_name, new_version);
-    execute_docker(&["push", &ecr_tag_version])?;
+    info!("Pushing {}/{}:{}", ecr_url, image_name, new_version);
+    execute_docker(&["push", &ecr_tag_version], &push)?;
 
-    println!("Push completed successfully");
+    info!("Push completed successfully");
     Ok(())
 }
 
 /// Executes Docker command with error handling.
-fn execute_docker(args: &[&str]) -> Result<()> {
-    let status = Command::new("docker").args(args).status().map_err(|e| {
-        StoolError::new(StoolErrorType::CommandExecutionFailed)
-            .with_message(format!("Failed to execute docker {}", args.join(" ")))
-            .with_source(e)
-    })?;
-
-    if !status.success() {
-        return Err(StoolError::new(StoolErrorType::CommandExecutionFailed)
-            .with_message(format!("Docker command failed: docker {}", args.join(" "))));
-    }
-
-    Ok(())
+///
+/// Docker's stderr is shown as it runs and its last lines are attached to
+/// the error on failure.
+fn execute_docker(args: &[&str], policy: &CommandPolicy) -> Result<()> {
+    command::execute_captured(
+        Command::new("docker").args(args),
+        StoolErrorType::CommandExecutionFailed,
+        policy,
+    )
+    .map_err(|e| {
+        let message = match e.process {
+            Some(_) => format!("Docker command failed: docker {}", args.join(" ")),
+            None => format!("Failed to execute docker {}", args.join(" ")),
+        };
+        e.with_message(message)
+    })
 }
 
 /// Retrieves latest version tag from ECR repository.
 /// Finds the highest semantic version (x.y.z) among all image tags.
-fn get_latest_ecr_version(registry: &EcrRegistry, image_name: &str) -> Result<Option<String>> {
+fn get_latest_ecr_version(
+    registry: &EcrRegistry,
+    image_name: &str,
+    policy: &CommandPolicy,
+) -> Result<Option<String>> {
     let mut args = vec![
         "ecr",
         "describe-images",
@@ -295,15 +365,25 @@ fn get_latest_ecr_version(registry: &EcrRegistry, image_name: &str) -> Result<Op
         args.push(&profile_flag);
     }
 
-    let output = Command::new("aws").args(&args).output().map_err(|e| {
-        StoolError::new(StoolErrorType::CommandExecutionFailed)
-            .with_message("Failed to execute aws ecr describe-images")
-            .with_source(e)
-    })?;
+    let output = command::query(
+        Command::new("aws").args(&args),
+        StoolErrorType::CommandExecutionFailed,
+        policy,
+    )
+    .map_err(|e| e.with_message("Failed to execute aws ecr describe-images"))?;
 
     if !output.status.success() {
-        // Repository might not exist or be empty
-        return Ok(None);
+        // A repository that does not exist yet has no versions
+        if String::from_utf8_lossy(&output.stderr).contains("RepositoryNotFoundException") {
+            return Ok(None);
+        }
+        return command::check_output(
+            output.status,
+            &output.stderr,
+            StoolErrorType::AwsCommandFailed,
+        )
+        .map(|()| None)
+        .map_err(|e| e.with_message(format!("aws ecr describe-images failed for {}", image_name)));
     }
 
     let tags_str = String::from_utf8_lossy(&output.stdout);
@@ -322,3 +402,136 @@ fn get_latest_ecr_version(registry: &EcrRegistry, image_name: &str) -> Result<Op
 
     Ok(max_version)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{ExitStatus, Output};
+    use std::rc::Rc;
+    use stool_utils::runner::{self, RecordingRunner};
+
+    fn registry(sso_profile: Option<&str>) -> EcrRegistry {
+        EcrRegistry {
+            name: "Dev ECR".to_string(),
+            account_id: "123456789012".to_string(),
+            region: "ap-northeast-2".to_string(),
+            images: Vec::new(),
+            sso_profile: sso_profile.map(str::to_string),
+        }
+    }
+
+    fn output(code: i32, stdout: &str, stderr: &str) -> Output {
+        Output {
+            status: ExitStatus::from_raw(code << 8),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn next_version_follows_the_increment() {
+        let cases = [
+            (Some("1.2.3"), VersionIncrement::Major, "2.0.0"),
+            (Some("1.2.3"), VersionIncrement::Middle, "1.3.0"),
+            (Some("1.2.3"), VersionIncrement::Minor, "1.2.4"),
+            (None, VersionIncrement::Major, "1.0.0"),
+            (None, VersionIncrement::Middle, "0.1.0"),
+            (None, VersionIncrement::Minor, "0.1.0"),
+            (None, VersionIncrement::Latest, "latest"),
+        ];
+        for (current, increment, expected) in cases {
+            assert_eq!(
+                next_version(current, increment),
+                expected,
+                "{current:?} {increment:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn next_ecr_version_starts_from_the_highest_tag() {
+        let recorder = Rc::new(RecordingRunner::new());
+        recorder.push(output(0, "latest\t0.9.0\t0.10.2\t0.2.7\n", ""));
+
+        let version = runner::with_runner(recorder.clone(), || {
+            next_ecr_version(
+                &registry(Some("dev")),
+                "api",
+                VersionIncrement::Minor,
+                &ExecutionConfig::default(),
+            )
+        })
+        .unwrap();
+
+        assert_eq!(version, "0.10.3");
+        assert_eq!(
+            recorder.commands(),
+            [
+                "aws ecr describe-images --repository-name api --region ap-northeast-2 \
+                 --query 'imageDetails[*].imageTags[]' --output text --profile dev"
+            ]
+        );
+    }
+
+    #[test]
+    fn next_ecr_version_of_a_new_repository_is_the_first() {
+        let recorder = Rc::new(RecordingRunner::new());
+        recorder.push(output(
+            254,
+            "",
+            "An error occurred (RepositoryNotFoundException) when calling the DescribeImages operation",
+        ));
+
+        let version = runner::with_runner(recorder.clone(), || {
+            next_ecr_version(
+                &registry(None),
+                "api",
+                VersionIncrement::Major,
+                &ExecutionConfig::default(),
+            )
+        })
+        .unwrap();
+
+        assert_eq!(version, "1.0.0");
+    }
+
+    #[test]
+    fn tag_and_push_pushes_latest_and_the_version() {
+        let recorder = Rc::new(RecordingRunner::new());
+
+        runner::with_runner(recorder.clone(), || {
+            tag_and_push(&registry(None), "api", "1.4.0", &ExecutionConfig::default())
+        })
+        .unwrap();
+
+        let ecr = "123456789012.dkr.ecr.ap-northeast-2.amazonaws.com";
+        assert_eq!(
+            recorder.commands(),
+            [
+                format!("docker tag api:latest {ecr}/api:latest"),
+                format!("docker tag api:latest {ecr}/api:1.4.0"),
+                format!("docker push {ecr}/api:latest"),
+                format!("docker push {ecr}/api:1.4.0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn tag_and_push_stops_at_the_first_failure() {
+        let recorder = Rc::new(RecordingRunner::new());
+        recorder.push(output(
+            1,
+            "",
+            "Error response from daemon: No such image: api:latest",
+        ));
+
+        let error = runner::with_runner(recorder.clone(), || {
+            tag_and_push(&registry(None), "api", "1.4.0", &ExecutionConfig::default())
+        })
+        .unwrap_err();
+
+        assert_eq!(error.error_type, StoolErrorType::CommandExecutionFailed);
+        assert_eq!(recorder.commands().len(), 1);
+    }
+}
@@ -0,0 +1,467 @@
+//! Config entry management module.
+//!
+//! Adds, edits and removes servers, ECR registries and SSO configs in the
+//! highest-priority config file (the user config unless `$STOOL_CONFIG` or
+//! `--config` is set). Entries are edited in the raw text so the rest of the
+//! file keeps its comments and ordering, and the result is validated against
+//! all other layers before it is written.
+
+use crate::config::{edit_target, read_target, validate_with, write_config};
+use log::{info, warn};
+use serde_yaml::Value;
+use stool_core::config::{Config, EcrRegistry, Section, Server, ServerAuth, SsoConfig, document};
+use stool_core::error::{Result, StoolError, StoolErrorType};
+use stool_core::secret::SecretRef;
+use stool_utils::interactive;
+
+/// Authentication choices offered for servers.
+const AUTH_METHODS: [&str; 4] = [
+    "1. Key file",
+    "2. Password (stored in config)",
+    "3. Secret reference (env:, file:, cmd:, vault:)",
+    "4. Default SSH authentication",
+];
+
+/// Default AWS CLI output format for new SSO configs.
+const DEFAULT_OUTPUT_FORMAT: &str = "json";
+
+/// Adds a server after prompting for its settings.
+///
+/// # Arguments
+/// * `cli_path` - Path passed via `--config`, if any
+///
+/// # Errors
+/// Returns error if the name is taken, input is invalid or the config cannot be written
+pub fn server_add(cli_path: Option<&str>) -> Result<()> {
+    let cfg = Config::resolve(cli_path)?;
+    let name = interactive::input_text("Server name:")?;
+    ensure_new(&cfg, Section::Servers, &name)?;
+
+    let server = prompt_server(Server {
+        name,
+        ..Server::default()
+    })?;
+    let entry = to_entry(serde_yaml::to_value(&server))?;
+    insert(cli_path, Section::Servers, &server.name, entry)
+}
+
+/// Edits a server, pre-filling every prompt with its current settings.
+///
+/// A server defined in another file is overridden by an entry of the same
+/// name in the target file.
+///
+/// # Arguments
+/// * `cli_path` - Path passed via `--config`, if any
+/// * `name` - Server to edit; selected from a menu if not given
+///
+/// # Errors
+/// Returns error if the server does not exist, input is invalid or the
+/// config cannot be written
+pub fn server_edit(cli_path: Option<&str>, name: Option<&str>) -> Result<()> {
+    let cfg = Config::resolve(cli_path)?;
+    let Some(name) = pick(&cfg, Section::Servers, name)? else {
+        return Ok(());
+    };
+    let Some(current) = cfg.servers.iter().find(|s| s.name == name) else {
+        return Err(not_found(Section::Servers, &name));
+    };
+
+    let server = prompt_server(current.clone())?;
+    let entry = to_entry(serde_yaml::to_value(&server))?;
+
+    let target = edit_target(cli_path)?;
+    let (original, format) = read_target(&target)?;
+    let content = match document::replace_entry(&original, format, Section::Servers, &name, &entry)
+    {
+        Err(e) if e.error_type == StoolErrorType::EntryNotFound => {
+            if let Some(source) = cfg.source_of(Section::Servers, &name) {
+                info!(
+                    "'{}' is defined in {}; adding an override to {}",
+                    name,
+                    source,
+                    target.display()
+                );
+            }
+            document::insert_entries(&original, format, Section::Servers, &[entry])?
+        }
+        result => result?,
+    };
+    validate_with(cli_path, &target, &content)?;
+    write_config(&target, &content)?;
+
+    info!("Server '{}' updated in {}", name, target.display());
+    Ok(())
+}
+
+/// Removes a server.
+///
+/// # Arguments
+/// * `cli_path` - Path passed via `--config`, if any
+/// * `name` - Server to remove; selected from a menu if not given
+/// * `yes` - Remove without asking for confirmation
+///
+/// # Errors
+/// Returns error if the server is not defined in the target file or the
+/// config cannot be written
+pub fn server_remove(cli_path: Option<&str>, name: Option<&str>, yes: bool) -> Result<()> {
+    remove(cli_path, Section::Servers, name, yes)
+}
+
+/// Adds an ECR registry after prompting for its settings.
+///
+/// # Arguments
+/// * `cli_path` - Path passed via `--config`, if any
+///
+/// # Errors
+/// Returns error if the name is taken, input is invalid or the config cannot be written
+pub fn registry_add(cli_path: Option<&str>) -> Result<()> {
+    let cfg = Config::resolve(cli_path)?;
+    let name = interactive::input_text("Registry name:")?;
+    ensure_new(&cfg, Section::EcrRegistries, &name)?;
+
+    let account_id = interactive::input_text("AWS Account ID:")?;
+    let region = interactive::input_text("Region:")?;
+
+    let mut items: Vec<String> = cfg
+        .sso_configs
+        .iter()
+        .enumerate()
+        .map(|(i, c)| format!("{}. {}", i + 1, c.profile_name))
+        .collect();
+    items.push(format!("{}. None (default credentials)", items.len() + 1));
+    let selection = interactive::select_from_list("SSO profile:", &items)?;
+    let sso_profile = cfg
+        .sso_configs
+        .get(selection)
+        .map(|c| c.profile_name.clone());
+
+    let images = split_list(&interactive::input_text_optional(
+        "Images (comma-separated, optional):",
+    )?);
+
+    let registry = EcrRegistry {
+        name,
+        account_id,
+        region,
+        images,
+        sso_profile,
+    };
+    let entry = to_entry(serde_yaml::to_value(&registry))?;
+    insert(cli_path, Section::EcrRegistries, &registry.name, entry)
+}
+
+/// Removes an ECR registry.
+///
+/// # Arguments
+/// * `cli_path` - Path passed via `--config`, if any
+/// * `name` - Registry to remove; selected from a menu if not given
+/// * `yes` - Remove without asking for confirmation
+///
+/// # Errors
+/// Returns error if the registry is not defined in the target file or the
+/// config cannot be written
+pub fn registry_remove(cli_path: Option<&str>, name: Option<&str>, yes: bool) -> Result<()> {
+    remove(cli_path, Section::EcrRegistries, name, yes)
+}
+
+/// Adds an SSO config after prompting for its settings.
+///
+/// # Arguments
+/// * `cli_path` - Path passed via `--config`, if any
+///
+/// # Errors
+/// Returns error if the profile name is taken or the config cannot be written
+pub fn sso_add(cli_path: Option<&str>) -> Result<()> {
+    let cfg = Config::resolve(cli_path)?;
+    let profile_name = interactive::input_text("Profile name:")?;
+    ensure_new(&cfg, Section::SsoConfigs, &profile_name)?;
+
+    let sso = SsoConfig {
+        profile_name,
+        sso_session_name: interactive::input_text("SSO session name:")?,
+        start_url: interactive::input_text("SSO start URL:")?,
+        region: interactive::input_text("Region:")?,
+        sso_account_id: interactive::input_text("AWS Account ID:")?,
+        sso_role_name: interactive::input_text("IAM Role name:")?,
+        output_format: interactive::input_text_initial("Output format:", DEFAULT_OUTPUT_FORMAT)?,
+    };
+    let entry = to_entry(serde_yaml::to_value(&sso))?;
+    insert(cli_path, Section::SsoConfigs, &sso.profile_name, entry)
+}
+
+/// Removes an SSO config.
+///
+/// # Arguments
+/// * `cli_path` - Path passed via `--config`, if any
+/// * `name` - Profile to remove; selected from a menu if not given
+/// * `yes` - Remove without asking for confirmation
+///
+/// # Errors
+/// Returns error if the profile is not defined in the target file or the
+/// config cannot be written
+pub fn sso_remove(cli_path: Option<&str>, name: Option<&str>, yes: bool) -> Result<()> {
+    remove(cli_path, Section::SsoConfigs, name, yes)
+}
+
+/// Prompts for every server setting, starting from `current`.
+///
+/// Settings without a prompt (agent forwarding, extra ssh options) are kept.
+fn prompt_server(current: Server) -> Result<Server> {
+    let ip = interactive::input_text_initial("IP address or host:", &current.ip)?;
+    let user = interactive::input_text_initial("Username:", &current.user)?;
+    let auth = prompt_auth(&current.auth)?;
+
+    let port = optional(interactive::input_text_initial(
+        "Port (empty for 22):",
+        &current.port.map(|p| p.to_string()).unwrap_or_default(),
+    )?)
+    .map(|p| {
+        p.parse::<u16>().map_err(|e| {
+            StoolError::new(StoolErrorType::InvalidInput)
+                .with_message(format!("Invalid port: {}", p))
+                .with_source(e)
+        })
+    })
+    .transpose()?;
+    let proxy_jump = optional(interactive::input_text_initial(
+        "Jump host (server name or [user@]host[:port], optional):",
+        current.proxy_jump.as_deref().unwrap_or_default(),
+    )?);
+    let group = optional(interactive::input_text_initial(
+        "Group (optional):",
+        current.group.as_deref().unwrap_or_default(),
+    )?);
+    let environment = optional(interactive::input_text_initial(
+        "Environment (optional):",
+        current.environment.as_deref().unwrap_or_default(),
+    )?);
+    let tags = split_list(&interactive::input_text_initial(
+        "Tags (comma-separated, optional):",
+        &current.tags.join(", "),
+    )?);
+
+    Ok(Server {
+        ip,
+        user,
+        auth,
+        port,
+        proxy_jump,
+        group,
+        environment,
+        tags,
+        ..current
+    })
+}
+
+/// Prompts for the authentication method, pre-selecting the current one.
+fn prompt_auth(current: &ServerAuth) -> Result<ServerAuth> {
+    let reference = current
+        .password
+        .as_deref()
+        .filter(|p| !SecretRef::parse(p).is_plain());
+    let default = match (&current.key_path, &current.password) {
+        (Some(_), _) => 0,
+        (None, Some(_)) if reference.is_some() => 2,
+        (None, Some(_)) => 1,
+        (None, None) => 3,
+    };
+    let items: Vec<String> = AUTH_METHODS.iter().map(|m| m.to_string()).collect();
+    let selection = interactive::select_from_list_with_default("Authentication:", &items, default)?;
+
+    let mut auth = ServerAuth {
+        identity_agent: current.identity_agent.clone(),
+        ..ServerAuth::default()
+    };
+    match selection {
+        0 => {
+            let key = match &current.key_path {
+                Some(key) => interactive::input_text_initial("Key file path:", key)?,
+                None => interactive::input_path("Key file path:")?,
+            };
+            auth.key_path = optional(key);
+        }
+        1 => {
+            let password = interactive::input_password("Password (leave empty to keep current):")?;
+            auth.password = if password.is_empty() {
+                current.password.clone().filter(|_| reference.is_none())
+            } else if matches!(SecretRef::parse(&password), SecretRef::Plain(p) if p == *password) {
+                Some(password.to_string())
+            } else {
+                // Keep passwords that look like references literal
+                Some(format!("plain:{}", *password))
+            };
+        }
+        2 => {
+            let value = interactive::input_text_initial(
+                "Secret reference:",
+                reference.unwrap_or_default(),
+            )?;
+            if SecretRef::parse(&value).is_plain() {
+                return Err(
+                    StoolError::new(StoolErrorType::InvalidInput).with_message(format!(
+                        "Not a secret reference: '{}' (expected env:, file:, cmd: or vault:)",
+                        value
+                    )),
+                );
+            }
+            auth.password = Some(value);
+        }
+        _ => {}
+    }
+    Ok(auth)
+}
+
+/// Appends a new entry to the target file.
+fn insert(cli_path: Option<&str>, section: Section, name: &str, entry: Value) -> Result<()> {
+    let target = edit_target(cli_path)?;
+    let (original, format) = read_target(&target)?;
+    let content = document::insert_entries(&original, format, section, &[entry])?;
+    validate_with(cli_path, &target, &content)?;
+    write_config(&target, &content)?;
+
+    info!(
+        "Added '{}' to {} in {}",
+        name,
+        section.key(),
+        target.display()
+    );
+    Ok(())
+}
+
+/// Removes an entry from the target file after confirmation.
+fn remove(cli_path: Option<&str>, section: Section, name: Option<&str>, yes: bool) -> Result<()> {
+    let cfg = Config::resolve(cli_path)?;
+    let Some(name) = pick(&cfg, section, name)? else {
+        return Ok(());
+    };
+
+    let target = edit_target(cli_path)?;
+    let (original, format) = read_target(&target)?;
+    let content =
+        document::remove_entry(&original, format, section, &name).map_err(|e| {
+            match cfg.source_of(section, &name) {
+                Some(source) if e.error_type == StoolErrorType::EntryNotFound => {
+                    e.with_message(format!(
+                        "'{}' is defined in {}, not in {}",
+                        name,
+                        source,
+                        target.display()
+                    ))
+                }
+                _ => e,
+            }
+        })?;
+
+    if section == Section::Servers {
+        for server in cfg
+            .servers
+            .iter()
+            .filter(|s| s.proxy_jump.as_deref() == Some(&name))
+        {
+            warn!("'{}' uses '{}' as its jump host", server.name, name);
+        }
+    }
+    if !yes
+        && !interactive::confirm(
+            &format!("Remove '{}' from {}?", name, target.display()),
+            false,
+        )?
+    {
+        info!("Cancelled");
+        return Ok(());
+    }
+
+    validate_with(cli_path, &target, &content)?;
+    write_config(&target, &content)?;
+    info!(
+        "Removed '{}' from {} in {}",
+        name,
+        section.key(),
+        target.display()
+    );
+    Ok(())
+}
+
+/// Returns the given name if it exists, or lets the user select one.
+///
+/// Returns `None` if the selection was cancelled.
+fn pick(cfg: &Config, section: Section, name: Option<&str>) -> Result<Option<String>> {
+    let names = entry_names(cfg, section);
+    if let Some(name) = name {
+        if !names.contains(&name) {
+            return Err(not_found(section, name));
+        }
+        return Ok(Some(name.to_string()));
+    }
+    if names.is_empty() {
+        return Err(StoolError::new(StoolErrorType::EntryNotFound)
+            .with_message(format!("No entries in {}", section.key())));
+    }
+
+    let mut items: Vec<String> = names
+        .iter()
+        .enumerate()
+        .map(|(i, n)| format!("{}. {}", i + 1, n))
+        .collect();
+    items.push(format!("{}. Cancel", items.len() + 1));
+    let selection = interactive::select_from_list("Select entry:", &items)?;
+    Ok(names.get(selection).map(|n| n.to_string()))
+}
+
+fn entry_names(cfg: &Config, section: Section) -> Vec<&str> {
+    match section {
+        Section::Servers => cfg.servers.iter().map(|s| s.name.as_str()).collect(),
+        Section::EcrRegistries => cfg.ecr_registries.iter().map(|r| r.name.as_str()).collect(),
+        Section::SsoConfigs => cfg
+            .sso_configs
+            .iter()
+            .map(|c| c.profile_name.as_str())
+            .collect(),
+        Section::Contexts => cfg.contexts.iter().map(|c| c.name.as_str()).collect(),
+    }
+}
+
+fn ensure_new(cfg: &Config, section: Section, name: &str) -> Result<()> {
+    if entry_names(cfg, section).contains(&name) {
+        return Err(
+            StoolError::new(StoolErrorType::InvalidInput).with_message(format!(
+                "'{}' already exists in {}",
+                name,
+                section.key()
+            )),
+        );
+    }
+    Ok(())
+}
+
+fn not_found(section: Section, name: &str) -> StoolError {
+    StoolError::new(StoolErrorType::EntryNotFound).with_message(format!(
+        "'{}' not found in {}",
+        name,
+        section.key()
+    ))
+}
+
+fn to_entry(value: serde_yaml::Result<Value>) -> Result<Value> {
+    value.map_err(|e| {
+        StoolError::new(StoolErrorType::ConfigParseError)
+            .with_message("Failed to serialize config entry")
+            .with_source(e)
+    })
+}
+
+/// Returns `None` for blank input.
+fn optional(value: String) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Splits comma-separated input, dropping blank items.
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+        .collect()
+}
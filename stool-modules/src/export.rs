@@ -0,0 +1,596 @@
+//! Server inventory export module.
+//!
+//! Renders configured servers for other tools:
+//! - OpenSSH client config (`ssh`, `scp`)
+//! - Ansible inventory (INI and YAML)
+//! - CSV for spreadsheets
+//! - The stool config itself as YAML, TOML or JSON (format conversion)
+//!
+//! Groups, environments and tags become Ansible groups and are kept as
+//! columns or comments in the other formats. Passwords are never written
+//! unless explicitly requested.
+
+use log::warn;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use stool_core::config::format::ConfigFormat;
+use stool_core::config::{Config, Section, Server, ServerFilter};
+use stool_core::error::{Result, StoolError, StoolErrorType};
+use stool_core::secret::SecretRef;
+use stool_utils::{command, interactive};
+use zeroize::Zeroizing;
+
+/// Ansible group containing every exported host.
+const ANSIBLE_ALL_GROUP: &str = "all";
+
+/// Inventory output format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    SshConfig,
+    AnsibleIni,
+    AnsibleYaml,
+    Csv,
+    /// The resolved stool config in a config file format.
+    Config(ConfigFormat),
+}
+
+impl ExportFormat {
+    /// Format names accepted on the command line.
+    pub const NAMES: [&'static str; 7] = [
+        "ssh-config",
+        "ansible-ini",
+        "ansible-yaml",
+        "csv",
+        "yaml",
+        "toml",
+        "json",
+    ];
+}
+
+impl FromStr for ExportFormat {
+    type Err = StoolError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ssh-config" => Ok(Self::SshConfig),
+            "ansible-ini" => Ok(Self::AnsibleIni),
+            "ansible-yaml" => Ok(Self::AnsibleYaml),
+            "csv" => Ok(Self::Csv),
+            "yaml" => Ok(Self::Config(ConfigFormat::Yaml)),
+            "toml" => Ok(Self::Config(ConfigFormat::Toml)),
+            "json" => Ok(Self::Config(ConfigFormat::Json)),
+            _ => Err(
+                StoolError::new(StoolErrorType::InvalidInput).with_message(format!(
+                    "Unknown export format '{}' (expected one of: {})",
+                    s,
+                    Self::NAMES.join(", ")
+                )),
+            ),
+        }
+    }
+}
+
+/// Server with its password resolved for export.
+struct ExportedServer<'a> {
+    server: &'a Server,
+    password: Option<Zeroizing<String>>,
+}
+
+/// Prints configured servers in the given format.
+///
+/// Config formats (`yaml`, `toml`, `json`) print the whole resolved config
+/// with passwords as configured instead of resolved.
+///
+/// # Arguments
+/// * `cfg` - Resolved configuration
+/// * `format` - Output format
+/// * `include_passwords` - Resolve and emit passwords (not supported by `ssh-config`)
+/// * `filter` - Criteria selecting the servers to export
+///
+/// # Errors
+/// Returns error if a password cannot be resolved or the output cannot be serialized
+pub fn export(
+    cfg: &Config,
+    format: ExportFormat,
+    include_passwords: bool,
+    filter: &ServerFilter,
+) -> Result<()> {
+    if include_passwords && format == ExportFormat::SshConfig {
+        warn!("ssh-config cannot hold passwords; exporting without them");
+    }
+    let include_passwords = include_passwords && format != ExportFormat::SshConfig;
+
+    let output = Zeroizing::new(match format {
+        ExportFormat::Config(config_format) => {
+            render_config(cfg, config_format, include_passwords, filter)?
+        }
+        _ => {
+            let servers = resolve_servers(filter.apply(&cfg.servers), include_passwords)?;
+            if format != ExportFormat::Csv {
+                for message in alias_collisions(&servers) {
+                    warn!("{}", message);
+                }
+            }
+            match format {
+                ExportFormat::SshConfig => render_ssh_config(&servers, &cfg.servers),
+                ExportFormat::AnsibleIni => render_ansible_ini(&servers, &cfg.servers)?,
+                ExportFormat::AnsibleYaml => render_ansible_yaml(&servers, &cfg.servers)?,
+                _ => render_csv(&servers, include_passwords),
+            }
+        }
+    });
+    print!("{}", *output);
+    Ok(())
+}
+
+/// Renders the resolved config, keeping only servers matching `filter`.
+///
+/// Secret references are kept since they hold no secret; literal passwords
+/// are only written with `include_passwords`.
+fn render_config(
+    cfg: &Config,
+    format: ConfigFormat,
+    include_passwords: bool,
+    filter: &ServerFilter,
+) -> Result<String> {
+    let servers: Vec<Server> = filter
+        .apply(&cfg.servers)
+        .into_iter()
+        .map(|server| {
+            let mut server = server.clone();
+            let literal = server
+                .auth
+                .password
+                .as_deref()
+                .is_some_and(|p| SecretRef::parse(p).is_plain());
+            if literal && !include_passwords {
+                server.auth.password = None;
+            }
+            server
+        })
+        .collect();
+
+    let serialize_error = |e: serde_yaml::Error| {
+        StoolError::new(StoolErrorType::ConfigParseError)
+            .with_message("Failed to serialize config")
+            .with_source(e)
+    };
+    let mut value = serde_yaml::to_value(cfg).map_err(serialize_error)?;
+    if let Some(mapping) = value.as_mapping_mut() {
+        mapping.insert(
+            Section::Servers.key().into(),
+            serde_yaml::to_value(servers).map_err(serialize_error)?,
+        );
+    }
+    format.render(&value)
+}
+
+/// Resolves passwords if requested, asking for the vault passphrase at most once.
+fn resolve_servers(
+    servers: Vec<&Server>,
+    include_passwords: bool,
+) -> Result<Vec<ExportedServer<'_>>> {
+    let mut passphrase: Option<Zeroizing<String>> = None;
+
+    servers
+        .into_iter()
+        .map(|server| {
+            let password = match server.auth.password.as_deref() {
+                Some(value) if include_passwords => {
+                    let password = interactive::resolve_secret(value, || {
+                        if passphrase.is_none() {
+                            passphrase = Some(interactive::vault_passphrase()?);
+                        }
+                        Ok(passphrase.clone().unwrap_or_default())
+                    })
+                    .map_err(|e| {
+                        StoolError::new(StoolErrorType::SecretResolutionFailed)
+                            .with_message(format!("Password of '{}'", server.name))
+                            .with_source(e)
+                    })?;
+                    Some(password)
+                }
+                _ => None,
+            };
+            Ok(ExportedServer { server, password })
+        })
+        .collect()
+}
+
+fn render_ssh_config(servers: &[ExportedServer], all: &[Server]) -> String {
+    let mut out = String::from("# Generated by stool config export\n");
+    for ExportedServer { server, .. } in servers {
+        let labels = labels(server);
+        if !labels.is_empty() {
+            out.push_str(&format!("\n# {}", labels.join(", ")));
+        }
+        out.push_str(&format!("\nHost {}\n", host_alias(&server.name)));
+        out.push_str(&format!("    HostName {}\n", server.ip));
+        out.push_str(&format!("    User {}\n", server.user));
+        if let Some(key) = &server.auth.key_path {
+            out.push_str(&format!("    IdentityFile {}\n", ssh_config_quote(key)));
+        }
+        if let Some(port) = server.port {
+            out.push_str(&format!("    Port {}\n", port));
+        }
+        if let Some(jump) = &server.proxy_jump {
+            // Named jump servers refer to their Host entry if it is exported too
+            let jump = match server.jump_server(all) {
+                Some(j) if servers.iter().any(|e| e.server.name == j.name) => host_alias(&j.name),
+                Some(j) => match j.port {
+                    Some(port) => format!("{}@{}:{}", j.user, j.ip, port),
+                    None => format!("{}@{}", j.user, j.ip),
+                },
+                None => jump.clone(),
+            };
+            out.push_str(&format!("    ProxyJump {}\n", jump));
+        }
+        if let Some(agent) = &server.auth.identity_agent {
+            out.push_str(&format!("    IdentityAgent {}\n", ssh_config_quote(agent)));
+        }
+        if server.forward_agent {
+            out.push_str("    ForwardAgent yes\n");
+        }
+        for (key, value) in &server.ssh_options {
+            out.push_str(&format!("    {} {}\n", key, ssh_config_quote(value)));
+        }
+    }
+    out
+}
+
+fn render_ansible_ini(servers: &[ExportedServer], all: &[Server]) -> Result<String> {
+    let mut out = format!("[{}]\n", ANSIBLE_ALL_GROUP);
+    for exported in servers {
+        out.push_str(&host_alias(&exported.server.name));
+        for (key, value) in ansible_vars(exported, all)? {
+            let value = Zeroizing::new(ini_quote(&value));
+            out.push(' ');
+            out.push_str(key);
+            out.push('=');
+            out.push_str(&value);
+        }
+        out.push('\n');
+    }
+
+    for (group, hosts) in ansible_groups(servers) {
+        out.push_str(&format!("\n[{}]\n", group));
+        for host in hosts {
+            out.push_str(&format!("{}\n", host));
+        }
+    }
+    Ok(out)
+}
+
+fn render_ansible_yaml(servers: &[ExportedServer], all: &[Server]) -> Result<String> {
+    use serde_yaml::{Mapping, Value};
+
+    let mut hosts = Mapping::new();
+    for exported in servers {
+        let vars: Mapping = ansible_vars(exported, all)?
+            .into_iter()
+            .map(|(key, value)| (Value::from(key), Value::from(value.as_str())))
+            .collect();
+        hosts.insert(
+            Value::from(host_alias(&exported.server.name)),
+            Value::Mapping(vars),
+        );
+    }
+
+    let mut children = Mapping::new();
+    for (name, members) in ansible_groups(servers) {
+        let members: Mapping = members
+            .into_iter()
+            .map(|host| (Value::from(host), Value::Mapping(Mapping::new())))
+            .collect();
+        let mut child = Mapping::new();
+        child.insert(Value::from("hosts"), Value::Mapping(members));
+        children.insert(Value::from(name), Value::Mapping(child));
+    }
+
+    let mut group = Mapping::new();
+    group.insert(Value::from("hosts"), Value::Mapping(hosts));
+    if !children.is_empty() {
+        group.insert(Value::from("children"), Value::Mapping(children));
+    }
+    let mut inventory = Mapping::new();
+    inventory.insert(Value::from(ANSIBLE_ALL_GROUP), Value::Mapping(group));
+
+    serde_yaml::to_string(&inventory).map_err(|e| {
+        StoolError::new(StoolErrorType::ConfigParseError)
+            .with_message("Failed to serialize Ansible inventory")
+            .with_source(e)
+    })
+}
+
+fn render_csv(servers: &[ExportedServer], include_passwords: bool) -> String {
+    let mut header = vec![
+        "name",
+        "ip",
+        "user",
+        "port",
+        "key_path",
+        "proxy_jump",
+        "group",
+        "environment",
+        "tags",
+    ];
+    if include_passwords {
+        header.push("password");
+    }
+    let mut out = format!("{}\n", header.join(","));
+
+    for ExportedServer { server, password } in servers {
+        let port = server.port.map(|p| p.to_string()).unwrap_or_default();
+        let tags = server.tags.join(";");
+        let mut row = vec![
+            server.name.as_str(),
+            server.ip.as_str(),
+            server.user.as_str(),
+            port.as_str(),
+            server.auth.key_path.as_deref().unwrap_or(""),
+            server.proxy_jump.as_deref().unwrap_or(""),
+            server.group.as_deref().unwrap_or(""),
+            server.environment.as_deref().unwrap_or(""),
+            tags.as_str(),
+        ];
+        if include_passwords {
+            row.push(password.as_ref().map_or("", |p| p.as_str()));
+        }
+        for (i, field) in row.into_iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str(&Zeroizing::new(csv_quote(field)));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Returns Ansible connection variables for a server.
+///
+/// Jump hosts, agent settings and extra options are passed as
+/// `ansible_ssh_common_args`, built the same way as for `stool ssh`.
+/// Values are zeroized on drop since `ansible_password` is among them.
+fn ansible_vars(
+    exported: &ExportedServer,
+    all: &[Server],
+) -> Result<Vec<(&'static str, Zeroizing<String>)>> {
+    let server = exported.server;
+    let mut vars = vec![
+        ("ansible_host", Zeroizing::new(server.ip.clone())),
+        ("ansible_user", Zeroizing::new(server.user.clone())),
+    ];
+    if let Some(port) = server.port {
+        vars.push(("ansible_port", Zeroizing::new(port.to_string())));
+    }
+    if let Some(key) = &server.auth.key_path {
+        vars.push(("ansible_ssh_private_key_file", Zeroizing::new(key.clone())));
+    }
+
+    // The port is already set through ansible_port
+    let without_port = Server {
+        port: None,
+        ..server.clone()
+    };
+    let args = command::ssh_option_args(&without_port, all)?;
+    if !args.is_empty() {
+        vars.push((
+            "ansible_ssh_common_args",
+            Zeroizing::new(command::shell_join(&args)),
+        ));
+    }
+    if let Some(password) = &exported.password {
+        vars.push(("ansible_password", password.clone()));
+    }
+    Ok(vars)
+}
+
+/// Returns Ansible groups (from group, environment and tags) and their hosts.
+fn ansible_groups(servers: &[ExportedServer]) -> BTreeMap<String, Vec<String>> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for ExportedServer { server, .. } in servers {
+        let names = server
+            .group
+            .iter()
+            .chain(server.environment.iter())
+            .chain(server.tags.iter());
+        for name in names {
+            let hosts = groups.entry(ansible_group_name(name)).or_default();
+            let alias = host_alias(&server.name);
+            if !hosts.contains(&alias) {
+                hosts.push(alias);
+            }
+        }
+    }
+    groups
+}
+
+/// Makes a label usable as an Ansible group name (letters, digits, `_`).
+fn ansible_group_name(label: &str) -> String {
+    label
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Returns `group: x`, `env: y` and `tags: a, b` labels of a server.
+fn labels(server: &Server) -> Vec<String> {
+    let mut labels = Vec::new();
+    if let Some(group) = &server.group {
+        labels.push(format!("group: {}", group));
+    }
+    if let Some(env) = &server.environment {
+        labels.push(format!("env: {}", env));
+    }
+    if !server.tags.is_empty() {
+        labels.push(format!("tags: {}", server.tags.join(" ")));
+    }
+    labels
+}
+
+/// Describes servers that would be exported under the same host alias, in
+/// which case `ssh` and Ansible only see the first of them.
+fn alias_collisions(servers: &[ExportedServer]) -> Vec<String> {
+    let mut seen: BTreeMap<String, &str> = BTreeMap::new();
+    let mut collisions = Vec::new();
+    for ExportedServer { server, .. } in servers {
+        let alias = host_alias(&server.name);
+        match seen.get(&alias) {
+            Some(first) => collisions.push(format!(
+                "Servers '{}' and '{}' are both exported as host '{}'",
+                first, server.name, alias
+            )),
+            None => {
+                seen.insert(alias, &server.name);
+            }
+        }
+    }
+    collisions
+}
+
+/// Makes a server name usable as an ssh/Ansible host alias.
+fn host_alias(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join("-")
+}
+
+/// Quotes an ssh config value containing whitespace.
+fn ssh_config_quote(value: &str) -> String {
+    if value.contains(char::is_whitespace) {
+        format!("\"{}\"", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Quotes an INI inventory value if Ansible's shlex parsing would split it.
+fn ini_quote(value: &str) -> String {
+    if value.is_empty() || value.contains(|c: char| c.is_whitespace() || "\"'#=\\".contains(c)) {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Quotes a CSV field per RFC 4180.
+fn csv_quote(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn servers(yaml: &str) -> Vec<Server> {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn ssh_config_quote_quotes_whitespace_only() {
+        assert_eq!(ssh_config_quote("~/.ssh/id_ed25519"), "~/.ssh/id_ed25519");
+        assert_eq!(ssh_config_quote("~/.ssh/my key"), "\"~/.ssh/my key\"");
+    }
+
+    #[test]
+    fn ini_quote_escapes_what_shlex_would_split() {
+        assert_eq!(ini_quote("10.0.1.5"), "10.0.1.5");
+        assert_eq!(ini_quote(""), "\"\"");
+        assert_eq!(
+            ini_quote("-o ProxyJump=bastion"),
+            "\"-o ProxyJump=bastion\""
+        );
+        assert_eq!(ini_quote(r#"pa"ss\word"#), r#""pa\"ss\\word""#);
+        assert_eq!(ini_quote("a#b"), "\"a#b\"");
+    }
+
+    #[test]
+    fn csv_quote_follows_rfc_4180() {
+        assert_eq!(csv_quote("web-1"), "web-1");
+        assert_eq!(csv_quote("web;api"), "web;api");
+        assert_eq!(csv_quote("a,b"), "\"a,b\"");
+        assert_eq!(csv_quote(r#"say "hi""#), r#""say ""hi""""#);
+        assert_eq!(csv_quote("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn ssh_config_quotes_extra_option_values() {
+        let all = servers(
+            "- {name: web-1, ip: 10.0.1.5, user: deploy, ssh_options: {RemoteCommand: tmux attach, Compression: 'yes'}}",
+        );
+        let exported = resolve_servers(all.iter().collect(), false).unwrap();
+        let out = render_ssh_config(&exported, &all);
+        assert!(out.contains("    RemoteCommand \"tmux attach\"\n"), "{out}");
+        assert!(out.contains("    Compression yes\n"), "{out}");
+    }
+
+    #[test]
+    fn passwords_are_left_out_unless_requested() {
+        let all = servers("- {name: web-1, ip: 10.0.1.5, user: deploy, auth: {password: hunter2}}");
+        let exported = resolve_servers(all.iter().collect(), false).unwrap();
+        assert!(exported[0].password.is_none());
+
+        let csv = render_csv(&exported, false);
+        assert_eq!(
+            csv,
+            "name,ip,user,port,key_path,proxy_jump,group,environment,tags\nweb-1,10.0.1.5,deploy,,,,,,\n"
+        );
+        let ini = render_ansible_ini(&exported, &all).unwrap();
+        assert!(!ini.contains("hunter2"), "{ini}");
+    }
+
+    #[test]
+    fn requested_passwords_are_exported() {
+        let all =
+            servers("- {name: web-1, ip: 10.0.1.5, user: deploy, auth: {password: 'hunter 2'}}");
+        let exported = resolve_servers(all.iter().collect(), true).unwrap();
+
+        let csv = render_csv(&exported, true);
+        assert!(
+            csv.starts_with(
+                "name,ip,user,port,key_path,proxy_jump,group,environment,tags,password\n"
+            ),
+            "{csv}"
+        );
+        assert!(csv.ends_with(",hunter 2\n"), "{csv}");
+        let ini = render_ansible_ini(&exported, &all).unwrap();
+        assert!(ini.contains(" ansible_password=\"hunter 2\""), "{ini}");
+        let ssh = render_ssh_config(&exported, &all);
+        assert!(!ssh.contains("hunter"), "{ssh}");
+    }
+
+    #[test]
+    fn config_export_keeps_references_and_drops_literal_passwords() {
+        let cfg: Config = serde_yaml::from_str(
+            "servers:
+  - {name: web-1, ip: 10.0.1.5, user: deploy, auth: {password: hunter2}}
+  - {name: web-2, ip: 10.0.1.6, user: deploy, auth: {password: 'env:WEB_PASSWORD'}}
+",
+        )
+        .unwrap();
+        let out = render_config(&cfg, ConfigFormat::Yaml, false, &ServerFilter::default()).unwrap();
+        assert!(!out.contains("hunter2"), "{out}");
+        assert!(out.contains("env:WEB_PASSWORD"), "{out}");
+
+        let out = render_config(&cfg, ConfigFormat::Yaml, true, &ServerFilter::default()).unwrap();
+        assert!(out.contains("hunter2"), "{out}");
+    }
+
+    #[test]
+    fn colliding_aliases_are_reported() {
+        let all = servers(
+            "- {name: web 1, ip: 10.0.1.5, user: deploy}
+- {name: web-1, ip: 10.0.1.6, user: deploy}
+- {name: web-2, ip: 10.0.1.7, user: deploy}
+",
+        );
+        let exported = resolve_servers(all.iter().collect(), false).unwrap();
+        assert_eq!(
+            alias_collisions(&exported),
+            vec!["Servers 'web 1' and 'web-1' are both exported as host 'web-1'".to_string()]
+        );
+    }
+}
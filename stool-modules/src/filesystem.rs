@@ -4,6 +4,7 @@
 //! - Find files by exact name, glob pattern, or partial match
 //! - Count files and directories in a path
 
+use log::{info, warn};
 use std::fs;
 use std::path::Path;
 use stool_core::error::{Result, StoolError, StoolErrorType};
@@ -46,7 +47,7 @@ pub fn find(pattern: &str, path: Option<&str>) -> Result<()> {
         (false, format!("*{}*", pattern))
     };
 
-    println!("Searching for '{}' in {}...", pattern, search_path);
+    info!("Searching for '{}' in {}...", pattern, search_path);
 
     // Compile regex once if using glob pattern
     let compiled_regex = if !is_exact {
@@ -131,7 +132,7 @@ fn search_recursive(
         let entry = match entry_result {
             Ok(e) => e,
             Err(e) => {
-                eprintln!("Warning: Failed to read entry in {}: {}", dir.display(), e);
+                warn!("Warning: Failed to read entry in {}: {}", dir.display(), e);
                 continue;
             }
         };
@@ -7,10 +7,22 @@
 //! - File transfer via SCP
 //! - Docker operations (build, tag, push to ECR)
 //! - AWS CLI wrapper
+//! - Config inspection and editing
+//! - Config entry management (add, edit, remove)
+//! - Context switching
+//! - Server inventory export (ssh config, Ansible, CSV)
+//! - Encrypted secrets vault
+//! - Operation history and re-run
 
 pub mod aws;
+pub mod config;
+pub mod context;
 pub mod docker;
+pub mod entries;
+pub mod export;
 pub mod filesystem;
+pub mod history;
 pub mod ssh;
 pub mod transfer;
 pub mod update;
+pub mod vault;
@@ -2,30 +2,224 @@
 //!
 //! Handles SSH connections to servers with multiple authentication methods:
 //! - PEM key authentication
-//! - Password authentication (via expect)
+//! - Password authentication (via the askpass helper)
 //! - Default SSH key authentication
+//!
+//! Ports, jump hosts, agent settings and extra SSH options from the server
+//! config apply to every method. A server named on the command line is
+//! connected to directly; otherwise it is chosen from a menu. Single
+//! commands can be run remotely without an interactive session.
+
+use crate::history;
+use log::{Level, log, warn};
+use std::io::{self, IsTerminal};
+use stool_core::config::{self, Context, NameMatch, Server, ServerFilter};
+use stool_core::error::{Result, StoolError, StoolErrorType};
+use stool_utils::command;
+use stool_utils::interactive::{self, ServerInfo};
 
-use stool_core::config::Server;
-use stool_core::error::Result;
-use stool_utils::{command, interactive};
+/// Remote command that runs a script read from stdin.
+const STDIN_SCRIPT_COMMAND: &str = "sh -s";
+
+/// Server given on the command line instead of chosen from the menu.
+///
+/// Every field is optional; `ip` and `user` override the values of the
+/// server found by `name`, or describe an ad-hoc host on their own.
+#[derive(Debug, Clone, Default)]
+pub struct Destination {
+    /// Server name, matched exactly, by prefix or fuzzily.
+    pub name: Option<String>,
+    /// Host to connect to instead of the server's IP.
+    pub ip: Option<String>,
+    /// User to log in as instead of the server's user.
+    pub user: Option<String>,
+}
 
-/// Establishes SSH connection to a selected server.
+/// Establishes SSH connection to a server.
 ///
-/// Presents an interactive menu for server selection and handles
+/// Connects directly if `destination` names exactly one server or gives an
+/// ad-hoc host; otherwise presents an interactive menu (limited to the
+/// closest matches if a name matched several servers) and handles
 /// authentication using the configured method (key, password, or default).
 ///
 /// # Arguments
 /// * `servers` - List of available servers from configuration
+/// * `destination` - Server name and overrides from the command line
+/// * `filter` - Group, tag and environment criteria narrowing the menu
+/// * `context` - Active context; its group is pre-selected
 ///
 /// # Errors
-/// Returns error if connection fails or user input is invalid
-pub fn connect(servers: &[Server]) -> Result<()> {
-    let server_info = interactive::select_server(servers)?;
-
-    let (user, ip, key_path, password) = match server_info {
+/// Returns error if no server matches the name and stdin is not a
+/// terminal, the connection fails or user input is invalid
+pub fn connect(
+    servers: &[Server],
+    destination: &Destination,
+    filter: &ServerFilter,
+    context: Option<&Context>,
+) -> Result<()> {
+    let info = match resolve(servers, destination, filter, context, Level::Info)? {
         Some(info) => info,
         None => return Ok(()), // User cancelled
     };
 
-    command::execute_ssh(&user, &ip, key_path.as_deref(), password.as_deref())
+    let operation = history::Operation::Ssh {
+        target: history::Target::of(&info),
+    };
+    history::record(operation, || command::execute_ssh(&info))
+}
+
+/// Runs one command on a server and waits for it.
+///
+/// The server is chosen like in [`connect`]. Without a command, a script
+/// piped to stool's stdin is run with `sh -s`. Only the command's own
+/// output reaches stdout; stool's messages are logged at debug level.
+///
+/// # Arguments
+/// * `servers` - List of available servers from configuration
+/// * `destination` - Server name and overrides from the command line
+/// * `filter` - Group, tag and environment criteria narrowing the menu
+/// * `context` - Active context; its group is pre-selected
+/// * `remote_command` - Command words, joined with spaces like `ssh` does
+/// * `tty` - Force a pseudo-terminal for the command
+///
+/// # Errors
+/// Returns `RemoteCommandFailed` with the remote exit code if the command
+/// fails, `Cancelled` if the server menu is cancelled, or error if no
+/// command is given, no server matches or the connection fails
+pub fn exec(
+    servers: &[Server],
+    destination: &Destination,
+    filter: &ServerFilter,
+    context: Option<&Context>,
+    remote_command: &[String],
+    tty: bool,
+) -> Result<()> {
+    let stdin_script = remote_command.is_empty();
+    let remote_command = if !stdin_script {
+        remote_command.join(" ")
+    } else if !io::stdin().is_terminal() {
+        STDIN_SCRIPT_COMMAND.to_string()
+    } else {
+        return Err(StoolError::new(StoolErrorType::InvalidInput)
+            .with_message("No command given")
+            .with_hint("pass the command after `--`, e.g. `stool ssh exec web -- uptime`, or pipe a script on stdin"));
+    };
+
+    // Scripts must be able to tell "ran nothing" from success
+    let info = resolve(servers, destination, filter, context, Level::Debug)?.ok_or_else(|| {
+        StoolError::new(StoolErrorType::Cancelled).with_message("No server selected")
+    })?;
+
+    let operation = history::Operation::exec(
+        history::Target::of(&info),
+        &remote_command,
+        tty,
+        stdin_script,
+    );
+    history::record(operation, || {
+        command::execute_remote(&info, &remote_command, tty)
+    })
+}
+
+/// Turns a destination into connection details, prompting only when the
+/// command line leaves the server open. Messages are logged at `level`.
+///
+/// Returns `None` if the user cancelled the menu.
+fn resolve(
+    servers: &[Server],
+    destination: &Destination,
+    filter: &ServerFilter,
+    context: Option<&Context>,
+    level: Level,
+) -> Result<Option<ServerInfo>> {
+    let default_group = context.and_then(|c| c.group.as_deref());
+    let info = match (&destination.name, &destination.ip) {
+        (Some(name), _) => {
+            let candidates = filter.apply(servers);
+            match config::match_server_name(&candidates, name) {
+                NameMatch::Unique(server) => {
+                    log!(level, "Selected server: {} ({})", server.name, server.ip);
+                    Some(interactive::server_info(server, servers)?)
+                }
+                NameMatch::Ambiguous(matches) => {
+                    // Without a terminal the menu cannot be answered
+                    if !io::stdin().is_terminal() {
+                        return Err(ambiguous_name(name, &matches));
+                    }
+                    interactive::select_server_from(matches, servers, default_group)?
+                }
+                NameMatch::None => {
+                    let mut message = format!("No server matches '{}'", name);
+                    if !filter.is_empty() {
+                        message.push_str(&format!(" ({})", filter));
+                    }
+                    // Without a terminal the menu cannot be answered
+                    if !io::stdin().is_terminal() {
+                        return Err(StoolError::new(StoolErrorType::ServerNotFound)
+                            .with_message(message)
+                            .with_hint("check the name with `stool config show`"));
+                    }
+                    warn!("{}", message);
+                    interactive::select_server(servers, filter, default_group)?
+                }
+            }
+        }
+        (None, Some(ip)) => {
+            let user = destination.user.clone().ok_or_else(|| {
+                StoolError::new(StoolErrorType::InvalidInput)
+                    .with_message(format!("No user given for {}", ip))
+                    .with_hint("pass --user along with --ip")
+            })?;
+            log!(level, "Target: {}@{}", user, ip);
+            Some(ServerInfo {
+                user,
+                ip: ip.clone(),
+                ..ServerInfo::default()
+            })
+        }
+        (None, None) => interactive::select_server(servers, filter, default_group)?,
+    };
+
+    Ok(info.map(|mut info| {
+        if let Some(ip) = &destination.ip {
+            info.ip = ip.clone();
+        }
+        if let Some(user) = &destination.user {
+            info.user = user.clone();
+        }
+        info
+    }))
+}
+
+/// Error for a name that matched several servers equally well.
+fn ambiguous_name(name: &str, matches: &[&Server]) -> StoolError {
+    let names: Vec<&str> = matches.iter().map(|s| s.name.as_str()).collect();
+    StoolError::new(StoolErrorType::ServerNotFound)
+        .with_message(format!("Ambiguous name '{}': {}", name, names.join(", ")))
+        .with_hint("give the full server name")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ambiguous_names_list_the_candidates() {
+        let servers: Vec<Server> = serde_yaml::from_str(
+            "- {name: web-1, ip: 10.0.1.5, user: deploy}
+- {name: web-2, ip: 10.0.1.6, user: deploy}
+",
+        )
+        .unwrap();
+        let matches: Vec<&Server> = servers.iter().collect();
+
+        let err = ambiguous_name("web", &matches);
+
+        assert_eq!(err.error_type, StoolErrorType::ServerNotFound);
+        assert!(
+            err.to_string()
+                .contains("Ambiguous name 'web': web-1, web-2"),
+            "{err}"
+        );
+    }
 }
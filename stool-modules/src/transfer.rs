@@ -5,8 +5,10 @@
 //! - Download files from remote servers
 //! - Supports multiple authentication methods (key, password, default)
 
-use stool_core::config::Server;
+use crate::history;
+use stool_core::config::{Context, Server, ServerFilter};
 use stool_core::error::{Result, StoolError, StoolErrorType};
+use stool_utils::interactive::ServerInfo;
 use stool_utils::{command, interactive};
 
 /// Default remote path for upload operations.
@@ -40,10 +42,22 @@ pub enum TransferMode {
 ///
 /// # Arguments
 /// * `servers` - List of available servers from configuration
+/// * `filter` - Group, tag and environment criteria narrowing the menu
+/// * `context` - Active context; its group is pre-selected
 ///
 /// # Errors
 /// Returns error if transfer fails or user input is invalid
-pub fn transfer(servers: &[Server]) -> Result<()> {
+pub fn transfer(
+    servers: &[Server],
+    filter: &ServerFilter,
+    context: Option<&Context>,
+) -> Result<()> {
+    // Fail before any prompt if the filter rules out every server
+    if !filter.is_empty() && filter.apply(servers).is_empty() {
+        return Err(StoolError::new(StoolErrorType::ServerNotFound)
+            .with_message(format!("No servers match {}", filter)));
+    }
+
     // Select transfer mode
     let mode_items: Vec<String> = vec![
         "1. Upload (local -> remote)".to_string(),
@@ -59,31 +73,23 @@ pub fn transfer(servers: &[Server]) -> Result<()> {
     };
 
     // Select server or manual input
-    let server_info = interactive::select_server(servers)?;
+    let server_info =
+        interactive::select_server(servers, filter, context.and_then(|c| c.group.as_deref()))?;
 
-    let (user, ip, key_path, password) = match server_info {
+    let info = match server_info {
         Some(info) => info,
         None => return Ok(()), // User cancelled
     };
 
     match mode {
-        TransferMode::Upload => {
-            execute_upload(&user, &ip, key_path.as_deref(), password.as_deref())?
-        }
-        TransferMode::Download => {
-            execute_download(&user, &ip, key_path.as_deref(), password.as_deref())?
-        }
+        TransferMode::Upload => execute_upload(&info)?,
+        TransferMode::Download => execute_download(&info)?,
     }
 
     Ok(())
 }
 
-fn execute_upload(
-    user: &str,
-    ip: &str,
-    key_path: Option<&str>,
-    password: Option<&str>,
-) -> Result<()> {
+fn execute_upload(info: &ServerInfo) -> Result<()> {
     let local_path = interactive::input_path("Local file path:")?;
     let remote_path_input = interactive::input_text_optional(&format!(
         "Remote path (default: {}): ",
@@ -95,20 +101,17 @@ fn execute_upload(
         remote_path_input
     };
 
-    command::execute_scp(
-        &local_path,
-        &format!("{}@{}:{}", user, ip, remote_path),
-        key_path,
-        password,
-    )
+    let operation = history::Operation::Upload {
+        target: history::Target::of(info),
+        local: local_path.clone(),
+        remote: remote_path.clone(),
+    };
+    history::record(operation, || {
+        command::upload(&local_path, &remote_path, info)
+    })
 }
 
-fn execute_download(
-    user: &str,
-    ip: &str,
-    key_path: Option<&str>,
-    password: Option<&str>,
-) -> Result<()> {
+fn execute_download(info: &ServerInfo) -> Result<()> {
     let remote_path = interactive::input_text("Remote file path:")?;
     let default_path = get_default_local_path();
     let local_path_input =
@@ -119,10 +122,12 @@ fn execute_download(
         local_path_input
     };
 
-    command::execute_scp(
-        &format!("{}@{}:{}", user, ip, remote_path),
-        &local_path,
-        key_path,
-        password,
-    )
+    let operation = history::Operation::Download {
+        target: history::Target::of(info),
+        remote: remote_path.clone(),
+        local: local_path.clone(),
+    };
+    history::record(operation, || {
+        command::download(&remote_path, &local_path, info)
+    })
 }
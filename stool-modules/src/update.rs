@@ -4,6 +4,9 @@
 //! - Homebrew packages
 //! - Rust toolchain via rustup
 
+use crate::history;
+use log::{error, info};
+use stool_core::config::execution::{ExecutionConfig, Operation};
 use stool_core::error::{Result, StoolError, StoolErrorType};
 use stool_utils::command;
 
@@ -11,15 +14,29 @@ use stool_utils::command;
 ///
 /// Executes `brew update` and `brew upgrade` sequentially.
 ///
+/// # Arguments
+/// * `execution` - Timeouts and retries for the brew commands
+///
 /// # Errors
 /// Returns error if brew commands fail
-pub fn update_brew() -> Result<()> {
-    println!("Updating Homebrew");
+pub fn update_brew(execution: &ExecutionConfig) -> Result<()> {
+    info!("Updating Homebrew");
 
-    command::execute_command("brew", &["update"], StoolErrorType::BrewUpdateFailed)?;
-    command::execute_command("brew", &["upgrade"], StoolErrorType::BrewUpdateFailed)?;
+    let policy = execution.policy(Operation::Brew);
+    command::execute_command(
+        "brew",
+        &["update"],
+        StoolErrorType::BrewUpdateFailed,
+        &policy,
+    )?;
+    command::execute_command(
+        "brew",
+        &["upgrade"],
+        StoolErrorType::BrewUpdateFailed,
+        &policy,
+    )?;
 
-    println!("Homebrew updated successfully");
+    info!("Homebrew updated successfully");
     Ok(())
 }
 
@@ -27,14 +44,22 @@ pub fn update_brew() -> Result<()> {
 ///
 /// Executes `rustup update` to update all installed toolchains.
 ///
+/// # Arguments
+/// * `execution` - Timeouts and retries for the rustup command
+///
 /// # Errors
 /// Returns error if rustup command fails
-pub fn update_rustup() -> Result<()> {
-    println!("Updating Rust toolchain");
+pub fn update_rustup(execution: &ExecutionConfig) -> Result<()> {
+    info!("Updating Rust toolchain");
 
-    command::execute_command("rustup", &["update"], StoolErrorType::RustupUpdateFailed)?;
+    command::execute_command(
+        "rustup",
+        &["update"],
+        StoolErrorType::RustupUpdateFailed,
+        &execution.policy(Operation::Rustup),
+    )?;
 
-    println!("Rust toolchain updated successfully");
+    info!("Rust toolchain updated successfully");
     Ok(())
 }
 
@@ -43,26 +68,55 @@ pub fn update_rustup() -> Result<()> {
 /// Continues execution even if one update fails, reporting all failures
 /// at the end.
 ///
+/// # Arguments
+/// * `execution` - Timeouts and retries for the update commands
+///
 /// # Errors
 /// Returns error if any update fails, with details of which updates failed
-pub fn update_all() -> Result<()> {
+pub fn update_all(execution: &ExecutionConfig) -> Result<()> {
     let mut errors = Vec::new();
 
-    if let Err(e) = update_brew() {
-        eprintln!("Brew update failed: {}", e);
+    if let Err(e) = update_brew(execution) {
+        error!("Brew update failed: {}", e);
         errors.push("brew");
     }
 
-    if let Err(e) = update_rustup() {
-        eprintln!("Rustup update failed: {}", e);
+    if let Err(e) = update_rustup(execution) {
+        error!("Rustup update failed: {}", e);
         errors.push("rustup");
     }
 
     if errors.is_empty() {
-        println!("\nAll updates completed successfully");
+        info!("All updates completed successfully");
         Ok(())
     } else {
         Err(StoolError::new(StoolErrorType::CommandExecutionFailed)
             .with_message(format!("Failed updates: {}", errors.join(", "))))
     }
 }
+
+/// Runs the selected updates and records them in the history.
+///
+/// Updates both when neither or both of `brew` and `rustup` are set.
+///
+/// # Arguments
+/// * `brew` - Update Homebrew
+/// * `rustup` - Update the Rust toolchain
+/// * `execution` - Timeouts and retries for the update commands
+///
+/// # Errors
+/// Returns error if any selected update fails
+pub fn run(brew: bool, rustup: bool, execution: &ExecutionConfig) -> Result<()> {
+    history::record(history::Operation::Update { brew, rustup }, || {
+        update_selected(brew, rustup, execution)
+    })
+}
+
+/// Runs the selected updates without recording them.
+pub(crate) fn update_selected(brew: bool, rustup: bool, execution: &ExecutionConfig) -> Result<()> {
+    match (brew, rustup) {
+        (true, false) => update_brew(execution),
+        (false, true) => update_rustup(execution),
+        _ => update_all(execution),
+    }
+}
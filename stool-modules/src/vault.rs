@@ -0,0 +1,141 @@
+//! Secrets vault module.
+//!
+//! Manages the passphrase-encrypted vault referenced from config as
+//! `vault:NAME`:
+//! - Initialize a new vault
+//! - Set, get, list and remove secrets
+
+use log::info;
+use std::io::Read;
+use std::path::PathBuf;
+use stool_core::error::{Result, StoolError, StoolErrorType};
+use stool_core::vault::{self, Vault};
+use stool_utils::interactive;
+use zeroize::Zeroizing;
+
+/// Creates a new, empty vault protected by a passphrase.
+///
+/// Uses `$STOOL_VAULT_PASSPHRASE` if set, otherwise prompts twice.
+///
+/// # Errors
+/// Returns error if a vault already exists or cannot be written
+pub fn init() -> Result<()> {
+    let path = vault_path()?;
+    if path.exists() {
+        return Err(StoolError::new(StoolErrorType::VaultAccessFailed)
+            .with_message(format!("Vault already exists: {}", path.display())));
+    }
+
+    let passphrase = match vault::passphrase_from_env() {
+        Ok(passphrase) => passphrase,
+        Err(_) => interactive::input_new_password("New vault passphrase:")?,
+    };
+    Vault::create(&path, &passphrase)?;
+
+    info!("Vault created: {}", path.display());
+    Ok(())
+}
+
+/// Stores a secret in the vault.
+///
+/// # Arguments
+/// * `name` - Secret name (referenced as `vault:NAME`)
+/// * `from_stdin` - Read the value from stdin instead of prompting
+///
+/// # Errors
+/// Returns error if the vault cannot be opened or written
+pub fn set(name: &str, from_stdin: bool) -> Result<()> {
+    let mut vault = open()?;
+
+    let value = if from_stdin {
+        let mut input = Zeroizing::new(String::new());
+        std::io::stdin().read_to_string(&mut input)?;
+        Zeroizing::new(input.trim_end_matches(['\n', '\r']).to_string())
+    } else {
+        interactive::input_new_password(&format!("Value for '{}':", name))?
+    };
+
+    if value.is_empty() {
+        return Err(StoolError::new(StoolErrorType::InvalidInput)
+            .with_message("Secret value must not be empty"));
+    }
+
+    vault.set(name, value.to_string());
+    vault.save()?;
+
+    info!("Secret '{}' saved", name);
+    Ok(())
+}
+
+/// Prints a secret value to stdout.
+///
+/// # Arguments
+/// * `name` - Secret name
+///
+/// # Errors
+/// Returns error if the vault cannot be opened or the secret does not exist
+pub fn get(name: &str) -> Result<()> {
+    let vault = open()?;
+    let value = vault.get(name).ok_or_else(|| {
+        StoolError::new(StoolErrorType::InvalidInput)
+            .with_message(format!("Secret not found: {}", name))
+    })?;
+
+    println!("{}", value);
+    Ok(())
+}
+
+/// Lists the names of all stored secrets.
+///
+/// # Errors
+/// Returns error if the vault cannot be opened
+pub fn list() -> Result<()> {
+    let vault = open()?;
+
+    let mut count = 0;
+    for name in vault.names() {
+        println!("{}", name);
+        count += 1;
+    }
+    if count == 0 {
+        println!("Vault is empty");
+    }
+    Ok(())
+}
+
+/// Removes a secret from the vault.
+///
+/// # Arguments
+/// * `name` - Secret name
+///
+/// # Errors
+/// Returns error if the vault cannot be opened or the secret does not exist
+pub fn remove(name: &str) -> Result<()> {
+    let mut vault = open()?;
+    if !vault.remove(name) {
+        return Err(StoolError::new(StoolErrorType::InvalidInput)
+            .with_message(format!("Secret not found: {}", name)));
+    }
+    vault.save()?;
+
+    info!("Secret '{}' removed", name);
+    Ok(())
+}
+
+fn open() -> Result<Vault> {
+    let path = vault_path()?;
+    if !path.exists() {
+        return Err(StoolError::new(StoolErrorType::VaultNotFound)
+            .with_message(format!("No vault at {}", path.display()))
+            .with_hint("run `stool vault init` to create one"));
+    }
+    let passphrase = interactive::vault_passphrase()?;
+    Vault::open(&path, &passphrase)
+}
+
+fn vault_path() -> Result<PathBuf> {
+    Vault::default_path().ok_or_else(|| {
+        StoolError::new(StoolErrorType::VaultNotFound)
+            .with_message("Cannot determine vault location (HOME not set)")
+    })
+}
@@ -0,0 +1,461 @@
+//! `SSH_ASKPASS` helper for password authentication.
+//!
+//! `ssh` asks for passwords through the program named in `$SSH_ASKPASS`
+//! when `SSH_ASKPASS_REQUIRE=force` is set. stool acts as that program:
+//! - [`AskpassSession`] serves the password on a Unix socket inside a
+//!   private (`0700`) temporary directory while `ssh`/`scp` runs
+//! - [`respond`] runs in the helper process (`stool askpass <prompt>`),
+//!   fetches the password from the socket and prints it for `ssh`
+//!
+//! The password never appears in a command line, script or file. The
+//! socket only answers clients presenting the session's random token, and
+//! only once, for the password prompt of the session's destination: a
+//! nested `ssh` (e.g. a `ProxyCommand` to a jump host) inherits the helper
+//! but is asked for its own password on the terminal.
+
+use crate::runner;
+use log::debug;
+use std::fs::{self, DirBuilder};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use stool_core::error::{Result, StoolError, StoolErrorType};
+use stool_core::hex;
+use zeroize::Zeroizing;
+
+/// Environment variable holding the session socket path.
+const SOCKET_ENV_VAR: &str = "STOOL_ASKPASS_SOCKET";
+/// Environment variable holding the session token.
+const TOKEN_ENV_VAR: &str = "STOOL_ASKPASS_TOKEN";
+/// Socket file name inside the session directory.
+const SOCKET_FILE_NAME: &str = "sock";
+/// Wrapper script `ssh` runs as `$SSH_ASKPASS`.
+const SCRIPT_FILE_NAME: &str = "askpass";
+/// Random bytes in a session token.
+const TOKEN_LEN: usize = 32;
+/// How long a client may take to send its request.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest prompt a client may send.
+const MAX_PROMPT_LEN: u64 = 1024;
+/// Longest `user` and `host` in ssh's password prompt (`%.30s@%.128s`).
+const PROMPT_USER_LEN: usize = 30;
+const PROMPT_HOST_LEN: usize = 128;
+
+/// Serves a password to `stool askpass` for the lifetime of the session.
+///
+/// Dropping the session stops the server and removes its directory.
+pub struct AskpassSession {
+    dir: PathBuf,
+    token: Zeroizing<String>,
+    stop: Arc<AtomicBool>,
+    server: Option<JoinHandle<()>>,
+}
+
+impl AskpassSession {
+    /// Starts serving `password` on a new private socket.
+    ///
+    /// The password is handed out once, for the password prompt of
+    /// `user@host`; every other request is refused.
+    ///
+    /// # Arguments
+    /// * `password` - Password to serve
+    /// * `user` - Login user of the destination
+    /// * `host` - Destination host as passed to ssh
+    ///
+    /// # Errors
+    /// Returns `AskpassFailed` if the directory, wrapper script or socket
+    /// cannot be created
+    pub fn start(password: &str, user: &str, host: &str) -> Result<Self> {
+        let exe = std::env::current_exe()
+            .map_err(|e| askpass_error("Cannot locate the stool executable").with_source(e))?;
+        let dir =
+            std::env::temp_dir().join(format!("stool-askpass-{}", hex::encode(&random::<8>()?)));
+        DirBuilder::new().mode(0o700).create(&dir).map_err(|e| {
+            askpass_error(format!("Failed to create {}", dir.display())).with_source(e)
+        })?;
+
+        // From here on, dropping the session cleans up the directory
+        let mut session = Self {
+            dir,
+            token: Zeroizing::new(hex::encode(&random::<TOKEN_LEN>()?)),
+            stop: Arc::new(AtomicBool::new(false)),
+            server: None,
+        };
+        session.write_script(&exe)?;
+
+        let socket = session.dir.join(SOCKET_FILE_NAME);
+        let listener = UnixListener::bind(&socket).map_err(|e| {
+            askpass_error(format!("Failed to listen on {}", socket.display())).with_source(e)
+        })?;
+        let token = session.token.clone();
+        let password = Zeroizing::new(password.to_string());
+        let destination = (user.to_string(), host.to_string());
+        let stop = Arc::clone(&session.stop);
+        debug!("Serving password to ssh from {}", session.dir.display());
+        session.server = Some(std::thread::spawn(move || {
+            let mut answered = false;
+            for stream in listener.incoming() {
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                // A misbehaving client must not take the session down
+                if let Ok(stream) = stream
+                    && serve(stream, &token, &destination, &password, answered).unwrap_or(false)
+                {
+                    answered = true;
+                }
+            }
+        }));
+        Ok(session)
+    }
+
+    /// Points `command` (`ssh` or `scp`) at this session's helper.
+    pub fn apply(&self, command: &mut Command) {
+        command
+            .env("SSH_ASKPASS", self.dir.join(SCRIPT_FILE_NAME))
+            .env("SSH_ASKPASS_REQUIRE", "force")
+            .env(SOCKET_ENV_VAR, self.dir.join(SOCKET_FILE_NAME))
+            .env(TOKEN_ENV_VAR, self.token.as_str());
+    }
+
+    /// Writes the script `ssh` executes; `$SSH_ASKPASS` cannot carry arguments.
+    fn write_script(&self, exe: &std::path::Path) -> Result<()> {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let path = self.dir.join(SCRIPT_FILE_NAME);
+        let script = format!(
+            "#!/bin/sh\nexec {} askpass \"$@\"\n",
+            crate::command::shell_quote(&exe.to_string_lossy())
+        );
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o700)
+            .open(&path)
+            .and_then(|mut file| file.write_all(script.as_bytes()))
+            .map_err(|e| {
+                askpass_error(format!("Failed to write {}", path.display())).with_source(e)
+            })
+    }
+}
+
+impl Drop for AskpassSession {
+    fn drop(&mut self) {
+        if let Some(server) = self.server.take() {
+            self.stop.store(true, Ordering::SeqCst);
+            // Wake the blocked accept so the server sees the stop flag
+            let _ = UnixStream::connect(self.dir.join(SOCKET_FILE_NAME));
+            let _ = server.join();
+        }
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Answers one client: a token line followed by ssh's prompt.
+///
+/// Returns `true` if the password was handed out, i.e. the token matches,
+/// the prompt asks for the password of `destination` (`user`, `host`) and
+/// the password was not `answered` before.
+fn serve(
+    mut stream: UnixStream,
+    token: &str,
+    destination: &(String, String),
+    password: &str,
+    answered: bool,
+) -> std::io::Result<bool> {
+    // A client that never sends its request must not block the session's end
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut line = Zeroizing::new(String::new());
+    (&mut reader)
+        .take(TOKEN_LEN as u64 * 2 + 1)
+        .read_line(&mut line)?;
+    let mut prompt = String::new();
+    reader.take(MAX_PROMPT_LEN).read_line(&mut prompt)?;
+
+    let (user, host) = destination;
+    if line.trim_end() != token {
+        debug!("Askpass client rejected: wrong token");
+        return Ok(false);
+    }
+    if !is_password_prompt_for(prompt.trim_end_matches('\n'), user, host) {
+        debug!(
+            "Askpass client rejected: prompt is not for {}@{}",
+            user, host
+        );
+        return Ok(false);
+    }
+    // Asked again, the password was wrong; the user is asked instead
+    if answered {
+        debug!("Askpass client rejected: password already handed out");
+        return Ok(false);
+    }
+
+    debug!("Password handed to the askpass helper");
+    stream.write_all(password.as_bytes())?;
+    Ok(true)
+}
+
+/// Whether `prompt` is ssh's password prompt for `user@host`.
+///
+/// ssh asks `user@host's password: ` (user and host cut to 30 and 128
+/// characters) for password authentication and `(user@host) Password: `
+/// for keyboard-interactive authentication.
+fn is_password_prompt_for(prompt: &str, user: &str, host: &str) -> bool {
+    let short_user: String = user.chars().take(PROMPT_USER_LEN).collect();
+    let short_host: String = host.chars().take(PROMPT_HOST_LEN).collect();
+    let password_auth = format!("{}@{}'s password:", short_user, short_host);
+    let interactive = format!("({}@{}) ", user, host);
+
+    prompt.starts_with(&password_auth)
+        || prompt
+            .strip_prefix(&interactive)
+            .is_some_and(|rest| rest.to_lowercase().starts_with("password"))
+}
+
+/// Answers an `ssh` askpass prompt; run as `stool askpass <prompt>`.
+///
+/// Password prompts are answered from the session started by the parent
+/// stool process if it accepts them. Prompts it refuses (e.g. a jump
+/// host's password) and any other prompt (key passphrases, verification
+/// codes) are asked on the terminal with echo off; only ssh's yes/no host
+/// key confirmation is echoed.
+///
+/// # Errors
+/// Returns `AskpassFailed` if not started by stool, the session is gone or
+/// there is no terminal to ask on
+pub fn respond(prompt: &str) -> Result<()> {
+    let answer = if is_host_key_confirmation(prompt) {
+        ask_terminal(prompt, true)?
+    } else if prompt.to_lowercase().contains("password") {
+        match fetch_password(prompt)? {
+            Some(password) => password,
+            None => ask_terminal(prompt, false)?,
+        }
+    } else {
+        ask_terminal(prompt, false)?
+    };
+    let mut stdout = std::io::stdout();
+    stdout
+        .write_all(answer.as_bytes())
+        .and_then(|()| stdout.write_all(b"\n"))
+        .and_then(|()| stdout.flush())
+        .map_err(|e| askpass_error("Failed to write the answer").with_source(e))
+}
+
+/// Asks the session of the parent stool process to answer `prompt`.
+///
+/// Returns `None` if the session refuses it.
+fn fetch_password(prompt: &str) -> Result<Option<Zeroizing<String>>> {
+    let (Ok(socket), Ok(token)) = (std::env::var(SOCKET_ENV_VAR), std::env::var(TOKEN_ENV_VAR))
+    else {
+        return Err(askpass_error("Not started by stool; nothing to answer"));
+    };
+    let answer = request(Path::new(&socket), &token, prompt)?;
+    if answer.is_none() {
+        debug!("Session refused the prompt; asking on the terminal");
+    }
+    Ok(answer)
+}
+
+/// Sends a token and prompt to a session socket and reads the answer.
+fn request(socket: &Path, token: &str, prompt: &str) -> Result<Option<Zeroizing<String>>> {
+    let mut stream = UnixStream::connect(socket).map_err(|e| {
+        askpass_error(format!("Failed to connect to {}", socket.display())).with_source(e)
+    })?;
+    // The session reads one line per field
+    let prompt = prompt.replace(['\r', '\n'], " ");
+    stream
+        .write_all(format!("{}\n{}\n", token, prompt).as_bytes())
+        .map_err(|e| askpass_error("Failed to send the request").with_source(e))?;
+
+    let mut password = Zeroizing::new(String::new());
+    stream
+        .read_to_string(&mut password)
+        .map_err(|e| askpass_error("Failed to read the password").with_source(e))?;
+    Ok(Some(password).filter(|p| !p.is_empty()))
+}
+
+/// Whether `prompt` is ssh's host key confirmation, the only prompt whose
+/// answer (`yes`, `no` or a fingerprint) is safe to echo.
+fn is_host_key_confirmation(prompt: &str) -> bool {
+    prompt.contains("(yes/no")
+}
+
+/// Shows a prompt on the controlling terminal and reads one line.
+///
+/// The terminal is opened directly, so stdin (e.g. a script piped to
+/// `stool ssh exec`) is never read. Without `echo`, echo is turned off
+/// with `stty` while reading; if that fails nothing is read.
+fn ask_terminal(prompt: &str, echo: bool) -> Result<Zeroizing<String>> {
+    let tty = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|e| askpass_error("No terminal to ask on").with_source(e))?;
+    let saved = if echo {
+        None
+    } else {
+        let saved = stty(&tty, &["-g"])
+            .and_then(|saved| stty(&tty, &["-echo"]).map(|_| saved))
+            .ok_or_else(|| askpass_error("Failed to turn off terminal echo"))?;
+        Some(saved)
+    };
+    (&tty)
+        .write_all(prompt.as_bytes())
+        .and_then(|()| (&tty).write_all(b" "))
+        .map_err(|e| askpass_error("Failed to show the prompt").with_source(e))?;
+
+    let mut answer = Zeroizing::new(String::new());
+    let read = BufReader::new(&tty).read_line(&mut answer);
+    if let Some(saved) = saved {
+        let _ = stty(&tty, &[saved.trim()]);
+        // The newline typed by the user was not echoed
+        let _ = (&tty).write_all(b"\n");
+    }
+    read.map_err(|e| askpass_error("Failed to read the answer").with_source(e))?;
+    let trimmed = answer.trim_end_matches(['\r', '\n']).len();
+    answer.truncate(trimmed);
+    Ok(answer)
+}
+
+/// Runs `stty` on `tty` and returns its output.
+fn stty(tty: &fs::File, args: &[&str]) -> Option<String> {
+    let stdin = tty.try_clone().ok()?;
+    let output = runner::output(
+        Command::new("stty").args(args).stdin(Stdio::from(stdin)),
+        None,
+    )
+    .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn askpass_error(message: impl Into<String>) -> StoolError {
+    StoolError::new(StoolErrorType::AskpassFailed).with_message(message)
+}
+
+fn random<const N: usize>() -> Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    getrandom::fill(&mut buf)
+        .map_err(|e| askpass_error(format!("Failed to generate random bytes: {}", e)))?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_host_key_confirmation_is_echoed() {
+        assert!(is_host_key_confirmation(
+            "Are you sure you want to continue connecting (yes/no/[fingerprint])?"
+        ));
+        assert!(is_host_key_confirmation(
+            "Are you sure you want to continue connecting (yes/no)?"
+        ));
+        assert!(!is_host_key_confirmation(
+            "Enter passphrase for key '/home/me/.ssh/id_ed25519': "
+        ));
+        assert!(!is_host_key_confirmation("Verification code: "));
+    }
+
+    #[test]
+    fn password_prompts_must_name_the_destination() {
+        assert!(is_password_prompt_for(
+            "deploy@10.0.0.1's password: ",
+            "deploy",
+            "10.0.0.1"
+        ));
+        assert!(is_password_prompt_for(
+            "(deploy@10.0.0.1) Password: ",
+            "deploy",
+            "10.0.0.1"
+        ));
+        assert!(!is_password_prompt_for(
+            "admin@10.0.0.254's password: ",
+            "deploy",
+            "10.0.0.1"
+        ));
+        assert!(!is_password_prompt_for(
+            "deploy@10.0.0.10's password: ",
+            "deploy",
+            "10.0.0.1"
+        ));
+        assert!(!is_password_prompt_for(
+            "(deploy@10.0.0.1) Verification code: ",
+            "deploy",
+            "10.0.0.1"
+        ));
+    }
+
+    #[test]
+    fn ssh_cuts_long_user_names_in_the_prompt() {
+        let user = "u".repeat(40);
+        let prompt = format!("{}@db's password: ", "u".repeat(30));
+
+        assert!(is_password_prompt_for(&prompt, &user, "db"));
+    }
+
+    #[test]
+    fn session_answers_its_own_password_prompt_once() {
+        let session = AskpassSession::start("s3cret", "deploy", "10.0.0.1").unwrap();
+        let socket = session.dir.join(SOCKET_FILE_NAME);
+        let prompt = "deploy@10.0.0.1's password: ";
+
+        let answer = request(&socket, &session.token, prompt).unwrap();
+
+        assert_eq!(answer.as_deref().map(String::as_str), Some("s3cret"));
+        // A repeated prompt means the password was wrong; ssh must not get it again
+        assert!(request(&socket, &session.token, prompt).unwrap().is_none());
+    }
+
+    #[test]
+    fn session_refuses_a_wrong_token() {
+        let session = AskpassSession::start("s3cret", "deploy", "10.0.0.1").unwrap();
+        let socket = session.dir.join(SOCKET_FILE_NAME);
+
+        let answer = request(&socket, "0000", "deploy@10.0.0.1's password: ").unwrap();
+
+        assert!(answer.is_none());
+    }
+
+    #[test]
+    fn session_refuses_other_prompts() {
+        let session = AskpassSession::start("s3cret", "deploy", "10.0.0.1").unwrap();
+        let socket = session.dir.join(SOCKET_FILE_NAME);
+
+        // A ProxyCommand ssh to the jump host inherits the helper
+        let jump = request(&socket, &session.token, "admin@bastion's password: ").unwrap();
+        let passphrase = request(
+            &socket,
+            &session.token,
+            "Enter passphrase for key '/home/me/.ssh/id_ed25519': ",
+        )
+        .unwrap();
+
+        assert!(jump.is_none());
+        assert!(passphrase.is_none());
+        // Refusals do not use up the session
+        let answer = request(&socket, &session.token, "deploy@10.0.0.1's password: ").unwrap();
+        assert!(answer.is_some());
+    }
+
+    #[test]
+    fn session_directory_is_removed_on_drop() {
+        let session = AskpassSession::start("s3cret", "deploy", "10.0.0.1").unwrap();
+        let dir = session.dir.clone();
+
+        drop(session);
+
+        assert!(!dir.exists());
+    }
+}
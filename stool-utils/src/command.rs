@@ -2,11 +2,69 @@
 //!
 //! Provides helpers for executing external commands:
 //! - SSH connection with multiple authentication methods
+//! - Remote command execution over SSH
+//! - Choice between the native client and `ssh`/`scp` ([`Transport`])
+//! - Password authentication through stool's own `SSH_ASKPASS` helper
+//! - SSH options for ports, jump hosts and agents
 //! - SCP file transfer with authentication
 //! - Generic command execution with status checking
+//! - Command execution that captures stderr for error reports
+//! - Per-call timeouts and retries with backoff ([`CommandPolicy`])
 
-use std::process::{Command, ExitStatus};
-use stool_core::error::{Result, StoolError, StoolErrorType};
+use crate::askpass::AskpassSession;
+use crate::interactive::ServerInfo;
+use crate::runner::{self, StderrTail};
+use log::{Level, info, log, warn};
+use std::io;
+use std::process::{Command, ExitStatus, Output};
+use std::time::Duration;
+use stool_core::config::Server;
+use stool_core::config::execution::CommandPolicy;
+use stool_core::error::{ProcessFailure, Result, StoolError, StoolErrorType};
+use stool_core::time::HumanDuration;
+
+pub use crate::runner::STDERR_TAIL_LINES;
+
+/// Exit code ssh uses for its own errors, e.g. a failed connection.
+const SSH_ERROR_CODE: i32 = 255;
+
+/// Shown when a password login through the askpass helper fails; older ssh
+/// ignores `SSH_ASKPASS_REQUIRE` and never asks the helper.
+const ASKPASS_HINT: &str =
+    "password login through stool needs OpenSSH 8.4 or newer; check `ssh -V`";
+
+/// How stool reaches a server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    /// In-process libssh2 client (`native-ssh` feature).
+    Native,
+    /// The `ssh` and `scp` binaries.
+    OpenSsh,
+}
+
+impl Transport {
+    /// Picks the transport for a connection.
+    ///
+    /// The native client is used only if it is built in, commands really
+    /// run (dry runs print the `ssh`/`scp` command instead), and the server
+    /// needs nothing only `ssh` supports (see [`ServerInfo::openssh_only`]).
+    pub fn select(native_built: bool, openssh_only: bool, executes: bool) -> Self {
+        if native_built && !openssh_only && executes {
+            Self::Native
+        } else {
+            Self::OpenSsh
+        }
+    }
+
+    /// Returns the transport used for `server` by this build and run.
+    pub fn for_server(server: &ServerInfo) -> Self {
+        Self::select(
+            cfg!(feature = "native-ssh"),
+            server.openssh_only,
+            runner::executes(),
+        )
+    }
+}
 
 /// Checks command exit status and returns error if failed.
 ///
@@ -17,215 +75,602 @@ use stool_core::error::{Result, StoolError, StoolErrorType};
 /// # Errors
 /// Returns specified error type if status indicates failure
 pub fn check_status(status: ExitStatus, error_type: StoolErrorType) -> Result<()> {
-    if !status.success() {
-        return Err(StoolError::new(error_type));
+    check_output(status, &[], error_type)
+}
+
+/// Checks a finished command's exit status, attaching its stderr on failure.
+///
+/// # Arguments
+/// * `status` - Exit status from command execution
+/// * `stderr` - Captured stderr; only the last [`STDERR_TAIL_LINES`] are kept
+/// * `error_type` - Error type to return on failure
+///
+/// # Errors
+/// Returns specified error type with exit code and stderr if status indicates failure
+pub fn check_output(status: ExitStatus, stderr: &[u8], error_type: StoolErrorType) -> Result<()> {
+    if status.success() {
+        return Ok(());
     }
-    Ok(())
+    let mut tail = StderrTail::default();
+    tail.push(stderr);
+    Err(StoolError::new(error_type).with_process(ProcessFailure {
+        code: status.code(),
+        stderr: tail.into_lines(),
+        ..ProcessFailure::default()
+    }))
 }
 
 /// Executes a command with arguments and verifies success.
 ///
-/// Runs command with inherited stdio for real-time output.
+/// Runs command with inherited stdout for real-time output; stderr is
+/// shown as well and its last lines are attached to the error on failure.
 ///
 /// # Arguments
 /// * `program` - Command name or path to execute
 /// * `args` - Command-line arguments
 /// * `error_type` - Error type to return on failure
+/// * `policy` - Timeout and retries for the command
+///
+/// # Errors
+/// Returns specified error type if command fails to execute, exits with
+/// error or times out on every attempt
+pub fn execute_command(
+    program: &str,
+    args: &[&str],
+    error_type: StoolErrorType,
+    policy: &CommandPolicy,
+) -> Result<()> {
+    execute_captured(Command::new(program).args(args), error_type, policy)
+}
+
+/// Executes a command, teeing its stderr to the terminal.
+///
+/// The last [`STDERR_TAIL_LINES`] lines of stderr and the exit code are
+/// attached to the returned error if the command fails. Failed or timed
+/// out attempts are retried as `policy` allows.
+///
+/// # Arguments
+/// * `command` - Command to run; its stderr is replaced with a pipe
+/// * `error_type` - Error type to return on failure
+/// * `policy` - Timeout and retries for the command
 ///
 /// # Errors
-/// Returns specified error type if command fails to execute or exits with error
-pub fn execute_command(program: &str, args: &[&str], error_type: StoolErrorType) -> Result<()> {
-    let status = Command::new(program)
-        .args(args)
-        .status()
-        .map_err(|e| StoolError::new(error_type).with_source(e))?;
+/// Returns specified error type if command fails to execute, exits with
+/// error or times out on every attempt
+pub fn execute_captured(
+    command: &mut Command,
+    error_type: StoolErrorType,
+    policy: &CommandPolicy,
+) -> Result<()> {
+    execute_with_input(command, None, error_type, policy)
+}
 
-    check_status(status, error_type)
+/// Executes a command like [`execute_captured`], writing `input` to its stdin.
+///
+/// Used to hand secrets to commands without putting them on the command line.
+///
+/// # Errors
+/// Returns specified error type if command fails to execute, exits with
+/// error or times out on every attempt
+pub fn execute_with_input(
+    command: &mut Command,
+    input: Option<&[u8]>,
+    error_type: StoolErrorType,
+    policy: &CommandPolicy,
+) -> Result<()> {
+    with_retries(command, policy, |command| {
+        let output = runner::tee(command, input, policy.timeout)
+            .map_err(|e| run_error(e, error_type, policy.timeout))?;
+        check_output(output.status, &output.stderr, error_type)
+    })
+}
+
+/// Runs a command and returns its output, whatever its exit status.
+///
+/// For queries whose failures the caller interprets itself. Only timeouts
+/// are retried.
+///
+/// # Arguments
+/// * `command` - Command to run
+/// * `error_type` - Error type to return if the command cannot run
+/// * `policy` - Timeout and retries for the command
+///
+/// # Errors
+/// Returns specified error type if command fails to execute or times out
+/// on every attempt
+pub fn query(
+    command: &mut Command,
+    error_type: StoolErrorType,
+    policy: &CommandPolicy,
+) -> Result<Output> {
+    with_retries(command, policy, |command| {
+        runner::output(command, policy.timeout)
+            .map_err(|e| run_error(e, error_type, policy.timeout))
+    })
+}
+
+/// Runs `attempt` until it succeeds or `policy.retries` retries are used up.
+///
+/// Only failures of the command itself (exit status or timeout) are
+/// retried; a command that cannot be started fails immediately. Retries
+/// are reported on stderr and the final error records the attempts made.
+fn with_retries<T>(
+    command: &mut Command,
+    policy: &CommandPolicy,
+    mut attempt: impl FnMut(&mut Command) -> Result<T>,
+) -> Result<T> {
+    let attempts = policy.retries.saturating_add(1);
+    let mut made = 1;
+    loop {
+        let mut error = match attempt(command) {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+        let Some(process) = error.process.as_mut() else {
+            return Err(error);
+        };
+        if made >= attempts {
+            process.attempts = made;
+            return Err(error);
+        }
+
+        let delay = policy.delay_before(made + 1);
+        warn!(
+            "{} {} (attempt {} of {}); retrying in {}",
+            runner::describe(command),
+            process,
+            made,
+            attempts,
+            HumanDuration(delay)
+        );
+        std::thread::sleep(delay);
+        made += 1;
+    }
+}
+
+/// Converts a runner error, recognising timeouts.
+fn run_error(e: io::Error, error_type: StoolErrorType, timeout: Option<Duration>) -> StoolError {
+    match timeout {
+        Some(limit) if e.kind() == io::ErrorKind::TimedOut => StoolError::new(error_type)
+            .with_process(ProcessFailure {
+                timed_out: Some(limit),
+                ..ProcessFailure::default()
+            }),
+        _ => StoolError::new(error_type).with_source(e),
+    }
+}
+
+/// Builds `ssh`/`scp` options for a server's connection settings.
+///
+/// Emits `-o` options for `port`, `auth.identity_agent`, `forward_agent`,
+/// `proxy_jump` and `ssh_options`, in that order (ssh uses the first value
+/// given for an option). A `proxy_jump` naming another server becomes a
+/// `ProxyCommand` so that server's own key, port and options apply to the
+/// jump as well; any other value is passed as `ProxyJump`.
+///
+/// # Arguments
+/// * `server` - Server to connect to
+/// * `servers` - All configured servers, for resolving jump hosts by name
+///
+/// # Errors
+/// Returns `ConfigParseError` if jump hosts reference each other in a cycle
+pub fn ssh_option_args(server: &Server, servers: &[Server]) -> Result<Vec<String>> {
+    option_args(server, servers, &mut vec![server.name.clone()])
+}
+
+fn option_args(
+    server: &Server,
+    servers: &[Server],
+    chain: &mut Vec<String>,
+) -> Result<Vec<String>> {
+    let mut options: Vec<(&str, String)> = Vec::new();
+    if let Some(port) = server.port {
+        options.push(("Port", port.to_string()));
+    }
+    if let Some(agent) = &server.auth.identity_agent {
+        options.push(("IdentityAgent", agent.clone()));
+    }
+    if server.forward_agent {
+        options.push(("ForwardAgent", "yes".to_string()));
+    }
+    if let Some(jump) = &server.proxy_jump {
+        match server.jump_server(servers) {
+            Some(jump_server) => {
+                if chain.contains(&jump_server.name) {
+                    return Err(
+                        StoolError::new(StoolErrorType::ConfigParseError).with_message(format!(
+                            "Jump host cycle: {} -> {}",
+                            chain.join(" -> "),
+                            jump_server.name
+                        )),
+                    );
+                }
+                chain.push(jump_server.name.clone());
+                options.push(("ProxyCommand", proxy_command(jump_server, servers, chain)?));
+            }
+            None => options.push(("ProxyJump", jump.clone())),
+        }
+    }
+    for (key, value) in &server.ssh_options {
+        options.push((key, value.clone()));
+    }
+
+    Ok(options
+        .into_iter()
+        .flat_map(|(key, value)| ["-o".to_string(), format!("{}={}", key, value)])
+        .collect())
+}
+
+/// Builds `ssh ... -W %h:%p jump` for connecting through a configured server.
+fn proxy_command(jump: &Server, servers: &[Server], chain: &mut Vec<String>) -> Result<String> {
+    let mut args = vec!["ssh".to_string()];
+    if let Some(key) = &jump.auth.key_path {
+        args.push("-i".to_string());
+        args.push(key.clone());
+    }
+    args.extend(option_args(jump, servers, chain)?);
+    args.push(format!("{}@{}", jump.user, jump.ip));
+
+    // ssh expands %-tokens in ProxyCommand, so literal % must be doubled
+    let mut command: Vec<String> = args
+        .iter()
+        .map(|arg| shell_quote(arg).replace('%', "%%"))
+        .collect();
+    command.insert(command.len() - 1, "-W %h:%p".to_string());
+    Ok(command.join(" "))
 }
 
 /// Executes SSH connection with appropriate authentication.
 ///
 /// Authentication priority:
 /// 1. PEM key (`key_path`) - Uses `ssh -i`
-/// 2. Password (`password`) - Uses expect script
+/// 2. Password (`password`) - Served to ssh by the askpass helper
 /// 3. Default - Standard SSH connection
 ///
+/// Connection options (`ssh_args`) are passed in every case. With the
+/// [`Transport::Native`] transport the shell runs on a pseudo-terminal of
+/// the in-process client instead, authenticating in the same order.
+///
 /// # Arguments
-/// * `user` - Remote username
-/// * `ip` - Remote server IP address
-/// * `key_path` - Optional path to PEM key file
-/// * `password` - Optional password for authentication
+/// * `server` - Connection details of the selected server
 ///
 /// # Errors
 /// Returns error if SSH connection fails or authentication is rejected
-pub fn execute_ssh(
-    user: &str,
-    ip: &str,
-    key_path: Option<&str>,
-    password: Option<&str>,
-) -> Result<()> {
-    if let Some(key) = key_path {
-        println!("Connecting with PEM key authentication");
-        let status = Command::new("ssh")
-            .arg("-i")
-            .arg(key)
-            .arg(format!("{}@{}", user, ip))
-            .status()
-            .map_err(|e| {
-                StoolError::new(StoolErrorType::SshConnectionFailed)
-                    .with_message(format!("Failed to execute ssh command to {}@{}", user, ip))
-                    .with_source(e)
-            })?;
-
-        check_status(status, StoolErrorType::SshConnectionFailed)?;
-    } else if let Some(pass) = password {
-        println!("Connecting with password authentication");
-        execute_expect_ssh(user, ip, pass)?;
-    } else {
-        println!("Connecting with default SSH authentication");
-        let status = Command::new("ssh")
-            .arg(format!("{}@{}", user, ip))
-            .status()
-            .map_err(|e| {
-                StoolError::new(StoolErrorType::SshConnectionFailed)
-                    .with_message(format!("Failed to execute ssh command to {}@{}", user, ip))
-                    .with_source(e)
-            })?;
+pub fn execute_ssh(server: &ServerInfo) -> Result<()> {
+    #[cfg(feature = "native-ssh")]
+    if Transport::for_server(server) == Transport::Native {
+        info!(
+            "Connecting with {} authentication (native)",
+            auth_method(server)
+        );
+        let code = crate::native::Connection::open(server)?.shell()?;
+        if code == 0 {
+            return Ok(());
+        }
+        return Err(
+            StoolError::new(StoolErrorType::SshConnectionFailed).with_process(ProcessFailure {
+                code: Some(code),
+                ..ProcessFailure::default()
+            }),
+        );
+    }
+    let status = run_ssh(server, &[], None, Level::Info)?;
+    check_status(status, StoolErrorType::SshConnectionFailed)
+        .map_err(|e| with_askpass_hint(e, server))
+}
+
+/// Runs one command on a server over SSH.
+///
+/// Authenticates and picks the transport like [`execute_ssh`]. The command
+/// shares stool's stdin, stdout and stderr, so its output is not mixed with
+/// stool's progress messages (those are logged at debug level) and a script
+/// can be piped to `sh -s`.
+///
+/// # Arguments
+/// * `server` - Connection details of the selected server
+/// * `remote_command` - Command line run by the remote user's shell
+/// * `tty` - Force a pseudo-terminal (`ssh -t`), e.g. for `sudo` prompts
+///
+/// # Errors
+/// Returns `RemoteCommandFailed` carrying the remote exit code if the
+/// command fails, or `SshConnectionFailed` if the connection fails (ssh
+/// exit code 255)
+pub fn execute_remote(server: &ServerInfo, remote_command: &str, tty: bool) -> Result<()> {
+    let transport = Transport::for_server(server);
+    let code = match transport {
+        #[cfg(feature = "native-ssh")]
+        Transport::Native => {
+            log!(
+                Level::Debug,
+                "Connecting with {} authentication (native)",
+                auth_method(server)
+            );
+            let connection = crate::native::Connection::open(server)?;
+            Some(connection.exec_attached(remote_command, tty)?)
+        }
+        _ => {
+            let tty_args: &[&str] = if tty { &["-t"] } else { &[] };
+            run_ssh(server, tty_args, Some(remote_command), Level::Debug)?.code()
+        }
+    };
+    match code {
+        Some(0) => Ok(()),
+        // Only the ssh binary reports its own failures as 255
+        Some(SSH_ERROR_CODE) if transport == Transport::OpenSsh => Err(with_askpass_hint(
+            StoolError::new(StoolErrorType::SshConnectionFailed)
+                .with_message(format!("{}@{}", server.user, server.ip))
+                .with_process(ProcessFailure {
+                    code,
+                    ..ProcessFailure::default()
+                }),
+            server,
+        )),
+        code => Err(StoolError::new(StoolErrorType::RemoteCommandFailed)
+            .with_message(format!(
+                "{} on {}@{}",
+                remote_command, server.user, server.ip
+            ))
+            .with_process(ProcessFailure {
+                code,
+                ..ProcessFailure::default()
+            })),
+    }
+}
+
+/// Runs `ssh [options] destination [command]` with the server's
+/// authentication, reporting the method at `level`.
+fn run_ssh(
+    server: &ServerInfo,
+    options: &[&str],
+    remote_command: Option<&str>,
+    level: Level,
+) -> Result<ExitStatus> {
+    let destination = format!("{}@{}", server.user, server.ip);
+    let mut args = server.ssh_args.clone();
+    let mut ssh = Command::new("ssh");
 
-        check_status(status, StoolErrorType::SshConnectionFailed)?;
+    log!(
+        level,
+        "Connecting with {} authentication",
+        auth_method(server)
+    );
+    // Kept alive until ssh exits so the helper can reach it
+    let _askpass;
+    if let Some(key) = &server.key_path {
+        args.splice(0..0, ["-i".to_string(), key.clone()]);
+    } else if let Some(pass) = &server.password {
+        _askpass = password_session(&mut ssh, pass, server)?;
+    }
+    args.extend(options.iter().map(|o| o.to_string()));
+    args.push(destination.clone());
+    if let Some(remote_command) = remote_command {
+        // Keeps a command starting with `-` from being read as an ssh option
+        args.push("--".to_string());
+        args.push(remote_command.to_string());
     }
 
-    Ok(())
+    runner::status(ssh.args(&args)).map_err(|e| {
+        StoolError::new(StoolErrorType::SshConnectionFailed)
+            .with_message(format!("Failed to execute ssh command to {}", destination))
+            .with_source(e)
+    })
+}
+
+/// Names the authentication method used for `server` in progress messages.
+fn auth_method(server: &ServerInfo) -> &'static str {
+    if server.key_path.is_some() {
+        "PEM key"
+    } else if server.password.is_some() {
+        "password"
+    } else {
+        "default SSH"
+    }
+}
+
+/// Adds [`ASKPASS_HINT`] to an ssh or scp failure if the password was
+/// served by the askpass helper.
+fn with_askpass_hint(error: StoolError, server: &ServerInfo) -> StoolError {
+    if server.key_path.is_none() && server.password.is_some() {
+        error.with_hint(ASKPASS_HINT)
+    } else {
+        error
+    }
 }
 
 /// Executes SCP file transfer with appropriate authentication.
 ///
 /// Authentication priority:
 /// 1. PEM key (`key_path`) - Uses `scp -i`
-/// 2. Password (`password`) - Uses expect script
+/// 2. Password (`password`) - Served to scp by the askpass helper
 /// 3. Default - Standard SCP connection
 ///
+/// Connection options (`ssh_args`) are passed in every case.
+///
 /// # Arguments
 /// * `source` - Source file path (local or remote format: `user@ip:path`)
 /// * `destination` - Destination path (local or remote format: `user@ip:path`)
-/// * `key_path` - Optional path to PEM key file
-/// * `password` - Optional password for authentication
+/// * `server` - Connection details of the remote server
 ///
 /// # Errors
 /// Returns error if file transfer fails or authentication is rejected
-pub fn execute_scp(
-    source: &str,
-    destination: &str,
-    key_path: Option<&str>,
-    password: Option<&str>,
-) -> Result<()> {
-    if let Some(key) = key_path {
-        println!("Transferring with PEM key authentication");
-        let status = Command::new("scp")
-            .arg("-i")
-            .arg(key)
-            .arg(source)
-            .arg(destination)
-            .status()
-            .map_err(|e| {
-                StoolError::new(StoolErrorType::FileTransferFailed)
-                    .with_message(format!(
-                        "Failed to execute scp from {} to {}",
-                        source, destination
-                    ))
-                    .with_source(e)
-            })?;
-
-        check_status(status, StoolErrorType::FileTransferFailed)?;
-    } else if let Some(pass) = password {
-        println!("Transferring with password authentication");
-        execute_expect_scp(source, destination, pass)?;
+pub fn execute_scp(source: &str, destination: &str, server: &ServerInfo) -> Result<()> {
+    let mut args = server.ssh_args.clone();
+    let mut scp = Command::new("scp");
+
+    // Kept alive until scp exits so the helper can reach it
+    let _askpass;
+    if let Some(key) = &server.key_path {
+        info!("Transferring with PEM key authentication");
+        args.splice(0..0, ["-i".to_string(), key.clone()]);
+    } else if let Some(pass) = &server.password {
+        info!("Transferring with password authentication");
+        _askpass = password_session(&mut scp, pass, server)?;
     } else {
-        println!("Transferring with default SSH authentication");
-        let status = Command::new("scp")
-            .arg(source)
-            .arg(destination)
-            .status()
-            .map_err(|e| {
-                StoolError::new(StoolErrorType::FileTransferFailed)
-                    .with_message(format!(
-                        "Failed to execute scp from {} to {}",
-                        source, destination
-                    ))
-                    .with_source(e)
-            })?;
-
-        check_status(status, StoolErrorType::FileTransferFailed)?;
-    }
-
-    println!("Transfer completed successfully");
+        info!("Transferring with default SSH authentication");
+    }
+    args.push(source.to_string());
+    args.push(destination.to_string());
+
+    let status = runner::status(scp.args(&args)).map_err(|e| {
+        StoolError::new(StoolErrorType::FileTransferFailed)
+            .with_message(format!(
+                "Failed to execute scp from {} to {}",
+                source, destination
+            ))
+            .with_source(e)
+    })?;
+    check_status(status, StoolErrorType::FileTransferFailed)
+        .map_err(|e| with_askpass_hint(e, server))?;
+
+    info!("Transfer completed successfully");
     Ok(())
 }
 
-fn execute_expect_ssh(user: &str, ip: &str, password: &str) -> Result<()> {
-    let status = Command::new("expect")
-        .arg("-c")
-        .arg(format!(
-            r#"
-            spawn ssh {user}@{ip}
-            expect {{
-                "yes/no" {{
-                    send "yes\r"
-                    exp_continue
-                }}
-                "password:" {{
-                    send "{pass}\r"
-                }}
-            }}
-            interact
-            "#,
-            user = user,
-            ip = ip,
-            pass = password
-        ))
-        .status()
-        .map_err(|e| {
-            StoolError::new(StoolErrorType::ExpectCommandFailed)
-                .with_message(format!(
-                    "Failed to execute expect for ssh to {}@{}",
-                    user, ip
-                ))
-                .with_source(e)
-        })?;
+/// Uploads a local file to a server.
+///
+/// Uses the native SFTP client when [`Transport::for_server`] picks it,
+/// otherwise `scp`.
+///
+/// # Arguments
+/// * `local` - Local file path
+/// * `remote` - Remote path; a directory (e.g. `~/`) keeps the file name
+/// * `server` - Connection details of the remote server
+///
+/// # Errors
+/// Returns error if file transfer fails or authentication is rejected
+pub fn upload(local: &str, remote: &str, server: &ServerInfo) -> Result<()> {
+    #[cfg(feature = "native-ssh")]
+    if Transport::for_server(server) == Transport::Native {
+        let connection = crate::native::Connection::open(server)?;
+        let written = connection.upload(
+            &stool_core::paths::expand_tilde(local),
+            remote,
+            &mut show_progress,
+        )?;
+        info!("Uploaded to {}", written.display());
+        return Ok(());
+    }
+    execute_scp(
+        local,
+        &format!("{}@{}:{}", server.user, server.ip, remote),
+        server,
+    )
+}
+
+/// Downloads a file from a server.
+///
+/// Uses the native SFTP client when available, like [`upload`].
+///
+/// # Arguments
+/// * `remote` - Remote file path
+/// * `local` - Local path; a directory keeps the remote file name
+/// * `server` - Connection details of the remote server
+///
+/// # Errors
+/// Returns error if file transfer fails or authentication is rejected
+pub fn download(remote: &str, local: &str, server: &ServerInfo) -> Result<()> {
+    #[cfg(feature = "native-ssh")]
+    if Transport::for_server(server) == Transport::Native {
+        let connection = crate::native::Connection::open(server)?;
+        let written = connection.download(
+            remote,
+            &stool_core::paths::expand_tilde(local),
+            &mut show_progress,
+        )?;
+        info!("Downloaded to {}", written.display());
+        return Ok(());
+    }
+    execute_scp(
+        &format!("{}@{}:{}", server.user, server.ip, remote),
+        local,
+        server,
+    )
+}
+
+/// Prints transfer progress on one line of stderr.
+#[cfg(feature = "native-ssh")]
+fn show_progress(done: u64, total: u64) {
+    use std::io::Write;
 
-    check_status(status, StoolErrorType::SshConnectionFailed)
+    if !log::log_enabled!(log::Level::Info) {
+        return;
+    }
+    let percent = (done * 100).checked_div(total).unwrap_or(100);
+    eprint!("\r{:>3}% ({} / {} bytes)", percent, done, total);
+    if done >= total {
+        eprintln!();
+    }
+    let _ = std::io::stderr().flush();
 }
 
-fn execute_expect_scp(source: &str, destination: &str, password: &str) -> Result<()> {
-    let status = Command::new("expect")
-        .arg("-c")
-        .arg(format!(
-            r#"
-            spawn scp {source} {destination}
-            expect {{
-                "yes/no" {{
-                    send "yes\r"
-                    exp_continue
-                }}
-                "password:" {{
-                    send "{pass}\r"
-                }}
-            }}
-            expect eof
-            "#,
-            source = source,
-            destination = destination,
-            pass = password
-        ))
-        .status()
-        .map_err(|e| {
-            StoolError::new(StoolErrorType::FileTransferFailed)
-                .with_message(format!(
-                    "Failed to execute expect for scp from {} to {}",
-                    source, destination
-                ))
-                .with_source(e)
-        })?;
+/// Starts an askpass session serving `server`'s password and points
+/// `command` at it.
+fn password_session(
+    command: &mut Command,
+    password: &str,
+    server: &ServerInfo,
+) -> Result<AskpassSession> {
+    let session = AskpassSession::start(password, &server.user, &server.ip)?;
+    session.apply(command);
+    Ok(session)
+}
 
-    check_status(status, StoolErrorType::FileTransferFailed)
+/// Joins arguments into a single `sh`-quoted command line.
+pub fn shell_join(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| shell_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Quotes an argument for `sh` if it contains anything but safe characters.
+pub(crate) fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./@:=,%+~".contains(c))
+    {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn native_transport_is_used_for_plain_servers_when_built_in() {
+        assert_eq!(Transport::select(true, false, true), Transport::Native);
+    }
+
+    #[test]
+    fn openssh_only_servers_use_openssh() {
+        assert_eq!(Transport::select(true, true, true), Transport::OpenSsh);
+    }
+
+    #[test]
+    fn dry_runs_select_openssh() {
+        assert_eq!(Transport::select(true, false, false), Transport::OpenSsh);
+    }
+
+    #[test]
+    fn builds_without_the_native_client_use_openssh() {
+        assert_eq!(Transport::select(false, false, true), Transport::OpenSsh);
+    }
+
+    #[test]
+    fn dry_runs_use_openssh() {
+        let server = ServerInfo {
+            user: "deploy".to_string(),
+            ip: "10.0.0.1".to_string(),
+            ..ServerInfo::default()
+        };
+        let recorder = std::rc::Rc::new(runner::RecordingRunner::new());
+
+        let transport = runner::with_runner(recorder, || Transport::for_server(&server));
+
+        assert_eq!(transport, Transport::OpenSsh);
+    }
 }
@@ -6,7 +6,9 @@
 //! - List selection dialogs
 //! - File path input with tab completion
 
-use dialoguer::{Input, Password, Select, theme::ColorfulTheme};
+use crate::{command, runner};
+use dialoguer::{Confirm, Input, Password, Select, theme::ColorfulTheme};
+use log::info;
 use rustyline::completion::{Completer, FilenameCompleter, Pair};
 use rustyline::error::ReadlineError;
 use rustyline::highlight::Highlighter;
@@ -14,8 +16,10 @@ use rustyline::hint::Hinter;
 use rustyline::validate::Validator;
 use rustyline::{Config, Editor};
 use rustyline::{Context, Helper};
-use stool_core::config::Server;
+use stool_core::config::{Server, ServerFilter};
 use stool_core::error::{Result, StoolError, StoolErrorType};
+use stool_core::{secret, vault};
+use zeroize::Zeroizing;
 
 /// Menu option for manual server input.
 pub const MENU_MANUAL_INPUT: &str = "Manual input";
@@ -23,8 +27,43 @@ pub const MENU_MANUAL_INPUT: &str = "Manual input";
 /// Menu option for canceling operation.
 pub const MENU_CANCEL: &str = "Cancel";
 
-/// Server information tuple: (user, ip, key_path, password).
-pub type ServerInfo = (String, String, Option<String>, Option<String>);
+/// Value of a `cmd:` secret when commands do not really run (`--dry-run`).
+const DRY_RUN_SECRET: &str = "[dry-run]";
+
+/// Connection details of a selected server.
+///
+/// `Debug` output redacts the password.
+#[derive(Clone, Default)]
+pub struct ServerInfo {
+    /// Config name of the server; `None` if entered manually.
+    pub name: Option<String>,
+    pub user: String,
+    pub ip: String,
+    pub port: Option<u16>,
+    pub key_path: Option<String>,
+    /// Resolved password, wiped from memory when dropped.
+    pub password: Option<Zeroizing<String>>,
+    /// Connection options for `ssh`/`scp`, see [`command::ssh_option_args`].
+    pub ssh_args: Vec<String>,
+    /// Set if the server uses a jump host, an identity agent or extra ssh
+    /// options, which only the `ssh` binary can honour.
+    pub openssh_only: bool,
+}
+
+impl std::fmt::Debug for ServerInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServerInfo")
+            .field("name", &self.name)
+            .field("user", &self.user)
+            .field("ip", &self.ip)
+            .field("port", &self.port)
+            .field("key_path", &self.key_path)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("ssh_args", &self.ssh_args)
+            .field("openssh_only", &self.openssh_only)
+            .finish()
+    }
+}
 
 /// Helper for rustyline with file path completion support.
 struct PathHelper(FilenameCompleter);
@@ -64,12 +103,61 @@ impl Validator for PathHelper {}
 /// # Errors
 /// Returns error if user interaction fails
 pub fn select_from_list(prompt: &str, items: &[String]) -> Result<usize> {
+    select_from_list_with_default(prompt, items, 0)
+}
+
+/// Displays an interactive selection menu with the cursor on `default`.
+///
+/// # Arguments
+/// * `prompt` - Message displayed above the menu
+/// * `items` - List of options to choose from
+/// * `default` - Index initially selected (clamped to the list)
+///
+/// # Returns
+/// Index of the selected item
+///
+/// # Errors
+/// Returns error if user interaction fails
+pub fn select_from_list_with_default(
+    prompt: &str,
+    items: &[String],
+    default: usize,
+) -> Result<usize> {
     Select::with_theme(&ColorfulTheme::default())
         .with_prompt(prompt)
         .items(items)
-        .default(0)
+        .default(default.min(items.len().saturating_sub(1)))
+        .interact()
+        .map_err(prompt_error)
+}
+
+/// Asks a yes/no question.
+///
+/// # Arguments
+/// * `prompt` - Question displayed to the user
+/// * `default` - Answer used when the user just presses Enter
+///
+/// # Returns
+/// `true` if the user answered yes
+///
+/// # Errors
+/// Returns error if user interaction fails
+pub fn confirm(prompt: &str, default: bool) -> Result<bool> {
+    Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(default)
         .interact()
-        .map_err(|e| StoolError::new(StoolErrorType::InvalidInput).with_source(e))
+        .map_err(prompt_error)
+}
+
+/// Converts a prompt failure into an error, treating an interrupt as cancellation.
+fn prompt_error(err: dialoguer::Error) -> StoolError {
+    match &err {
+        dialoguer::Error::IO(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+            StoolError::new(StoolErrorType::Cancelled)
+        }
+        _ => StoolError::new(StoolErrorType::InvalidInput).with_source(err),
+    }
 }
 
 /// Prompts user for text input.
@@ -86,7 +174,7 @@ pub fn input_text(prompt: &str) -> Result<String> {
     Input::with_theme(&ColorfulTheme::default())
         .with_prompt(prompt)
         .interact_text()
-        .map_err(|e| StoolError::new(StoolErrorType::InvalidInput).with_source(e))
+        .map_err(prompt_error)
 }
 
 /// Prompts user for text input with empty value allowed.
@@ -104,7 +192,27 @@ pub fn input_text_optional(prompt: &str) -> Result<String> {
         .with_prompt(prompt)
         .allow_empty(true)
         .interact_text()
-        .map_err(|e| StoolError::new(StoolErrorType::InvalidInput).with_source(e))
+        .map_err(prompt_error)
+}
+
+/// Prompts user for text input pre-filled with an editable value.
+///
+/// # Arguments
+/// * `prompt` - Message displayed before input field
+/// * `initial` - Current value placed in the input field
+///
+/// # Returns
+/// User-entered text string (may be empty)
+///
+/// # Errors
+/// Returns error if user interaction fails
+pub fn input_text_initial(prompt: &str, initial: &str) -> Result<String> {
+    Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .with_initial_text(initial)
+        .allow_empty(true)
+        .interact_text()
+        .map_err(prompt_error)
 }
 
 /// Prompts user for password input with masked display.
@@ -113,16 +221,47 @@ pub fn input_text_optional(prompt: &str) -> Result<String> {
 /// * `prompt` - Message displayed before input field
 ///
 /// # Returns
-/// User-entered password string (may be empty)
+/// User-entered password (may be empty)
 ///
 /// # Errors
 /// Returns error if user interaction fails
-pub fn input_password(prompt: &str) -> Result<String> {
+pub fn input_password(prompt: &str) -> Result<Zeroizing<String>> {
     Password::with_theme(&ColorfulTheme::default())
         .with_prompt(prompt)
         .allow_empty_password(true)
         .interact()
-        .map_err(|e| StoolError::new(StoolErrorType::InvalidInput).with_source(e))
+        .map(Zeroizing::new)
+        .map_err(prompt_error)
+}
+
+/// Prompts user for a new password, asking twice for confirmation.
+///
+/// # Arguments
+/// * `prompt` - Message displayed before input field
+///
+/// # Returns
+/// User-entered password (must not be empty)
+///
+/// # Errors
+/// Returns error if user interaction fails
+pub fn input_new_password(prompt: &str) -> Result<Zeroizing<String>> {
+    Password::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .with_confirmation("Confirm:", "Values do not match")
+        .interact()
+        .map(Zeroizing::new)
+        .map_err(prompt_error)
+}
+
+/// Returns the vault passphrase from `$STOOL_VAULT_PASSPHRASE` or a prompt.
+///
+/// # Errors
+/// Returns error if user interaction fails
+pub fn vault_passphrase() -> Result<Zeroizing<String>> {
+    if let Ok(passphrase) = vault::passphrase_from_env() {
+        return Ok(passphrase);
+    }
+    input_password("Vault passphrase:")
 }
 
 /// Prompts user for file path input with tab completion.
@@ -164,55 +303,229 @@ pub fn input_path(prompt: &str) -> Result<String> {
 /// - Enter server details manually
 /// - Cancel the operation
 ///
+/// Servers matching `filter` are listed by group (ungrouped last), labeled
+/// with their environment and tags. The cursor starts on the first server
+/// of `default_group`, if given.
+///
 /// # Arguments
 /// * `servers` - List of available servers from configuration
+/// * `filter` - Criteria narrowing the listed servers
+/// * `default_group` - Group to pre-select, e.g. from the active context
 ///
 /// # Returns
 /// - `Some(ServerInfo)` if server selected or manual input provided
 /// - `None` if user cancelled
 ///
 /// # Errors
+/// Returns `ServerNotFound` if no server matches a non-empty filter, or
+/// error if user interaction fails
+pub fn select_server(
+    servers: &[Server],
+    filter: &ServerFilter,
+    default_group: Option<&str>,
+) -> Result<Option<ServerInfo>> {
+    let candidates = filter.apply(servers);
+    if candidates.is_empty() && !filter.is_empty() {
+        return Err(StoolError::new(StoolErrorType::ServerNotFound)
+            .with_message(format!("No servers match {}", filter)));
+    }
+    select_server_from(candidates, servers, default_group)
+}
+
+/// Shows a server selection menu limited to the given candidates.
+///
+/// Behaves like [`select_server`] otherwise, including the manual input
+/// entry.
+///
+/// # Arguments
+/// * `candidates` - Servers listed in the menu
+/// * `servers` - All configured servers, for resolving jump hosts by name
+/// * `default_group` - Group to pre-select, e.g. from the active context
+///
+/// # Errors
 /// Returns error if user interaction fails
-pub fn select_server(servers: &[Server]) -> Result<Option<ServerInfo>> {
-    let mut items: Vec<String> = servers
+pub fn select_server_from(
+    mut candidates: Vec<&Server>,
+    servers: &[Server],
+    default_group: Option<&str>,
+) -> Result<Option<ServerInfo>> {
+    // Stable sort keeps config order within each group
+    candidates.sort_by_key(|s| (s.group.is_none(), s.group.as_deref().map(str::to_lowercase)));
+
+    let mut items: Vec<String> = candidates
         .iter()
         .enumerate()
-        .map(|(i, s)| format!("{}. {} ({}@{})", i + 1, s.name, s.user, s.ip))
+        .map(|(i, s)| format!("{}. {}", i + 1, server_label(s)))
         .collect();
     items.push(MENU_MANUAL_INPUT.to_string());
     items.push(MENU_CANCEL.to_string());
 
-    let selection = select_from_list("Select server:", &items)?;
+    let default = default_group
+        .and_then(|group| {
+            candidates.iter().position(|s| {
+                s.group
+                    .as_deref()
+                    .is_some_and(|g| g.eq_ignore_ascii_case(group))
+            })
+        })
+        .unwrap_or(0);
+    let selection = select_from_list_with_default("Select server:", &items, default)?;
 
     if selection == items.len() - 1 {
         // Cancel selected
         return Ok(None);
     }
 
-    let (user, ip, key_path, mut password) = if selection < servers.len() {
-        let server = &servers[selection];
-        println!("Selected server: {} ({})", server.name, server.ip);
-        (
-            server.user.clone(),
-            server.ip.clone(),
-            server.key_path.clone(),
-            server.password.clone(),
-        )
+    let mut info = if selection < candidates.len() {
+        let server = candidates[selection];
+        info!("Selected server: {} ({})", server.name, server.ip);
+        // Resolve secret references only for the server actually selected
+        server_info(server, servers)?
     } else {
         // Manual input
         let user_input = input_text("Enter username:")?;
         let ip_input = input_text("Enter IP address:")?;
-        println!("Target: {}@{}", user_input, ip_input);
-        (user_input, ip_input, None, None)
+        info!("Target: {}@{}", user_input, ip_input);
+        ServerInfo {
+            user: user_input,
+            ip: ip_input,
+            ..ServerInfo::default()
+        }
     };
 
     // Prompt for password if not in config and no key path
-    if password.is_none() && key_path.is_none() {
+    if info.password.is_none() && info.key_path.is_none() {
         let pass = input_password("Enter password (leave empty for default SSH auth):")?;
         if !pass.is_empty() {
-            password = Some(pass);
+            info.password = Some(pass);
         }
     }
 
-    Ok(Some((user, ip, key_path, password)))
+    Ok(Some(info))
+}
+
+/// Resolves a config value that may be a secret reference.
+///
+/// `cmd:` references run through the active [`runner`], so `--dry-run`
+/// prints the command instead and the secret resolves to a placeholder.
+///
+/// # Arguments
+/// * `value` - Config value, e.g. `auth.password`
+/// * `passphrase` - Asked for the vault passphrase on `vault:` references
+///
+/// # Errors
+/// Returns error if the reference cannot be resolved
+pub fn resolve_secret(
+    value: &str,
+    passphrase: impl FnOnce() -> Result<Zeroizing<String>>,
+) -> Result<Zeroizing<String>> {
+    secret::resolve_with(value, passphrase, |command| {
+        let mut output = runner::output(command, None)?;
+        if !runner::executes() && output.stdout.is_empty() {
+            output.stdout = DRY_RUN_SECRET.into();
+        }
+        Ok(output)
+    })
+}
+
+/// Builds connection details for a configured server without prompting.
+///
+/// Resolves the server's password reference; asks for the vault passphrase
+/// only if the password is stored in the vault and
+/// `$STOOL_VAULT_PASSPHRASE` is not set.
+///
+/// # Arguments
+/// * `server` - Server to connect to
+/// * `servers` - All configured servers, for resolving jump hosts by name
+///
+/// # Errors
+/// Returns error if the password reference cannot be resolved or jump
+/// hosts form a cycle
+pub fn server_info(server: &Server, servers: &[Server]) -> Result<ServerInfo> {
+    let password = server
+        .auth
+        .password
+        .as_deref()
+        .map(|p| resolve_secret(p, vault_passphrase))
+        .transpose()?;
+    Ok(ServerInfo {
+        name: Some(server.name.clone()),
+        user: server.user.clone(),
+        ip: server.ip.clone(),
+        port: server.port,
+        key_path: server.auth.key_path.clone(),
+        password,
+        ssh_args: command::ssh_option_args(server, servers)?,
+        openssh_only: server.proxy_jump.is_some()
+            || server.auth.identity_agent.is_some()
+            || !server.ssh_options.is_empty(),
+    })
+}
+
+/// Formats a server menu entry: `[group] name (user@ip) env=prod tags=a,b`.
+fn server_label(server: &Server) -> String {
+    let mut label = match &server.group {
+        Some(group) => format!("[{}] ", group),
+        None => String::new(),
+    };
+    label.push_str(&format!("{} ({}@{})", server.name, server.user, server.ip));
+    if let Some(env) = &server.environment {
+        label.push_str(&format!(" env={}", env));
+    }
+    if !server.tags.is_empty() {
+        label.push_str(&format!(" tags={}", server.tags.join(",")));
+    }
+    label
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::RecordingRunner;
+    use std::process::{ExitStatus, Output};
+    use std::rc::Rc;
+
+    #[test]
+    fn debug_output_redacts_the_password() {
+        let info = ServerInfo {
+            user: "deploy".to_string(),
+            ip: "10.0.0.1".to_string(),
+            password: Some(Zeroizing::new("hunter2".to_string())),
+            ..ServerInfo::default()
+        };
+        let debug = format!("{:?}", info);
+
+        assert!(!debug.contains("hunter2"), "{debug}");
+        assert!(debug.contains("<redacted>"), "{debug}");
+    }
+
+    #[test]
+    fn secret_commands_run_through_the_runner() {
+        let recorder = Rc::new(RecordingRunner::new());
+        recorder.push(Output {
+            status: ExitStatus::default(),
+            stdout: b"s3cret\n".to_vec(),
+            stderr: Vec::new(),
+        });
+
+        let password = runner::with_runner(recorder.clone(), || {
+            resolve_secret("cmd:pass show prod", || unreachable!())
+        })
+        .unwrap();
+
+        assert_eq!(*password, "s3cret");
+        assert_eq!(recorder.commands(), ["sh -c 'pass show prod'"]);
+    }
+
+    #[test]
+    fn secret_commands_resolve_to_a_placeholder_when_not_run() {
+        let recorder = Rc::new(RecordingRunner::new());
+
+        let password = runner::with_runner(recorder, || {
+            resolve_secret("cmd:pass show prod", || unreachable!())
+        })
+        .unwrap();
+
+        assert_eq!(*password, DRY_RUN_SECRET);
+    }
 }
@@ -3,6 +3,15 @@
 //! This crate provides shared utilities used across feature modules:
 //! - Interactive user input and selection menus
 //! - Command execution helpers for SSH, SCP, and system commands
+//! - `SSH_ASKPASS` helper serving passwords to ssh and scp
+//! - Command runners for real, dry-run and recorded execution
+//! - Leveled terminal and file logging
+//! - Native SSH/SFTP client (`native-ssh` feature)
 
+pub mod askpass;
 pub mod command;
 pub mod interactive;
+pub mod logger;
+#[cfg(feature = "native-ssh")]
+pub mod native;
+pub mod runner;
@@ -0,0 +1,283 @@
+//! Leveled logging for stool.
+//!
+//! Modules report progress through the [`log`] macros; [`init`] installs a
+//! logger that writes them to the terminal and, optionally, a log file:
+//! - Terminal: `info` goes to stdout as plain text (stool's normal progress
+//!   messages); errors, warnings and debug detail go to stderr, prefixed
+//!   with their level
+//! - File: `$XDG_STATE_HOME/stool/stool.log`, always recorded at `debug`
+//!   detail or more, rotated at [`MAX_LOG_SIZE`] keeping [`KEPT_LOGS`] old files
+//!
+//! Only records from stool's own crates are shown; dependencies' logs are
+//! dropped.
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use stool_core::error::{Result, StoolError, StoolErrorType};
+use stool_core::{paths, time};
+
+/// Log file name under the state directory.
+const LOG_FILE_NAME: &str = "stool.log";
+/// Size at which the log file is rotated.
+pub const MAX_LOG_SIZE: u64 = 1024 * 1024;
+/// Number of rotated log files kept (`stool.log.1` is the newest).
+pub const KEPT_LOGS: usize = 3;
+/// Target prefix of records worth showing.
+const OWN_TARGET: &str = "stool";
+
+/// Returns the log file location (`$XDG_STATE_HOME/stool/stool.log`).
+pub fn log_path() -> Option<PathBuf> {
+    paths::state_dir().map(|d| d.join(LOG_FILE_NAME))
+}
+
+/// Installs the logger; only the first call has an effect.
+///
+/// # Arguments
+/// * `level` - Most detailed level shown on the terminal
+/// * `file` - Also append records to the log file
+///
+/// # Errors
+/// Returns `IoError` if the log file cannot be opened; terminal logging is
+/// installed regardless
+pub fn init(level: LevelFilter, file: bool) -> Result<()> {
+    let (log_file, error) = match file.then(LogFile::open) {
+        Some(Ok(log_file)) => (Some(Mutex::new(log_file)), None),
+        Some(Err(e)) => (None, Some(e)),
+        None => (None, None),
+    };
+    let file_level = match log_file {
+        Some(_) => level.max(LevelFilter::Debug),
+        None => LevelFilter::Off,
+    };
+
+    let logger = Logger {
+        level,
+        file_level,
+        file: log_file,
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(level.max(file_level));
+    }
+    error.map_or(Ok(()), Err)
+}
+
+struct Logger {
+    level: LevelFilter,
+    file_level: LevelFilter,
+    file: Option<Mutex<LogFile>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with(OWN_TARGET)
+            && metadata.level() <= self.level.max(self.file_level)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if record.level() <= self.level {
+            // Nowhere to report a failed terminal write
+            let _ = match record.level() {
+                Level::Info => writeln!(io::stdout(), "{}", record.args()),
+                level => writeln!(io::stderr(), "{}: {}", prefix(level), record.args()),
+            };
+        }
+        if record.level() <= self.file_level
+            && let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            file.write(record);
+        }
+    }
+
+    fn flush(&self) {
+        let _ = io::stdout().flush();
+        if let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+fn prefix(level: Level) -> &'static str {
+    match level {
+        Level::Error => "Error",
+        Level::Warn => "Warning",
+        Level::Info => "Info",
+        Level::Debug => "Debug",
+        Level::Trace => "Trace",
+    }
+}
+
+/// Append-only log file that rotates itself when it grows too large.
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open() -> Result<Self> {
+        let path = log_path().ok_or_else(|| {
+            StoolError::new(StoolErrorType::IoError)
+                .with_message("Cannot determine state directory (HOME not set)")
+        })?;
+        Self::open_at(path)
+    }
+
+    /// Opens a log file at `path`, rotating it first if it is already full.
+    fn open_at(path: PathBuf) -> Result<Self> {
+        let message = format!("Failed to open log file: {}", path.display());
+        let io_error = |e: io::Error| {
+            StoolError::new(StoolErrorType::IoError)
+                .with_message(message.clone())
+                .with_source(e)
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let (file, size) = append(&path).map_err(io_error)?;
+        let mut log_file = Self { path, file, size };
+        if log_file.size >= MAX_LOG_SIZE {
+            log_file.rotate().map_err(io_error)?;
+        }
+        Ok(log_file)
+    }
+
+    fn write(&mut self, record: &Record) {
+        let line = format!(
+            "{} {:<5} [{}] {}: {}\n",
+            time::now_utc(),
+            record.level(),
+            std::process::id(),
+            record.target(),
+            record.args()
+        );
+        // Logging must never make a command fail
+        if self.file.write_all(line.as_bytes()).is_ok() {
+            self.size += line.len() as u64;
+        }
+        if self.size >= MAX_LOG_SIZE {
+            let _ = self.rotate();
+        }
+    }
+
+    /// Shifts `stool.log.N` to `stool.log.N+1` and starts a new file.
+    fn rotate(&mut self) -> io::Result<()> {
+        let numbered = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        let _ = fs::remove_file(numbered(KEPT_LOGS));
+        for n in (1..KEPT_LOGS).rev() {
+            let _ = fs::rename(numbered(n), numbered(n + 1));
+        }
+        fs::rename(&self.path, numbered(1))?;
+        (self.file, self.size) = append(&self.path)?;
+        Ok(())
+    }
+}
+
+fn append(path: &Path) -> io::Result<(File, u64)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok((file, size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(test: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("stool-logger-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn records_are_written_with_time_level_pid_and_target() {
+        let dir = scratch_dir("write");
+        let path = dir.join(LOG_FILE_NAME);
+        let mut log_file = LogFile::open_at(path.clone()).unwrap();
+
+        log_file.write(
+            &Record::builder()
+                .args(format_args!("Running: docker build"))
+                .level(Level::Debug)
+                .target("stool_utils::runner")
+                .build(),
+        );
+
+        let content = fs::read_to_string(&path).unwrap();
+        let expected = format!(
+            " DEBUG [{}] stool_utils::runner: Running: docker build\n",
+            std::process::id()
+        );
+        assert!(content.ends_with(&expected), "{content}");
+        assert_eq!(log_file.size, content.len() as u64);
+    }
+
+    #[test]
+    fn a_full_log_is_rotated_when_opened() {
+        let dir = scratch_dir("open-full");
+        let path = dir.join(LOG_FILE_NAME);
+        fs::write(&path, vec![b'x'; MAX_LOG_SIZE as usize]).unwrap();
+
+        let log_file = LogFile::open_at(path.clone()).unwrap();
+
+        assert_eq!(log_file.size, 0);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+        assert_eq!(
+            fs::metadata(dir.join("stool.log.1")).unwrap().len(),
+            MAX_LOG_SIZE
+        );
+    }
+
+    #[test]
+    fn writing_past_the_limit_rotates() {
+        let dir = scratch_dir("write-full");
+        let path = dir.join(LOG_FILE_NAME);
+        fs::write(&path, vec![b'x'; MAX_LOG_SIZE as usize - 10]).unwrap();
+        let mut log_file = LogFile::open_at(path.clone()).unwrap();
+
+        log_file.write(
+            &Record::builder()
+                .args(format_args!("this line crosses the limit"))
+                .level(Level::Info)
+                .target("stool")
+                .build(),
+        );
+
+        assert_eq!(log_file.size, 0);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        let rotated = fs::read_to_string(dir.join("stool.log.1")).unwrap();
+        assert!(rotated.ends_with("stool: this line crosses the limit\n"));
+    }
+
+    #[test]
+    fn rotation_shifts_old_logs_and_drops_the_oldest() {
+        let dir = scratch_dir("rotate");
+        let path = dir.join(LOG_FILE_NAME);
+        fs::write(&path, "current").unwrap();
+        fs::write(dir.join("stool.log.1"), "one").unwrap();
+        fs::write(dir.join("stool.log.2"), "two").unwrap();
+        fs::write(dir.join("stool.log.3"), "three").unwrap();
+        let mut log_file = LogFile::open_at(path.clone()).unwrap();
+
+        log_file.rotate().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        assert_eq!(
+            fs::read_to_string(dir.join("stool.log.1")).unwrap(),
+            "current"
+        );
+        assert_eq!(fs::read_to_string(dir.join("stool.log.2")).unwrap(), "one");
+        assert_eq!(fs::read_to_string(dir.join("stool.log.3")).unwrap(), "two");
+        assert!(!dir.join("stool.log.4").exists());
+    }
+}
@@ -0,0 +1,817 @@
+//! Native SSH client (`native-ssh` feature).
+//!
+//! Speaks SSH in-process through libssh2 instead of running `ssh`/`scp`,
+//! so callers get structured results and transfer progress:
+//! - [`Connection::open`] - Connects and authenticates (key, password or agent)
+//! - [`Connection::exec`] - Runs a remote command and collects its output
+//! - [`Connection::exec_attached`] - Runs a remote command on stool's stdio
+//! - [`Connection::shell`] - Interactive login shell on a pseudo-terminal
+//! - [`Connection::upload`] / [`Connection::download`] - SFTP file transfer
+//!
+//! Host keys are checked against `~/.ssh/known_hosts` and
+//! `/etc/ssh/ssh_known_hosts`; unknown hosts are rejected rather than
+//! trusted. Jump hosts, custom identity agents and
+//! extra ssh options are not supported (see [`ServerInfo::openssh_only`]);
+//! callers fall back to the `ssh` binary for those servers.
+
+use crate::interactive::ServerInfo;
+use crate::runner;
+use log::debug;
+use nix::poll::{PollFd, PollFlags, PollTimeout, poll};
+use ssh2::{Channel, CheckResult, KnownHostFileKind, Session};
+use std::fs::File;
+use std::io::{self, IsTerminal, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::os::fd::AsFd;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+use stool_core::error::{Result, StoolError, StoolErrorType};
+use stool_core::paths;
+
+/// Port used when the server does not set one.
+const DEFAULT_PORT: u16 = 22;
+/// How long to wait for the TCP connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default keys tried after the agent, in `ssh`'s order.
+const DEFAULT_KEYS: &[&str] = &["id_ed25519", "id_ecdsa", "id_rsa"];
+/// Transfer buffer size.
+const CHUNK_SIZE: usize = 64 * 1024;
+/// How long to wait before polling a channel again when no data moved.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Terminal type requested for a pseudo-terminal when `$TERM` is not set.
+const DEFAULT_TERM: &str = "xterm";
+/// System-wide known hosts, read after the user's own.
+const GLOBAL_KNOWN_HOSTS: &str = "/etc/ssh/ssh_known_hosts";
+
+/// Result of a remote command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// Exit status reported by the remote shell.
+    pub exit_status: i32,
+}
+
+impl RemoteOutput {
+    /// Returns true if the command exited with status 0.
+    pub fn success(&self) -> bool {
+        self.exit_status == 0
+    }
+}
+
+/// Progress callback for transfers: `(bytes done, total bytes)`.
+pub type Progress<'a> = &'a mut dyn FnMut(u64, u64);
+
+/// Authenticated SSH session to one server.
+pub struct Connection {
+    session: Session,
+    destination: String,
+}
+
+impl Connection {
+    /// Connects to a server and authenticates.
+    ///
+    /// Authentication follows the same priority as `ssh`/`scp` in stool:
+    /// `key_path`, then password, then the ssh-agent and default keys.
+    ///
+    /// # Errors
+    /// Returns `SshConnectionFailed` if the host cannot be reached or its key
+    /// is unknown or changed, or `SshAuthenticationFailed` if every
+    /// authentication method is rejected
+    pub fn open(server: &ServerInfo) -> Result<Self> {
+        let port = server.port.unwrap_or(DEFAULT_PORT);
+        let destination = format!("{}@{}:{}", server.user, server.ip, port);
+        debug!("Connecting to {} (native)", destination);
+
+        let address = (server.ip.as_str(), port)
+            .to_socket_addrs()
+            .map_err(|e| connection_error(&destination, "Cannot resolve host").with_source(e))?
+            .next()
+            .ok_or_else(|| connection_error(&destination, "Cannot resolve host"))?;
+        let tcp = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+            .map_err(|e| connection_error(&destination, "Cannot connect").with_source(e))?;
+
+        let mut session = Session::new()
+            .map_err(|e| connection_error(&destination, "Cannot start session").with_source(e))?;
+        session.set_tcp_stream(tcp);
+        session
+            .handshake()
+            .map_err(|e| connection_error(&destination, "SSH handshake failed").with_source(e))?;
+
+        let connection = Self {
+            session,
+            destination,
+        };
+        connection.verify_host_key(&server.ip, port)?;
+        connection.authenticate(server)?;
+        Ok(connection)
+    }
+
+    /// Runs a command on the server and waits for it to exit.
+    ///
+    /// The command's stdin is closed; stdout and stderr are collected.
+    ///
+    /// # Errors
+    /// Returns `SshConnectionFailed` if the command cannot be started or its
+    /// output cannot be read, or `RemoteCommandFailed` if it is killed by a
+    /// signal
+    pub fn exec(&self, command: &str) -> Result<RemoteOutput> {
+        debug!("Running on {}: {}", self.destination, command);
+        let mut channel = self
+            .session
+            .channel_session()
+            .map_err(|e| self.run_error(command, e))?;
+        channel
+            .exec(command)
+            .map_err(|e| self.run_error(command, e))?;
+
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        self.pump(&mut channel, None, &mut stdout, &mut stderr)?;
+        let exit_status = self.finish(channel, command)?;
+        Ok(RemoteOutput {
+            stdout,
+            stderr,
+            exit_status,
+        })
+    }
+
+    /// Runs a command on the server with stool's stdin, stdout and stderr
+    /// attached, and returns its exit status.
+    ///
+    /// With `tty` the command gets a pseudo-terminal (like `ssh -t`) and
+    /// the local terminal is put in raw mode until it exits.
+    ///
+    /// # Errors
+    /// Same as [`Connection::exec`]
+    pub fn exec_attached(&self, command: &str, tty: bool) -> Result<i32> {
+        debug!("Running on {}: {}", self.destination, command);
+        self.attached(Some(command), tty)
+    }
+
+    /// Opens a login shell on a pseudo-terminal attached to stool's terminal
+    /// and returns its exit status.
+    ///
+    /// The terminal size is taken when the shell starts; later resizes are
+    /// not passed on.
+    ///
+    /// # Errors
+    /// Same as [`Connection::exec`]
+    pub fn shell(&self) -> Result<i32> {
+        debug!("Opening a shell on {}", self.destination);
+        self.attached(None, true)
+    }
+
+    /// Uploads a local file over SFTP.
+    ///
+    /// A remote directory (including `~/`) receives the file under its
+    /// local name. Returns the remote path written.
+    ///
+    /// # Errors
+    /// Returns `SourceFileNotFound` if the local file cannot be opened, or
+    /// `FileTransferFailed` if the remote file cannot be written
+    pub fn upload(&self, local: &Path, remote: &str, progress: Progress) -> Result<PathBuf> {
+        let mut source = File::open(local).map_err(|e| {
+            StoolError::new(StoolErrorType::SourceFileNotFound)
+                .with_message(local.display().to_string())
+                .with_source(e)
+        })?;
+        let total = source.metadata().map(|m| m.len()).unwrap_or(0);
+
+        let sftp = self.sftp()?;
+        let mut target = remote_path(remote);
+        if remote.ends_with('/') || sftp.stat(&target).is_ok_and(|s| s.is_dir()) {
+            target.push(local.file_name().unwrap_or_default());
+        }
+        debug!(
+            "Uploading {} to {}:{}",
+            local.display(),
+            self.destination,
+            target.display()
+        );
+        let mut dest = sftp
+            .create(&target)
+            .map_err(|e| self.transfer_error(&target, e))?;
+
+        copy(&mut source, &mut dest, total, progress)
+            .map_err(|e| self.transfer_error(&target, e))?;
+        Ok(target)
+    }
+
+    /// Downloads a remote file over SFTP.
+    ///
+    /// A local directory receives the file under its remote name. Returns
+    /// the local path written.
+    ///
+    /// # Errors
+    /// Returns `FileTransferFailed` if the remote file cannot be read or the
+    /// local file cannot be written
+    pub fn download(&self, remote: &str, local: &Path, progress: Progress) -> Result<PathBuf> {
+        let sftp = self.sftp()?;
+        let source_path = remote_path(remote);
+        let mut source = sftp
+            .open(&source_path)
+            .map_err(|e| self.transfer_error(&source_path, e))?;
+        let total = source.stat().ok().and_then(|s| s.size).unwrap_or(0);
+
+        let mut target = local.to_path_buf();
+        if target.is_dir() {
+            target.push(source_path.file_name().unwrap_or_default());
+        }
+        debug!(
+            "Downloading {}:{} to {}",
+            self.destination,
+            source_path.display(),
+            target.display()
+        );
+        let mut dest = File::create(&target).map_err(|e| {
+            StoolError::new(StoolErrorType::FileTransferFailed)
+                .with_message(format!("Failed to create {}", target.display()))
+                .with_source(e)
+        })?;
+
+        copy(&mut source, &mut dest, total, progress)
+            .map_err(|e| self.transfer_error(&source_path, e))?;
+        Ok(target)
+    }
+
+    fn verify_host_key(&self, host: &str, port: u16) -> Result<()> {
+        let (key, _) = self
+            .session
+            .host_key()
+            .ok_or_else(|| connection_error(&self.destination, "Server sent no host key"))?;
+        let mut known_hosts = self.session.known_hosts().map_err(|e| {
+            connection_error(&self.destination, "Cannot check host key").with_source(e)
+        })?;
+        let files = paths::home_dir()
+            .map(|h| h.join(".ssh").join("known_hosts"))
+            .into_iter()
+            .chain([PathBuf::from(GLOBAL_KNOWN_HOSTS)]);
+        for file in files {
+            // A missing file just means no host is known yet
+            let _ = known_hosts.read_file(&file, KnownHostFileKind::OpenSSH);
+        }
+
+        match known_hosts.check_port(host, port, key) {
+            CheckResult::Match => Ok(()),
+            CheckResult::Mismatch => Err(connection_error(
+                &self.destination,
+                "Host key does not match ~/.ssh/known_hosts",
+            )
+            .with_hint("the host key changed; verify it before removing the old entry with `ssh-keygen -R`")),
+            CheckResult::NotFound => Err(connection_error(
+                &self.destination,
+                "Host key is not in ~/.ssh/known_hosts",
+            )
+            .with_hint(format!(
+                "connect once with `ssh -p {} {}` to verify and record the host key; \
+                 the native client reads only ~/.ssh/known_hosts and {}, \
+                 not other UserKnownHostsFile settings",
+                port, host, GLOBAL_KNOWN_HOSTS
+            ))),
+            CheckResult::Failure => Err(connection_error(
+                &self.destination,
+                "Cannot check host key against ~/.ssh/known_hosts",
+            )
+            .with_hint(format!(
+                "check that `ssh -p {} {}` accepts the host key",
+                port, host
+            ))),
+        }
+    }
+
+    fn authenticate(&self, server: &ServerInfo) -> Result<()> {
+        let user = server.user.as_str();
+        let result = if let Some(key) = &server.key_path {
+            debug!("Authenticating with key {}", key);
+            self.session
+                .userauth_pubkey_file(user, None, &paths::expand_tilde(key), None)
+        } else if let Some(password) = &server.password {
+            debug!("Authenticating with password");
+            self.session.userauth_password(user, password)
+        } else {
+            debug!("Authenticating with ssh-agent and default keys");
+            self.authenticate_default(user)
+        };
+
+        if result.is_err() || !self.session.authenticated() {
+            let mut error = StoolError::new(StoolErrorType::SshAuthenticationFailed)
+                .with_message(format!("Authentication rejected for {}", self.destination));
+            if let Err(e) = result {
+                error = error.with_source(e);
+            }
+            if server.key_path.is_some() {
+                error = error
+                    .with_hint("passphrase-protected keys are only supported through ssh-agent");
+            }
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Tries the ssh-agent, then each default key that exists.
+    fn authenticate_default(&self, user: &str) -> std::result::Result<(), ssh2::Error> {
+        let mut last = match self.session.userauth_agent(user) {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        let ssh_dir = paths::home_dir()
+            .map(|h| h.join(".ssh"))
+            .unwrap_or_default();
+        for key in DEFAULT_KEYS
+            .iter()
+            .map(|k| ssh_dir.join(k))
+            .filter(|k| k.exists())
+        {
+            match self.session.userauth_pubkey_file(user, None, &key, None) {
+                Ok(()) => return Ok(()),
+                Err(e) => last = e,
+            }
+        }
+        Err(last)
+    }
+
+    fn attached(&self, command: Option<&str>, tty: bool) -> Result<i32> {
+        let label = command.unwrap_or("login shell");
+        let mut channel = self
+            .session
+            .channel_session()
+            .map_err(|e| self.run_error(label, e))?;
+
+        // Restored when dropped, after the remote side has exited
+        let _raw = if tty {
+            let term = std::env::var("TERM").unwrap_or_else(|_| DEFAULT_TERM.to_string());
+            let size = terminal_size().map(|(rows, columns)| (columns, rows, 0, 0));
+            channel
+                .request_pty(&term, None, size)
+                .map_err(|e| self.run_error(label, e))?;
+            RawMode::enable()
+        } else {
+            None
+        };
+        match command {
+            Some(command) => channel.exec(command),
+            None => channel.shell(),
+        }
+        .map_err(|e| self.run_error(label, e))?;
+
+        let mut stdin = PolledStdin::new()
+            .map_err(|e| connection_error(&self.destination, "Cannot read stdin").with_source(e))?;
+        self.pump(
+            &mut channel,
+            Some(&mut stdin),
+            &mut io::stdout(),
+            &mut io::stderr(),
+        )?;
+        self.finish(channel, label)
+    }
+
+    /// Moves data between a channel and local streams until the remote side
+    /// closes its output.
+    ///
+    /// The session is non-blocking while pumping, so stdout and stderr are
+    /// drained as data arrives and neither can fill up and stall the other.
+    /// Without `input` the command's stdin is closed right away.
+    fn pump(
+        &self,
+        channel: &mut Channel,
+        input: Option<&mut dyn Read>,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> Result<()> {
+        self.session.set_blocking(false);
+        let result = pump(channel, input, stdout, stderr);
+        self.session.set_blocking(true);
+        result.map_err(|e| {
+            connection_error(&self.destination, "Failed to relay command I/O").with_source(e)
+        })
+    }
+
+    /// Waits for the channel to close and returns the exit status.
+    fn finish(&self, mut channel: Channel, label: &str) -> Result<i32> {
+        channel.wait_close().map_err(|e| self.run_error(label, e))?;
+        if let Some(signal) = channel.exit_signal().ok().and_then(|s| s.exit_signal) {
+            return Err(
+                StoolError::new(StoolErrorType::RemoteCommandFailed).with_message(format!(
+                    "{} killed by SIG{} on {}",
+                    label, signal, self.destination
+                )),
+            );
+        }
+        let exit_status = channel
+            .exit_status()
+            .map_err(|e| self.run_error(label, e))?;
+        debug!("Remote command exited with {}: {}", exit_status, label);
+        Ok(exit_status)
+    }
+
+    fn run_error(&self, label: &str, source: ssh2::Error) -> StoolError {
+        connection_error(&self.destination, format!("Failed to run '{}'", label))
+            .with_source(source)
+    }
+
+    fn sftp(&self) -> Result<ssh2::Sftp> {
+        self.session.sftp().map_err(|e| {
+            StoolError::new(StoolErrorType::SftpCommandFailed)
+                .with_message(format!("Cannot start SFTP on {}", self.destination))
+                .with_source(e)
+        })
+    }
+
+    fn transfer_error(
+        &self,
+        path: &Path,
+        source: impl std::error::Error + Send + Sync + 'static,
+    ) -> StoolError {
+        StoolError::new(StoolErrorType::FileTransferFailed)
+            .with_message(format!("{} on {}", path.display(), self.destination))
+            .with_source(source)
+    }
+}
+
+/// Maps `~` and `~/...` to paths relative to the SFTP home directory.
+fn remote_path(path: &str) -> PathBuf {
+    match path {
+        "~" | "~/" | "" => PathBuf::from("."),
+        _ => PathBuf::from(path.strip_prefix("~/").unwrap_or(path)),
+    }
+}
+
+fn copy(
+    source: &mut dyn Read,
+    dest: &mut dyn Write,
+    total: u64,
+    progress: Progress,
+) -> io::Result<()> {
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut done = 0u64;
+    progress(done, total);
+    loop {
+        let n = source.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        dest.write_all(&buf[..n])?;
+        done += n as u64;
+        progress(done, total);
+    }
+    dest.flush()
+}
+
+/// The side of an SSH channel the relay loop talks to; implemented for
+/// [`Channel`] and by in-memory commands in tests.
+///
+/// Reads fail with `WouldBlock` while no data has arrived.
+trait RemoteStreams {
+    fn read_stdout(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+    fn read_stderr(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+    fn write_stdin(&mut self, data: &[u8]) -> io::Result<usize>;
+    fn close_stdin(&mut self) -> io::Result<()>;
+    /// Returns true once the command has closed its output.
+    fn output_closed(&self) -> bool;
+}
+
+impl RemoteStreams for Channel {
+    fn read_stdout(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream(0).read(buf)
+    }
+
+    fn read_stderr(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stderr().read(buf)
+    }
+
+    fn write_stdin(&mut self, data: &[u8]) -> io::Result<usize> {
+        Write::write(self, data)
+    }
+
+    fn close_stdin(&mut self) -> io::Result<()> {
+        self.send_eof().map_err(io::Error::from)
+    }
+
+    fn output_closed(&self) -> bool {
+        self.eof()
+    }
+}
+
+/// Relays a non-blocking channel: output to `stdout`/`stderr`, `input` to
+/// the command's stdin.
+///
+/// `input` must not block: it fails with `WouldBlock` while nothing is
+/// waiting. Returns once the remote side has sent EOF and no data is left.
+fn pump(
+    channel: &mut impl RemoteStreams,
+    mut input: Option<&mut dyn Read>,
+    stdout: &mut dyn Write,
+    stderr: &mut dyn Write,
+) -> io::Result<()> {
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut pending: Vec<u8> = Vec::new();
+    let mut send_eof = input.is_none();
+    loop {
+        let read = channel.read_stdout(&mut buf);
+        let mut busy = forward(read, &buf, stdout)?;
+        let read = channel.read_stderr(&mut buf);
+        busy |= forward(read, &buf, stderr)?;
+        if channel.output_closed() {
+            if !busy {
+                return Ok(());
+            }
+            continue;
+        }
+
+        if pending.is_empty()
+            && let Some(reader) = input.as_mut()
+        {
+            match reader.read(&mut buf) {
+                Ok(0) => {
+                    input = None;
+                    send_eof = true;
+                }
+                Ok(n) => pending.extend_from_slice(&buf[..n]),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+                    ) => {}
+                Err(e) => {
+                    debug!("Stopped reading stdin: {}", e);
+                    input = None;
+                    send_eof = true;
+                }
+            }
+        }
+        if !pending.is_empty() {
+            match channel.write_stdin(&pending) {
+                Ok(n) => {
+                    pending.drain(..n);
+                    busy = true;
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                // The command no longer reads its stdin; keep relaying output
+                Err(e) => {
+                    debug!("Stopped forwarding stdin: {}", e);
+                    pending.clear();
+                    input = None;
+                }
+            }
+        }
+        if send_eof && pending.is_empty() {
+            match channel.close_stdin() {
+                Ok(()) => send_eof = false,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        if !busy {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// Copies the result of one channel stream read to `out`; returns true if
+/// anything was read.
+fn forward(read: io::Result<usize>, buf: &[u8], out: &mut dyn Write) -> io::Result<bool> {
+    match read {
+        Ok(0) => Ok(false),
+        Ok(n) => {
+            out.write_all(&buf[..n])?;
+            out.flush()?;
+            Ok(true)
+        }
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// stool's stdin, read only when input is waiting so that nothing is left
+/// blocked on it (and swallowing keystrokes) once the remote command exits.
+///
+/// Reads bypass the buffer of [`io::Stdin`] and fail with `WouldBlock`
+/// while no input is waiting.
+struct PolledStdin {
+    file: File,
+}
+
+impl PolledStdin {
+    fn new() -> io::Result<Self> {
+        let fd = io::stdin().as_fd().try_clone_to_owned()?;
+        Ok(Self {
+            file: File::from(fd),
+        })
+    }
+}
+
+impl Read for PolledStdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut fds = [PollFd::new(self.file.as_fd(), PollFlags::POLLIN)];
+        if poll(&mut fds, PollTimeout::ZERO)? == 0 {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        self.file.read(buf)
+    }
+}
+
+/// The local terminal in raw mode (set with `stty`), restored on drop.
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    /// Returns `None` if stdin is not a terminal or `stty` fails.
+    fn enable() -> Option<Self> {
+        if !io::stdin().is_terminal() {
+            return None;
+        }
+        let saved = stty(&["-g"])?.trim().to_string();
+        stty(&["raw", "-echo"])?;
+        Some(Self { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+/// Returns the local terminal size as `(rows, columns)`.
+fn terminal_size() -> Option<(u32, u32)> {
+    let size = stty(&["size"])?;
+    let mut parts = size.split_whitespace().map(str::parse);
+    match (parts.next(), parts.next()) {
+        (Some(Ok(rows)), Some(Ok(columns))) => Some((rows, columns)),
+        _ => None,
+    }
+}
+
+/// Runs `stty` on stool's terminal and returns its output.
+fn stty(args: &[&str]) -> Option<String> {
+    let output = runner::output(
+        Command::new("stty").args(args).stdin(Stdio::inherit()),
+        None,
+    )
+    .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn connection_error(destination: &str, message: impl std::fmt::Display) -> StoolError {
+    StoolError::new(StoolErrorType::SshConnectionFailed)
+        .with_message(format!("{}: {}", message, destination))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// In-memory command that echoes its stdin to stdout, like `cat`.
+    #[derive(Default)]
+    struct Cat {
+        stdout: VecDeque<u8>,
+        stdin_closed: bool,
+        /// Writes refused with `WouldBlock` before one is accepted.
+        busy_writes: usize,
+    }
+
+    impl RemoteStreams for Cat {
+        fn read_stdout(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.stdout.is_empty() {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            self.stdout.read(buf)
+        }
+
+        fn read_stderr(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::WouldBlock.into())
+        }
+
+        fn write_stdin(&mut self, data: &[u8]) -> io::Result<usize> {
+            if self.busy_writes > 0 {
+                self.busy_writes -= 1;
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            self.stdout.extend(data);
+            Ok(data.len())
+        }
+
+        fn close_stdin(&mut self) -> io::Result<()> {
+            self.stdin_closed = true;
+            Ok(())
+        }
+
+        fn output_closed(&self) -> bool {
+            self.stdin_closed
+        }
+    }
+
+    /// In-memory command that has already exited, leaving output behind.
+    struct Exited {
+        stdout: VecDeque<u8>,
+        stderr: VecDeque<u8>,
+    }
+
+    impl RemoteStreams for Exited {
+        fn read_stdout(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.stdout.read(buf)
+        }
+
+        fn read_stderr(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.stderr.read(buf)
+        }
+
+        fn write_stdin(&mut self, _data: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn close_stdin(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn output_closed(&self) -> bool {
+            true
+        }
+    }
+
+    /// Local input with nothing typed yet.
+    struct Idle;
+
+    impl Read for Idle {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::WouldBlock.into())
+        }
+    }
+
+    #[test]
+    fn input_is_relayed_to_the_command_and_its_output_back() {
+        let mut cat = Cat::default();
+        let mut input: &[u8] = b"hello\nworld\n";
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+
+        pump(&mut cat, Some(&mut input), &mut stdout, &mut stderr).unwrap();
+
+        assert_eq!(stdout, b"hello\nworld\n");
+        assert!(stderr.is_empty());
+        assert!(cat.stdin_closed);
+    }
+
+    #[test]
+    fn stdin_is_closed_right_away_without_input() {
+        let mut cat = Cat::default();
+        let mut stdout = Vec::new();
+
+        pump(&mut cat, None, &mut stdout, &mut Vec::new()).unwrap();
+
+        assert!(stdout.is_empty());
+        assert!(cat.stdin_closed);
+    }
+
+    #[test]
+    fn writes_are_retried_until_the_channel_takes_them() {
+        let mut cat = Cat {
+            busy_writes: 3,
+            ..Cat::default()
+        };
+        let mut input: &[u8] = b"data";
+        let mut stdout = Vec::new();
+
+        pump(&mut cat, Some(&mut input), &mut stdout, &mut Vec::new()).unwrap();
+
+        assert_eq!(stdout, b"data");
+        assert_eq!(cat.busy_writes, 0);
+    }
+
+    #[test]
+    fn output_left_at_eof_is_delivered() {
+        let mut exited = Exited {
+            stdout: VecDeque::from(b"out".to_vec()),
+            stderr: VecDeque::from(b"err".to_vec()),
+        };
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+
+        pump(&mut exited, None, &mut stdout, &mut stderr).unwrap();
+
+        assert_eq!(stdout, b"out");
+        assert_eq!(stderr, b"err");
+    }
+
+    #[test]
+    fn waiting_input_does_not_outlive_the_command() {
+        let mut exited = Exited {
+            stdout: VecDeque::from(b"bye".to_vec()),
+            stderr: VecDeque::new(),
+        };
+        let mut stdout = Vec::new();
+
+        pump(&mut exited, Some(&mut Idle), &mut stdout, &mut Vec::new()).unwrap();
+
+        assert_eq!(stdout, b"bye");
+    }
+}
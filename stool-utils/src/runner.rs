@@ -0,0 +1,553 @@
+//! External command runners.
+//!
+//! Modules never spawn processes directly; they go through the functions in
+//! this module, which hand the [`Command`] to the active [`CommandRunner`]:
+//! - [`SystemRunner`] - Runs commands (the default)
+//! - [`DryRunRunner`] - Prints commands instead of running them (`--dry-run`)
+//! - [`RecordingRunner`] - Records commands and replays canned results, for tests
+//!
+//! The process-wide runner is chosen once with [`set_default`]; tests can
+//! swap it for the current thread with [`with_runner`].
+
+use log::debug;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::io::{self, Read, Write};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::rc::Rc;
+use std::sync::OnceLock;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use stool_core::time::HumanDuration;
+
+/// Number of trailing stderr lines kept by [`CommandRunner::tee`].
+pub const STDERR_TAIL_LINES: usize = 20;
+
+/// Placeholder printed in place of secret values.
+const REDACTED: &str = "[redacted]";
+/// How often a command with a timeout is checked for having exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Executes external commands on behalf of stool modules.
+pub trait CommandRunner {
+    /// Runs a command with inherited stdio and waits for it to exit.
+    fn status(&self, command: &mut Command) -> io::Result<ExitStatus>;
+
+    /// Runs a command and collects its stdout and stderr.
+    ///
+    /// A command still running after `timeout` is killed and an error of
+    /// kind [`io::ErrorKind::TimedOut`] is returned.
+    fn output(&self, command: &mut Command, timeout: Option<Duration>) -> io::Result<Output>;
+
+    /// Runs a command with inherited stdout, echoing its stderr to the
+    /// terminal while keeping the last [`STDERR_TAIL_LINES`] lines.
+    ///
+    /// `input`, if given, is written to the command's stdin. The returned
+    /// `stdout` is empty and `stderr` holds the kept lines. `timeout` works
+    /// as for [`CommandRunner::output`].
+    fn tee(
+        &self,
+        command: &mut Command,
+        input: Option<&[u8]>,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output>;
+
+    /// Returns true if commands really run, so side effects stool performs
+    /// itself (e.g. a native SSH transfer) should happen too.
+    fn executes(&self) -> bool {
+        false
+    }
+}
+
+/// Runs commands for real.
+#[derive(Debug, Default)]
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn executes(&self) -> bool {
+        true
+    }
+
+    fn status(&self, command: &mut Command) -> io::Result<ExitStatus> {
+        command.status()
+    }
+
+    fn output(&self, command: &mut Command, timeout: Option<Duration>) -> io::Result<Output> {
+        let Some(limit) = timeout else {
+            return command.output();
+        };
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdout = child
+            .stdout
+            .take()
+            .map(|pipe| thread::spawn(|| read_all(pipe)));
+        let stderr = child
+            .stderr
+            .take()
+            .map(|pipe| thread::spawn(|| read_all(pipe)));
+
+        let status = wait(&mut child, Some(limit))?;
+        Ok(Output {
+            status,
+            stdout: join(stdout)?.unwrap_or_default(),
+            stderr: join(stderr)?.unwrap_or_default(),
+        })
+    }
+
+    fn tee(
+        &self,
+        command: &mut Command,
+        input: Option<&[u8]>,
+        timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        if input.is_some() {
+            command.stdin(Stdio::piped());
+        }
+        let mut child = command.stderr(Stdio::piped()).spawn()?;
+
+        if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+            // Dropping stdin closes it so the command sees end of input
+            let written = stdin.write_all(input);
+            drop(stdin);
+            if let Err(e) = written {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(e);
+            }
+        }
+
+        let stderr = child
+            .stderr
+            .take()
+            .map(|pipe| thread::spawn(|| echo_stderr(pipe)));
+        let status = wait(&mut child, timeout)?;
+        Ok(Output {
+            status,
+            stdout: Vec::new(),
+            stderr: join(stderr)?
+                .unwrap_or_default()
+                .into_lines()
+                .join("\n")
+                .into_bytes(),
+        })
+    }
+}
+
+/// Waits for a child, killing it once `timeout` has passed.
+fn wait(child: &mut Child, timeout: Option<Duration>) -> io::Result<ExitStatus> {
+    let Some(limit) = timeout else {
+        return child.wait();
+    };
+    let deadline = Instant::now() + limit;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            // Readers are left behind: a grandchild may still hold the pipes
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("timed out after {}", HumanDuration(limit)),
+            ));
+        }
+        thread::sleep(POLL_INTERVAL.min(deadline - now));
+    }
+}
+
+/// Collects the result of a pipe reader thread.
+fn join<T>(reader: Option<JoinHandle<io::Result<T>>>) -> io::Result<Option<T>> {
+    reader
+        .map(|r| {
+            r.join()
+                .map_err(|_| io::Error::other("pipe reader panicked"))?
+        })
+        .transpose()
+}
+
+fn read_all(mut pipe: impl Read) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    pipe.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Copies a child's stderr to ours, keeping its last lines.
+fn echo_stderr(mut pipe: impl Read) -> io::Result<StderrTail> {
+    let mut tail = StderrTail::default();
+    let mut terminal = io::stderr();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = match pipe.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        // Echo failures must not abort the command being watched
+        let _ = terminal.write_all(&buf[..n]);
+        tail.push(&buf[..n]);
+    }
+    // Keep our own output off an unterminated last line
+    if !tail.partial.is_empty() {
+        let _ = terminal.write_all(b"\n");
+    }
+    Ok(tail)
+}
+
+/// Prints commands instead of running them.
+///
+/// Every command succeeds with empty output. Environment values and stdin
+/// input are redacted, since they are how secrets reach commands.
+#[derive(Debug, Default)]
+pub struct DryRunRunner;
+
+impl DryRunRunner {
+    fn print(command: &Command, input: Option<&[u8]>) -> Output {
+        println!("{}", Self::line(command, input));
+        success()
+    }
+
+    /// Formats the line printed for a command.
+    fn line(command: &Command, input: Option<&[u8]>) -> String {
+        let mut line = format!("[dry-run] {}", describe(command));
+        if input.is_some() {
+            line.push_str(&format!(" < {}", REDACTED));
+        }
+        line
+    }
+}
+
+impl CommandRunner for DryRunRunner {
+    fn status(&self, command: &mut Command) -> io::Result<ExitStatus> {
+        Ok(Self::print(command, None).status)
+    }
+
+    fn output(&self, command: &mut Command, _timeout: Option<Duration>) -> io::Result<Output> {
+        Ok(Self::print(command, None))
+    }
+
+    fn tee(
+        &self,
+        command: &mut Command,
+        input: Option<&[u8]>,
+        _timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        Ok(Self::print(command, input))
+    }
+}
+
+/// Records commands without running them and replays queued results.
+///
+/// Each call takes the next result queued with [`RecordingRunner::push`];
+/// once the queue is empty, commands succeed with empty output.
+#[derive(Debug, Default)]
+pub struct RecordingRunner {
+    commands: RefCell<Vec<String>>,
+    inputs: RefCell<Vec<Option<Vec<u8>>>>,
+    results: RefCell<VecDeque<Output>>,
+}
+
+impl RecordingRunner {
+    /// Creates a runner with no queued results.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues the result returned for the next command.
+    pub fn push(&self, output: Output) {
+        self.results.borrow_mut().push_back(output);
+    }
+
+    /// Returns the commands run so far, formatted as by [`describe`].
+    pub fn commands(&self) -> Vec<String> {
+        self.commands.borrow().clone()
+    }
+
+    /// Returns the stdin input given to each command run so far.
+    pub fn inputs(&self) -> Vec<Option<Vec<u8>>> {
+        self.inputs.borrow().clone()
+    }
+
+    fn record(&self, command: &Command, input: Option<&[u8]>) -> Output {
+        self.commands.borrow_mut().push(describe(command));
+        self.inputs.borrow_mut().push(input.map(<[u8]>::to_vec));
+        self.results
+            .borrow_mut()
+            .pop_front()
+            .unwrap_or_else(success)
+    }
+}
+
+impl CommandRunner for RecordingRunner {
+    fn status(&self, command: &mut Command) -> io::Result<ExitStatus> {
+        Ok(self.record(command, None).status)
+    }
+
+    fn output(&self, command: &mut Command, _timeout: Option<Duration>) -> io::Result<Output> {
+        Ok(self.record(command, None))
+    }
+
+    fn tee(
+        &self,
+        command: &mut Command,
+        input: Option<&[u8]>,
+        _timeout: Option<Duration>,
+    ) -> io::Result<Output> {
+        Ok(self.record(command, input))
+    }
+}
+
+static DEFAULT: OnceLock<Box<dyn CommandRunner + Send + Sync>> = OnceLock::new();
+
+thread_local! {
+    static OVERRIDE: RefCell<Option<Rc<dyn CommandRunner>>> = const { RefCell::new(None) };
+}
+
+/// Sets the process-wide runner; [`SystemRunner`] is used if never called.
+///
+/// Only the first call has an effect.
+pub fn set_default(runner: Box<dyn CommandRunner + Send + Sync>) {
+    let _ = DEFAULT.set(runner);
+}
+
+/// Runs `f` with `runner` handling every command started on this thread.
+pub fn with_runner<T>(runner: Rc<dyn CommandRunner>, f: impl FnOnce() -> T) -> T {
+    let previous = OVERRIDE.with(|o| o.replace(Some(runner)));
+    let result = f();
+    OVERRIDE.with(|o| *o.borrow_mut() = previous);
+    result
+}
+
+fn dispatch<T>(f: impl FnOnce(&dyn CommandRunner) -> T) -> T {
+    match OVERRIDE.with(|o| o.borrow().clone()) {
+        Some(runner) => f(runner.as_ref()),
+        None => f(DEFAULT.get_or_init(|| Box::new(SystemRunner)).as_ref()),
+    }
+}
+
+/// Runs a command with inherited stdio using the active runner.
+pub fn status(command: &mut Command) -> io::Result<ExitStatus> {
+    logged(
+        command,
+        |runner, command| runner.status(command),
+        |status| *status,
+    )
+}
+
+/// Runs a command and collects its output using the active runner.
+///
+/// See [`CommandRunner::output`].
+pub fn output(command: &mut Command, timeout: Option<Duration>) -> io::Result<Output> {
+    logged(
+        command,
+        |runner, command| runner.output(command, timeout),
+        |output| output.status,
+    )
+}
+
+/// Runs a command, teeing its stderr, using the active runner.
+///
+/// See [`CommandRunner::tee`].
+pub fn tee(
+    command: &mut Command,
+    input: Option<&[u8]>,
+    timeout: Option<Duration>,
+) -> io::Result<Output> {
+    logged(
+        command,
+        |runner, command| runner.tee(command, input, timeout),
+        |output| output.status,
+    )
+}
+
+/// Runs a command on the active runner, logging it and its outcome at debug level.
+fn logged<T>(
+    command: &mut Command,
+    run: impl FnOnce(&dyn CommandRunner, &mut Command) -> io::Result<T>,
+    status: impl FnOnce(&T) -> ExitStatus,
+) -> io::Result<T> {
+    let line = log::log_enabled!(log::Level::Debug).then(|| describe(command));
+    if let Some(line) = &line {
+        debug!("Running: {}", line);
+    }
+    let started = Instant::now();
+    let result = dispatch(|runner| run(runner, command));
+    if let Some(line) = &line {
+        match &result {
+            Ok(value) => debug!(
+                "Finished in {:.1?} ({}): {}",
+                started.elapsed(),
+                status(value),
+                line
+            ),
+            Err(e) => debug!("Failed after {:.1?} ({}): {}", started.elapsed(), e, line),
+        }
+    }
+    result
+}
+
+/// Returns true if the active runner really runs commands.
+pub fn executes() -> bool {
+    dispatch(|runner| runner.executes())
+}
+
+/// Formats a command as a shell command line.
+///
+/// Environment variables set on the command are shown with their values
+/// redacted.
+pub fn describe(command: &Command) -> String {
+    let env = command.get_envs().filter_map(|(key, value)| {
+        value.map(|_| format!("{}={}", key.to_string_lossy(), REDACTED))
+    });
+    let words: Vec<String> = std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(OsStr::to_string_lossy)
+        .map(|word| crate::command::shell_quote(&word))
+        .collect();
+    env.chain(words).collect::<Vec<_>>().join(" ")
+}
+
+fn success() -> Output {
+    Output {
+        status: ExitStatus::default(),
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+    }
+}
+
+/// Keeps the last lines of a stderr stream fed in arbitrary chunks.
+#[derive(Default)]
+pub(crate) struct StderrTail {
+    lines: VecDeque<String>,
+    partial: Vec<u8>,
+    carriage_return: bool,
+}
+
+impl StderrTail {
+    pub(crate) fn push(&mut self, chunk: &[u8]) {
+        for &byte in chunk {
+            // Progress output redraws a line after '\r'; keep only the final state
+            match byte {
+                b'\n' => {
+                    let line = std::mem::take(&mut self.partial);
+                    self.push_line(&line);
+                    self.carriage_return = false;
+                }
+                b'\r' => self.carriage_return = true,
+                _ => {
+                    if std::mem::take(&mut self.carriage_return) {
+                        self.partial.clear();
+                    }
+                    self.partial.push(byte);
+                }
+            }
+        }
+    }
+
+    fn push_line(&mut self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line).trim_end().to_string();
+        if line.is_empty() {
+            return;
+        }
+        if self.lines.len() == STDERR_TAIL_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    pub(crate) fn into_lines(mut self) -> Vec<String> {
+        let partial = std::mem::take(&mut self.partial);
+        self.push_line(&partial);
+        self.lines.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stderr_tail_keeps_the_last_lines() {
+        let mut tail = StderrTail::default();
+        for i in 0..STDERR_TAIL_LINES + 5 {
+            tail.push(format!("line {}\n", i).as_bytes());
+        }
+
+        let lines = tail.into_lines();
+
+        assert_eq!(lines.len(), STDERR_TAIL_LINES);
+        assert_eq!(lines[0], "line 5");
+        assert_eq!(
+            lines[STDERR_TAIL_LINES - 1],
+            format!("line {}", STDERR_TAIL_LINES + 4)
+        );
+    }
+
+    #[test]
+    fn stderr_tail_keeps_the_final_state_of_progress_lines() {
+        let mut tail = StderrTail::default();
+
+        tail.push(b"Pushing  10%\rPushing  55%\rPushing 100%\ndone\r\n");
+
+        assert_eq!(tail.into_lines(), ["Pushing 100%", "done"]);
+    }
+
+    #[test]
+    fn stderr_tail_joins_lines_split_across_chunks() {
+        let mut tail = StderrTail::default();
+
+        tail.push(b"denied: token ");
+        tail.push(b"expired\nretry");
+
+        assert_eq!(tail.into_lines(), ["denied: token expired", "retry"]);
+    }
+
+    #[test]
+    fn stderr_tail_skips_blank_lines() {
+        let mut tail = StderrTail::default();
+
+        tail.push(b"\n  \nerror: failed  \n\n");
+
+        assert_eq!(tail.into_lines(), ["error: failed"]);
+    }
+
+    #[test]
+    fn dry_run_redacts_environment_values() {
+        let mut command = Command::new("aws");
+        command
+            .args(["ecr", "get-login-password"])
+            .env("AWS_SECRET_ACCESS_KEY", "s3cret");
+
+        let line = DryRunRunner::line(&command, None);
+
+        assert_eq!(
+            line,
+            "[dry-run] AWS_SECRET_ACCESS_KEY=[redacted] aws ecr get-login-password"
+        );
+    }
+
+    #[test]
+    fn dry_run_redacts_stdin() {
+        let mut command = Command::new("docker");
+        command.args(["login", "--password-stdin"]);
+
+        let line = DryRunRunner::line(&command, Some(b"s3cret"));
+
+        assert_eq!(line, "[dry-run] docker login --password-stdin < [redacted]");
+    }
+
+    #[test]
+    fn commands_are_described_with_shell_quoting() {
+        let mut command = Command::new("ssh");
+        command.args(["deploy@10.0.0.1", "--", "uptime; df -h"]);
+
+        assert_eq!(describe(&command), "ssh deploy@10.0.0.1 -- 'uptime; df -h'");
+    }
+}
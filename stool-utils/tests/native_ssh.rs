@@ -0,0 +1,145 @@
+//! Native SSH client tests against a local sshd.
+//!
+//! Ignored by default. Start an sshd whose host key is in
+//! `~/.ssh/known_hosts`, then run:
+//!
+//! ```text
+//! STOOL_TEST_SSH_USER=tester STOOL_TEST_SSH_PASSWORD=secret \
+//! STOOL_TEST_SSH_KEY=~/.ssh/stool_test STOOL_TEST_SSH_PORT=2222 \
+//!     cargo test -p stool-utils --features native-ssh -- --ignored
+//! ```
+//!
+//! `STOOL_TEST_SSH_HOST` defaults to `127.0.0.1`. Key and agent tests are
+//! skipped unless `STOOL_TEST_SSH_KEY` / `SSH_AUTH_SOCK` are set.
+
+#![cfg(feature = "native-ssh")]
+
+use std::env;
+use std::fs;
+use stool_core::error::StoolErrorType;
+use stool_utils::interactive::ServerInfo;
+use stool_utils::native::Connection;
+
+fn server() -> ServerInfo {
+    ServerInfo {
+        user: env::var("STOOL_TEST_SSH_USER").expect("STOOL_TEST_SSH_USER not set"),
+        ip: env::var("STOOL_TEST_SSH_HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
+        port: env::var("STOOL_TEST_SSH_PORT")
+            .ok()
+            .map(|p| p.parse().expect("STOOL_TEST_SSH_PORT is not a port")),
+        ..ServerInfo::default()
+    }
+}
+
+fn password_server() -> ServerInfo {
+    ServerInfo {
+        password: Some(
+            env::var("STOOL_TEST_SSH_PASSWORD")
+                .expect("STOOL_TEST_SSH_PASSWORD not set")
+                .into(),
+        ),
+        ..server()
+    }
+}
+
+#[test]
+#[ignore = "needs a local sshd"]
+fn password_auth_runs_command() {
+    let connection = Connection::open(&password_server()).unwrap();
+    let output = connection.exec("echo out; echo err >&2; exit 3").unwrap();
+
+    assert_eq!(output.stdout, b"out\n");
+    assert_eq!(output.stderr, b"err\n");
+    assert_eq!(output.exit_status, 3);
+    assert!(!output.success());
+}
+
+#[test]
+#[ignore = "needs a local sshd"]
+fn large_stderr_does_not_stall_stdout() {
+    // More than a channel window on stderr before anything on stdout
+    let connection = Connection::open(&password_server()).unwrap();
+    let output = connection
+        .exec("head -c 4000000 /dev/zero >&2; echo done")
+        .unwrap();
+
+    assert_eq!(output.stderr.len(), 4_000_000);
+    assert_eq!(output.stdout, b"done\n");
+}
+
+#[test]
+#[ignore = "needs a local sshd"]
+fn exec_closes_stdin() {
+    let output = Connection::open(&password_server())
+        .unwrap()
+        .exec("cat; echo closed")
+        .unwrap();
+
+    assert_eq!(output.stdout, b"closed\n");
+}
+
+#[test]
+#[ignore = "needs a local sshd"]
+fn wrong_password_is_rejected() {
+    let server = ServerInfo {
+        password: Some("not-the-password".to_string().into()),
+        ..server()
+    };
+    let error = Connection::open(&server).err().unwrap();
+
+    assert_eq!(error.error_type, StoolErrorType::SshAuthenticationFailed);
+}
+
+#[test]
+#[ignore = "needs a local sshd"]
+fn key_auth_runs_command() {
+    let Ok(key) = env::var("STOOL_TEST_SSH_KEY") else {
+        return;
+    };
+    let server = ServerInfo {
+        key_path: Some(key),
+        ..server()
+    };
+    let output = Connection::open(&server).unwrap().exec("true").unwrap();
+
+    assert!(output.success());
+}
+
+#[test]
+#[ignore = "needs a local sshd"]
+fn agent_auth_runs_command() {
+    if env::var_os("SSH_AUTH_SOCK").is_none() {
+        return;
+    }
+    let output = Connection::open(&server()).unwrap().exec("true").unwrap();
+
+    assert!(output.success());
+}
+
+#[test]
+#[ignore = "needs a local sshd"]
+fn upload_and_download_round_trip() {
+    let connection = Connection::open(&password_server()).unwrap();
+    let local_dir = env::temp_dir().join(format!("stool-native-test-{}", std::process::id()));
+    fs::create_dir_all(&local_dir).unwrap();
+    let source = local_dir.join("upload.bin");
+    let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(&source, &content).unwrap();
+
+    let mut last = (0, 0);
+    let remote = connection
+        .upload(&source, "~/", &mut |done, total| last = (done, total))
+        .unwrap();
+    assert_eq!(remote.to_str(), Some("./upload.bin"));
+    assert_eq!(last, (content.len() as u64, content.len() as u64));
+
+    let download_dir = local_dir.join("download");
+    fs::create_dir_all(&download_dir).unwrap();
+    let local = connection
+        .download("~/upload.bin", &download_dir, &mut |_, _| {})
+        .unwrap();
+    assert_eq!(fs::read(&local).unwrap(), content);
+
+    connection.exec("rm -f upload.bin").unwrap();
+    fs::remove_dir_all(&local_dir).unwrap();
+}